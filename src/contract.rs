@@ -1,17 +1,74 @@
 #[cfg(not(feature = "library"))]
 use cosmwasm_std::entry_point;
-use cosmwasm_std::{to_json_binary, BankMsg, Binary, Coin, CosmosMsg, Deps, DepsMut, Env, MessageInfo, Response, StdResult, Uint128};
-use cw2::set_contract_version;
+use cosmwasm_std::{to_json_binary, Addr, Api, BankMsg, Binary, Coin, CosmosMsg, Deps, DepsMut, Env, Event, IbcMsg, MessageInfo, Reply, Response, Storage, StdError, StdResult, SubMsg, SubMsgResult, Timestamp, Uint128};
+use bech32::{ToBase32, Variant};
+use cw2::{get_contract_version, set_contract_version};
+use ripemd::Ripemd160;
+use semver::Version;
+use sha2::{Digest, Sha256};
 
 use crate::ContractResult;
 use crate::error::ContractError;
-use crate::msg::{EncryptionKeyResponse, ExecuteMsg, InstantiateMsg, QueryMsg};
-use crate::state::{find_senders, load_enc_key, load_fees, load_note_meta, load_notes, save_enc_key, save_fees, store_note, Fees, Note};
+use crate::events::{
+  key_hash, AdminChanged, AdminTransferCancelled, AdminTransferProposed, CounterpartyChanged, CreditsDeposited,
+  CreditsWithdrawn, FeeTierSet, FeeUpdateCancelled, FeeUpdateScheduled, FeesUpdated, FeesWithdrawn, KeyUpdated,
+  NoteRelayed, NoteStored, NotesRead, PauseChanged, PostageSet, ReceiveHookFailed, ReceiveHookSet, RemoteKeyRequested,
+  RemoteNoteSent, RoleChanged, SealedNoteStored, SponsorUpdated, SponsorshipAccepted, SponsorshipsRemoved,
+  SubscriberAdded, SubscriberFailed, SubscriberRemoved, SubscriptionDepositSet,
+};
+use crate::migrations;
+use crate::pagination::{page_limit, paginate};
+use crate::msg::{
+  BeneficiaryMsg, ChannelInfo, ChannelsResponse, Conversation, ConversationOrder, ConversationsResponse,
+  CounterpartiesResponse, CounterpartyInfo, CreditsResponse, DenomFeeStats, DropnoteReceiveMsg, EffectiveFeesResponse,
+  EncryptionKeyEntry, EncryptionKeyResponse, EncryptionKeysResponse, ExecuteMsg, FeeBalanceResponse, FeeStatsResponse,
+  FeesMsg, FeesResponse, InstantiateMsg, KeyLookupRequest, MigrateMsg, NoteCountEntry, NoteCountsResponse, NotePacket,
+  NotePair, NoteRange, NoteRef, NoteStoredHook, NotesResponse, PacketMsg, PausedResponse, PostageResponse, QueryMsg,
+  ReceiveHookResponse, RelaySignDoc, RemoteEncryptionKeyResponse, RoleMembersResponse, RolesResponse,
+  SealedNotesResponse, SenderSummary, SendersResponse, SignedNote, SponsorResponse, SponsorshipResponse, StatsResponse,
+  SubscriberEntry, SubscribersResponse, SubscriptionDepositResponse, SudoMsg,
+};
+use crate::state::{
+  add_credits, add_fee_balance, add_subscriber, apply_scheduled_fees, count_sponsorships, find_note_index_by_height,
+  find_note_index_by_time, find_note_meta, find_recent_senders, find_role_members, find_senders, find_sponsorships,
+  find_subscribers, grant_role, has_role, has_sponsorship_offer, is_paused, load_channel, load_channel_chain,
+  load_channels, load_counterparties, load_credit, load_credits, load_enc_key, load_fee_balances, load_fee_stats,
+  load_fee_tier, load_fees, load_note, load_note_meta, load_notes, load_ownership, load_postage, load_receive_hook,
+  load_relay_nonce, load_remote_key, load_scheduled_fees, load_sealed_count, load_sealed_notes, load_sponsor,
+  load_sponsorship, load_stats, load_subscriber, load_subscriber_by_id, load_subscription_deposit, next_packet_id,
+  remove_counterparty, remove_sponsorship, remove_sponsorship_offer, remove_subscriber, revoke_role, save_counterparty,
+  save_enc_key, save_escrow, save_fee_tier, save_fees, save_note_meta, save_ownership, save_postage, save_receive_hook,
+  save_relay_nonce, save_scheduled_fees, save_sponsor, save_sponsorship, save_sponsorship_offer, save_subscriber,
+  save_subscription_deposit, set_paused, store_note, store_sealed_note, sub_credits, sub_fee_balance, update_fee_stats,
+  Beneficiary, Counterparty, Escrow, FeeTier, Fees, HookFailure, Note, Operation, Ownership, ReceiveHook, Role,
+  ScheduledFees, SealedNote, Sponsor, Subscriber,
+};
 
 // version info for migration info
 const CONTRACT_NAME: &str = env!("CARGO_PKG_NAME");
 const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
 
+const BPS_DENOMINATOR: u16 = 10_000;
+// number of leading note bytes included in conversation previews
+const NOTE_PREVIEW_LEN: usize = 64;
+// maximum number of entries in batch queries
+const MAX_BATCH_SIZE: usize = 30;
+// storage entries migrated per call unless specified otherwise
+const DEFAULT_MIGRATION_LIMIT: usize = 200;
+
+const RECEIVE_HOOK_REPLY_ID: u64 = 1;
+// subscriber notifications reply with this offset plus the subscriber id
+const SUBSCRIBER_REPLY_ID_OFFSET: u64 = 1 << 32;
+// every stored note may cost its sender up to `MAX_SUBSCRIBERS * MAX_SUBSCRIBER_GAS_LIMIT` gas
+const MAX_SUBSCRIBERS: usize = 5;
+const MAX_SUBSCRIBER_GAS_LIMIT: u64 = 200_000;
+// self-registered subscribers may only take this many slots, the rest are left to moderators
+const MAX_SELF_REGISTERED_SUBSCRIBERS: usize = 2;
+// consecutive failed notifications after which a subscriber is removed
+const MAX_SUBSCRIBER_FAILURES: u32 = 3;
+// bounds the sponsorships tried when charging a sender's fee
+const MAX_SPONSORS_PER_SENDER: usize = 5;
+
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn instantiate(
   deps: DepsMut,
@@ -21,21 +78,72 @@ pub fn instantiate(
 ) -> ContractResult<Response> {
   set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
 
+  let admin = match msg.admin {
+    Some(admin) => deps.api.addr_validate(&admin)?,
+    None => info.sender,
+  };
+  save_ownership(deps.storage, &Ownership {
+    owner: Some(admin.clone()),
+    pending_owner: None,
+    pending_expiry: None,
+  })?;
   save_fees(deps.storage, &Fees {
-    admin: Some(info.sender.clone()),
     store_keys: msg.store_keys_fee,
     store_notes: msg.store_notes_fee,
     denom: msg.denom,
     burn_fees: false,
+    beneficiaries: vec![],
+    accumulate: false,
+    update_delay: 0,
   })?;
 
   Ok(Response::new()
     .add_attribute("method", "instantiate")
+    .add_attribute("admin", admin)
     .add_attribute("new_store_keys_fee", msg.store_keys_fee.to_string())
     .add_attribute("new_store_notes_fee", msg.store_notes_fee.to_string())
   )
 }
 
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn migrate(deps: DepsMut, _env: Env, msg: MigrateMsg) -> ContractResult<Response> {
+  let stored = get_contract_version(deps.storage)?;
+  if stored.contract != CONTRACT_NAME {
+    return Err(ContractError::InvalidMigration { contract: stored.contract });
+  }
+
+  let from = Version::parse(&stored.version)?;
+  let to = Version::parse(CONTRACT_VERSION)?;
+  if from > to {
+    return Err(ContractError::Downgrade { from: from.to_string(), to: to.to_string() });
+  }
+
+  // the stored version is only bumped once all batches are through
+  let limit = msg.limit.map_or(DEFAULT_MIGRATION_LIMIT, |limit| limit as usize);
+  let complete = migrations::run(deps.storage, &from, limit)?;
+  if complete {
+    set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
+  }
+
+  Ok(Response::new()
+    .add_attribute("method", "migrate")
+    .add_attribute("from_version", from.to_string())
+    .add_attribute("to_version", to.to_string())
+    .add_attribute("complete", complete.to_string())
+  )
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn sudo(deps: DepsMut, env: Env, msg: SudoMsg) -> ContractResult<Response> {
+  use SudoMsg::*;
+  match msg {
+    SetAdmin { address } => sudo_set_admin(deps, address),
+    Pause { operations } => sudo_set_paused(deps, operations, true),
+    Unpause { operations } => sudo_set_paused(deps, operations, false),
+    SetFees { fees } => sudo_set_fees(deps, env, fees),
+  }
+}
+
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn execute(
   deps: DepsMut,
@@ -44,395 +152,3116 @@ pub fn execute(
   msg: ExecuteMsg,
 ) -> ContractResult<Response> {
   use ExecuteMsg::*;
+  if migrations::is_pending(deps.storage) {
+    return Err(ContractError::MigrationPending {});
+  }
+  if let Some(operation) = write_operation(&msg) {
+    if is_paused(deps.storage, operation) {
+      return Err(ContractError::Paused { operation });
+    }
+  }
+
   let ctx = ExecuteContext { deps, env, info };
   match msg {
-    UpdateFees { admin, store_keys, store_notes, denom, burn_fees } =>
-      exec_update_fees(ctx, admin, store_keys, store_notes, denom, burn_fees),
+    ProposeAdmin { address, expiry } => exec_propose_admin(ctx, address, expiry),
+    AcceptAdmin {} => exec_accept_admin(ctx),
+    CancelAdminTransfer {} => exec_cancel_admin_transfer(ctx),
+    RenounceAdmin {} => exec_renounce_admin(ctx),
+    GrantRole { address, role } => exec_grant_role(ctx, address, role),
+    RevokeRole { address, role } => exec_revoke_role(ctx, address, role),
+    Pause { operations } => exec_set_paused(ctx, operations, true),
+    Unpause { operations } => exec_set_paused(ctx, operations, false),
+    UpdateFees { store_keys, store_notes, denom, burn_fees, beneficiaries, accumulate } =>
+      exec_update_fees(ctx, FeesMsg { store_keys, store_notes, denom, burn_fees, beneficiaries, accumulate, update_delay: 0 }),
+    ScheduleFeeUpdate { fees, effective_at } => exec_schedule_fee_update(ctx, fees, effective_at),
+    CancelFeeUpdate {} => exec_cancel_fee_update(ctx),
+    SetFeeTier { address, tier } => exec_set_fee_tier(ctx, address, tier),
+    WithdrawFees { amount, to } => exec_withdraw_fees(ctx, amount, to),
+    Deposit {} => exec_deposit(ctx),
+    Withdraw { amount } => exec_withdraw(ctx, amount),
+    Sponsor { senders, per_sender_cap, expires } => exec_sponsor(ctx, senders, per_sender_cap, expires),
+    Unsponsor { senders } => exec_unsponsor(ctx, senders),
+    AcceptSponsorship { sponsor } => exec_accept_sponsorship(ctx, sponsor),
+    RemoveSponsor { sponsor } => exec_remove_sponsor(ctx, sponsor),
     UpdateKey { key } => exec_update_key(ctx, key),
     StoreNote { recipient, note } => exec_store_note(ctx, recipient, note),
+    SetReceiveHook { hook } => exec_set_receive_hook(ctx, hook),
+    MarkRead { sender, until } => exec_mark_read(ctx, sender, until),
+    AddSubscriber { address, gas_limit } => exec_add_subscriber(ctx, address, gas_limit),
+    RemoveSubscriber { address } => exec_remove_subscriber(ctx, address),
+    Subscribe { gas_limit } => exec_subscribe(ctx, gas_limit),
+    Unsubscribe {} => exec_unsubscribe(ctx),
+    SetSubscriptionDeposit { deposit } => exec_set_subscription_deposit(ctx, deposit),
+    StoreRemoteNote { channel, recipient, note } => exec_store_remote_note(ctx, channel, recipient, note),
+    AllowCounterparty { connection_id, port_id, chain_id } => exec_allow_counterparty(ctx, connection_id, port_id, chain_id),
+    RemoveCounterparty { connection_id } => exec_remove_counterparty(ctx, connection_id),
+    LookupRemoteKey { channel, address } => exec_lookup_remote_key(ctx, channel, address),
+    RelayNote { signed } => exec_relay_note(ctx, signed),
+    StoreSealedNote { recipient, envelope } => exec_store_sealed_note(ctx, recipient, envelope),
+    SetPostage { postage } => exec_set_postage(ctx, postage),
+  }
+}
+
+/// Map an execute message to the write operation it performs, if it can be paused. Deliberately
+/// exhaustive, such that every new message must be classified.
+fn write_operation(msg: &ExecuteMsg) -> Option<Operation> {
+  use ExecuteMsg::*;
+  match msg {
+    StoreNote { .. } | StoreRemoteNote { .. } | RelayNote { .. } | StoreSealedNote { .. } => Some(Operation::StoreNote),
+    UpdateKey { .. } => Some(Operation::UpdateKey),
+    Deposit { .. } => Some(Operation::Deposit),
+    Withdraw { .. } => Some(Operation::Withdraw),
+    Sponsor { .. } | Unsponsor { .. } | AcceptSponsorship { .. } | RemoveSponsor { .. } => Some(Operation::Sponsor),
+    WithdrawFees { .. } => Some(Operation::WithdrawFees),
+    SetReceiveHook { .. } => Some(Operation::SetReceiveHook),
+    MarkRead { .. } => Some(Operation::MarkRead),
+    Subscribe { .. } | Unsubscribe { .. } | AddSubscriber { .. } | RemoveSubscriber { .. } => Some(Operation::Subscribe),
+    LookupRemoteKey { .. } => Some(Operation::LookupRemoteKey),
+    SetPostage { .. } => Some(Operation::SetPostage),
+    // administrative messages remain available to resolve incidents while paused
+    ProposeAdmin { .. } | AcceptAdmin { .. } | CancelAdminTransfer { .. } | RenounceAdmin { .. } | GrantRole { .. }
+    | RevokeRole { .. } | Pause { .. } | Unpause { .. } | UpdateFees { .. } | ScheduleFeeUpdate { .. }
+    | CancelFeeUpdate { .. } | SetFeeTier { .. } | SetSubscriptionDeposit { .. } | AllowCounterparty { .. }
+    | RemoveCounterparty { .. } => None,
+  }
+}
+
+fn exec_propose_admin(ctx: ExecuteContext, address: String, expiry: Option<Timestamp>) -> ContractResult<Response> {
+  let mut ownership = ensure_admin(&ctx)?;
+  if expiry.is_some_and(|expiry| ctx.env.block.time >= expiry) {
+    return Err(ContractError::AdminTransferExpired {});
+  }
+
+  let address = ctx.deps.api.addr_validate(address.as_str())?;
+  ownership.pending_owner = Some(address.clone());
+  ownership.pending_expiry = expiry;
+  save_ownership(ctx.deps.storage, &ownership)?;
+
+  Ok(Response::new()
+    .add_event(AdminTransferProposed { pending_admin: address.clone(), expiry }.into())
+    .add_attribute("method", "propose_admin")
+    .add_attribute("pending_admin", address)
+  )
+}
+
+fn exec_accept_admin(ctx: ExecuteContext) -> ContractResult<Response> {
+  let mut ownership = load_ownership(ctx.deps.storage)?;
+  match &ownership.pending_owner {
+    Some(pending) if *pending == ctx.info.sender => {}
+    Some(_) => return Err(ContractError::NotPendingAdmin {}),
+    None => return Err(ContractError::NoPendingAdmin {}),
+  }
+  if ownership.pending_expiry.is_some_and(|expiry| ctx.env.block.time >= expiry) {
+    return Err(ContractError::AdminTransferExpired {});
   }
+
+  let previous_admin = ownership.owner.take();
+  ownership.owner = ownership.pending_owner.take();
+  ownership.pending_expiry = None;
+  save_ownership(ctx.deps.storage, &ownership)?;
+
+  Ok(Response::new()
+    .add_event(AdminChanged { previous_admin, admin: ownership.owner }.into())
+    .add_attribute("method", "accept_admin")
+    .add_attribute("admin", ctx.info.sender)
+  )
 }
 
-fn exec_update_fees(ctx: ExecuteContext, admin: Option<String>, store_keys: Uint128, store_notes: Uint128, denom: String, burn_fees: bool) -> ContractResult<Response> {
-  let fees = load_fees(ctx.deps.storage)?;
-  if fees.admin.is_none() || fees.admin.unwrap() != ctx.info.sender {
-    return Err(ContractError::Unauthorized {});
+fn exec_cancel_admin_transfer(ctx: ExecuteContext) -> ContractResult<Response> {
+  let mut ownership = ensure_admin(&ctx)?;
+  if ownership.pending_owner.is_none() {
+    return Err(ContractError::NoPendingAdmin {});
   }
 
-  let admin = admin.map(|a| ctx.deps.api.addr_validate(a.as_str())).transpose()?;
-  save_fees(ctx.deps.storage, &Fees {
-    admin,
-    store_keys,
-    store_notes,
-    denom,
-    burn_fees,
+  ownership.pending_owner = None;
+  ownership.pending_expiry = None;
+  save_ownership(ctx.deps.storage, &ownership)?;
+
+  Ok(Response::new()
+    .add_event(AdminTransferCancelled.into())
+    .add_attribute("method", "cancel_admin_transfer")
+  )
+}
+
+fn exec_renounce_admin(ctx: ExecuteContext) -> ContractResult<Response> {
+  let ownership = ensure_admin(&ctx)?;
+  save_ownership(ctx.deps.storage, &Ownership {
+    owner: None,
+    pending_owner: None,
+    pending_expiry: None,
   })?;
-  Ok(Response::new().add_attribute("method", "update_fees"))
+
+  Ok(Response::new()
+    .add_event(AdminChanged { previous_admin: ownership.owner, admin: None }.into())
+    .add_attribute("method", "renounce_admin")
+  )
 }
 
-fn exec_update_key(ctx: ExecuteContext, key: String) -> ContractResult<Response> {
-  let fees = load_fees(ctx.deps.storage)?;
-  let mut msgs: Vec<CosmosMsg> = vec![];
+fn exec_grant_role(ctx: ExecuteContext, address: String, role: Role) -> ContractResult<Response> {
+  ensure_admin(&ctx)?;
+  if role == Role::Owner {
+    return Err(ContractError::InvalidRole { role });
+  }
 
-  if fees.store_keys > Uint128::zero() {
-    let coin = find_coin(&fees.denom, &ctx.info.funds);
-    match coin {
-      Some(coin) => {
-        if coin.amount < fees.store_keys {
-          return Err(ContractError::InsufficientFunds {});
-        }
-        msgs.push(get_fee_msg(&fees, &coin));
-      }
-      None => return Err(ContractError::InsufficientFunds {}),
+  let address = ctx.deps.api.addr_validate(address.as_str())?;
+  grant_role(ctx.deps.storage, role, address.clone())?;
+  Ok(Response::new()
+    .add_event(RoleChanged { role, address: address.clone(), granted: true }.into())
+    .add_attribute("method", "grant_role")
+    .add_attribute("role", role.as_str())
+    .add_attribute("address", address)
+  )
+}
+
+fn exec_revoke_role(ctx: ExecuteContext, address: String, role: Role) -> ContractResult<Response> {
+  ensure_admin(&ctx)?;
+  if role == Role::Owner {
+    return Err(ContractError::InvalidRole { role });
+  }
+
+  let address = ctx.deps.api.addr_validate(address.as_str())?;
+  revoke_role(ctx.deps.storage, role, address.clone());
+  Ok(Response::new()
+    .add_event(RoleChanged { role, address: address.clone(), granted: false }.into())
+    .add_attribute("method", "revoke_role")
+    .add_attribute("role", role.as_str())
+    .add_attribute("address", address)
+  )
+}
+
+fn exec_set_paused(ctx: ExecuteContext, operations: Option<Vec<Operation>>, paused: bool) -> ContractResult<Response> {
+  ensure_role(&ctx, Role::Pauser)?;
+  let operations = pause_operations(ctx.deps.storage, operations, paused)?;
+
+  Ok(Response::new()
+    .add_attribute("method", if paused { "pause" } else { "unpause" })
+    .add_attribute("operations", join_operations(&operations))
+    .add_event(PauseChanged { operations, paused }.into())
+  )
+}
+
+fn sudo_set_admin(deps: DepsMut, address: Option<String>) -> ContractResult<Response> {
+  let previous_admin = load_ownership(deps.storage)?.owner;
+  let owner = address.map(|address| deps.api.addr_validate(address.as_str())).transpose()?;
+  save_ownership(deps.storage, &Ownership {
+    owner: owner.clone(),
+    pending_owner: None,
+    pending_expiry: None,
+  })?;
+
+  let admin = owner.clone().map(String::from).unwrap_or_default();
+  Ok(Response::new()
+    .add_event(Event::new("sudo").add_attribute("action", "set_admin").add_attribute("admin", admin.as_str()))
+    .add_event(AdminChanged { previous_admin, admin: owner }.into())
+    .add_attribute("method", "sudo_set_admin")
+    .add_attribute("admin", admin)
+  )
+}
+
+fn sudo_set_paused(deps: DepsMut, operations: Option<Vec<Operation>>, paused: bool) -> ContractResult<Response> {
+  let operations = pause_operations(deps.storage, operations, paused)?;
+  let action = if paused { "pause" } else { "unpause" };
+
+  let joined = join_operations(&operations);
+  Ok(Response::new()
+    .add_event(Event::new("sudo").add_attribute("action", action).add_attribute("operations", joined.as_str()))
+    .add_event(PauseChanged { operations, paused }.into())
+    .add_attribute("method", format!("sudo_{action}"))
+    .add_attribute("operations", joined)
+  )
+}
+
+fn sudo_set_fees(deps: DepsMut, env: Env, fees: FeesMsg) -> ContractResult<Response> {
+  let old = load_fees(deps.storage, env.block.time)?;
+  let cancelled = load_scheduled_fees(deps.storage, env.block.time)?.is_some();
+  let fees = validate_fees(deps.api, fees)?;
+  save_fees(deps.storage, &fees)?;
+  save_scheduled_fees(deps.storage, None)?;
+
+  let response = Response::new()
+    .add_event(Event::new("sudo").add_attribute("action", "set_fees"))
+    .add_event(FeesUpdated { old: &old, new: &fees }.into())
+    .add_attribute("method", "sudo_set_fees")
+    .add_attribute("new_store_keys_fee", fees.store_keys.to_string())
+    .add_attribute("new_store_notes_fee", fees.store_notes.to_string());
+  Ok(if cancelled { response.add_event(FeeUpdateCancelled.into()) } else { response })
+}
+
+/// Set the paused state of the given operations, defaulting to all operations. Returns the
+/// affected operations.
+fn pause_operations(store: &mut dyn Storage, operations: Option<Vec<Operation>>, paused: bool) -> ContractResult<Vec<Operation>> {
+  let operations = operations.unwrap_or_else(|| Operation::ALL.to_vec());
+  for operation in operations.iter() {
+    set_paused(store, *operation, paused)?;
+  }
+  Ok(operations)
+}
+
+fn join_operations(operations: &[Operation]) -> String {
+  operations.iter().map(|op| op.as_str()).collect::<Vec<_>>().join(",")
+}
+
+fn exec_update_fees(ctx: ExecuteContext, fees: FeesMsg) -> ContractResult<Response> {
+  ensure_role(&ctx, Role::FeeManager)?;
+  apply_scheduled_fees(ctx.deps.storage, ctx.env.block.time)?;
+
+  let current = load_fees(ctx.deps.storage, ctx.env.block.time)?;
+  if current.update_delay > 0 {
+    return Err(ContractError::FeeUpdateDelayed { delay: current.update_delay });
+  }
+
+  // an instant update supersedes any pending scheduled update
+  let cancelled = load_scheduled_fees(ctx.deps.storage, ctx.env.block.time)?.is_some();
+  let fees = validate_fees(ctx.deps.api, fees)?;
+  save_fees(ctx.deps.storage, &fees)?;
+  save_scheduled_fees(ctx.deps.storage, None)?;
+
+  let response = Response::new()
+    .add_event(FeesUpdated { old: &current, new: &fees }.into())
+    .add_attribute("method", "update_fees");
+  Ok(if cancelled { response.add_event(FeeUpdateCancelled.into()) } else { response })
+}
+
+fn exec_schedule_fee_update(ctx: ExecuteContext, fees: FeesMsg, effective_at: Timestamp) -> ContractResult<Response> {
+  ensure_role(&ctx, Role::FeeManager)?;
+  apply_scheduled_fees(ctx.deps.storage, ctx.env.block.time)?;
+
+  let current = load_fees(ctx.deps.storage, ctx.env.block.time)?;
+  let earliest = ctx.env.block.time.plus_seconds(current.update_delay);
+  if effective_at < earliest {
+    return Err(ContractError::FeeUpdateTooEarly { earliest });
+  }
+
+  let fees = validate_fees(ctx.deps.api, fees)?;
+  let event: Event = FeeUpdateScheduled { fees: &fees, effective_at }.into();
+  save_scheduled_fees(ctx.deps.storage, Some(&ScheduledFees { fees, effective_at }))?;
+  Ok(Response::new()
+    .add_event(event)
+    .add_attribute("method", "schedule_fee_update")
+    .add_attribute("effective_at", effective_at.to_string())
+  )
+}
+
+fn exec_cancel_fee_update(ctx: ExecuteContext) -> ContractResult<Response> {
+  ensure_role(&ctx, Role::FeeManager)?;
+  apply_scheduled_fees(ctx.deps.storage, ctx.env.block.time)?;
+
+  if load_scheduled_fees(ctx.deps.storage, ctx.env.block.time)?.is_none() {
+    return Err(ContractError::NoScheduledFeeUpdate {});
+  }
+  save_scheduled_fees(ctx.deps.storage, None)?;
+  Ok(Response::new()
+    .add_event(FeeUpdateCancelled.into())
+    .add_attribute("method", "cancel_fee_update")
+  )
+}
+
+fn exec_set_fee_tier(ctx: ExecuteContext, address: String, tier: Option<FeeTier>) -> ContractResult<Response> {
+  ensure_role(&ctx, Role::FeeManager)?;
+
+  if let Some(FeeTier::Discount { bps }) = tier {
+    if bps == 0 || bps > BPS_DENOMINATOR {
+      return Err(ContractError::InvalidFeeTier {});
     }
   }
 
-  save_enc_key(ctx.deps.storage, ctx.info.sender.clone(), &key.as_bytes().to_owned())?;
+  let address = ctx.deps.api.addr_validate(address.as_str())?;
+  save_fee_tier(ctx.deps.storage, address.clone(), tier.as_ref())?;
+  Ok(Response::new()
+    .add_event(FeeTierSet { address: address.clone(), tier }.into())
+    .add_attribute("method", "set_fee_tier")
+    .add_attribute("address", address)
+  )
+}
+
+fn exec_withdraw_fees(ctx: ExecuteContext, amount: Coin, to: Option<String>) -> ContractResult<Response> {
+  if amount.amount.is_zero() {
+    return Err(ContractError::NoFunds {});
+  }
+  let to = match to {
+    Some(to) => ctx.deps.api.addr_validate(to.as_str())?,
+    None => ctx.info.sender.clone(),
+  };
+
+  sub_fee_balance(ctx.deps.storage, ctx.info.sender.clone(), &amount)?;
+  update_fee_stats(ctx.deps.storage, &amount.denom, |stats| stats.withdrawn += amount.amount)?;
 
   Ok(Response::new()
-    .add_attribute("method", "update_key")
-    .add_messages(msgs)
+    .add_event(FeesWithdrawn { address: ctx.info.sender.clone(), to: to.clone(), amount: amount.clone() }.into())
+    .add_attribute("method", "withdraw_fees")
+    .add_attribute("to", to.clone())
+    .add_attribute("amount", amount.to_string())
+    .add_message(BankMsg::Send {
+      to_address: to.to_string(),
+      amount: vec![amount],
+    })
   )
 }
 
-fn exec_store_note(ctx: ExecuteContext, recipient: String, note: String) -> ContractResult<Response> {
-  let fees = load_fees(ctx.deps.storage)?;
-  let mut msgs: Vec<CosmosMsg> = vec![];
+fn exec_deposit(ctx: ExecuteContext) -> ContractResult<Response> {
+  if ctx.info.funds.is_empty() {
+    return Err(ContractError::NoFunds {});
+  }
 
-  if fees.store_notes > Uint128::zero() {
-    let coin = find_coin(&fees.denom, &ctx.info.funds);
-    match coin {
-      Some(coin) => {
-        if coin.amount < fees.store_notes {
-          return Err(ContractError::InsufficientFunds {});
-        }
-        msgs.push(get_fee_msg(&fees, &coin));
-      }
-      None => return Err(ContractError::InsufficientFunds {}),
+  for coin in ctx.info.funds.iter() {
+    add_credits(ctx.deps.storage, ctx.info.sender.clone(), coin)?;
+  }
+
+  Ok(Response::new()
+    .add_event(CreditsDeposited { address: ctx.info.sender.clone(), amount: ctx.info.funds.clone() }.into())
+    .add_attribute("method", "deposit")
+    .add_attribute("amount", ctx.info.funds.iter().map(|c| c.to_string()).collect::<Vec<_>>().join(","))
+  )
+}
+
+fn exec_withdraw(ctx: ExecuteContext, amount: Coin) -> ContractResult<Response> {
+  if amount.amount.is_zero() {
+    return Err(ContractError::NoFunds {});
+  }
+  sub_credits(ctx.deps.storage, ctx.info.sender.clone(), &amount)?;
+
+  Ok(Response::new()
+    .add_event(CreditsWithdrawn { address: ctx.info.sender.clone(), amount: amount.clone() }.into())
+    .add_attribute("method", "withdraw")
+    .add_attribute("amount", amount.to_string())
+    .add_message(BankMsg::Send {
+      to_address: ctx.info.sender.to_string(),
+      amount: vec![amount],
+    })
+  )
+}
+
+/// Offer sponsorship to senders not yet covered. Senders must opt in, such that nobody can burden a
+/// sender's fee payments with sponsorships it never asked for.
+fn exec_sponsor(ctx: ExecuteContext, senders: Vec<String>, per_sender_cap: Option<Coin>, expires: Option<Timestamp>) -> ContractResult<Response> {
+  save_sponsor(ctx.deps.storage, ctx.info.sender.clone(), &Sponsor { per_sender_cap: per_sender_cap.clone(), expires })?;
+
+  let senders = senders.iter()
+    .map(|sender| ctx.deps.api.addr_validate(sender.as_str()))
+    .collect::<StdResult<Vec<_>>>()?;
+  for sender in senders.iter() {
+    if load_sponsorship(ctx.deps.storage, sender.clone(), ctx.info.sender.clone())?.is_none() {
+      save_sponsorship_offer(ctx.deps.storage, sender.clone(), ctx.info.sender.clone())?;
     }
   }
 
+  Ok(Response::new()
+    .add_event(SponsorUpdated { sponsor: ctx.info.sender.clone(), senders, per_sender_cap, expires }.into())
+    .add_attribute("method", "sponsor")
+  )
+}
+
+fn exec_unsponsor(ctx: ExecuteContext, senders: Vec<String>) -> ContractResult<Response> {
+  let senders = senders.iter()
+    .map(|sender| ctx.deps.api.addr_validate(sender.as_str()))
+    .collect::<StdResult<Vec<_>>>()?;
+  for sender in senders.iter() {
+    remove_sponsorship(ctx.deps.storage, sender.clone(), ctx.info.sender.clone());
+    remove_sponsorship_offer(ctx.deps.storage, sender.clone(), ctx.info.sender.clone());
+  }
+
+  Ok(Response::new()
+    .add_event(SponsorshipsRemoved { sponsor: ctx.info.sender.clone(), senders }.into())
+    .add_attribute("method", "unsponsor")
+  )
+}
+
+fn exec_accept_sponsorship(ctx: ExecuteContext, sponsor: String) -> ContractResult<Response> {
+  let sponsor = ctx.deps.api.addr_validate(sponsor.as_str())?;
+  if !has_sponsorship_offer(ctx.deps.storage, ctx.info.sender.clone(), sponsor.clone()) {
+    return Err(ContractError::NoSponsorshipOffer { sponsor: sponsor.to_string() });
+  }
+  if count_sponsorships(ctx.deps.storage, ctx.info.sender.clone()) >= MAX_SPONSORS_PER_SENDER {
+    return Err(ContractError::TooManySponsors { max: MAX_SPONSORS_PER_SENDER });
+  }
+
+  remove_sponsorship_offer(ctx.deps.storage, ctx.info.sender.clone(), sponsor.clone());
+  save_sponsorship(ctx.deps.storage, ctx.info.sender.clone(), sponsor.clone(), Uint128::zero())?;
+
+  Ok(Response::new()
+    .add_event(SponsorshipAccepted { sponsor: sponsor.clone(), sender: ctx.info.sender.clone() }.into())
+    .add_attribute("method", "accept_sponsorship")
+    .add_attribute("sponsor", sponsor)
+  )
+}
+
+fn exec_remove_sponsor(ctx: ExecuteContext, sponsor: String) -> ContractResult<Response> {
+  let sponsor = ctx.deps.api.addr_validate(sponsor.as_str())?;
+  remove_sponsorship(ctx.deps.storage, ctx.info.sender.clone(), sponsor.clone());
+  remove_sponsorship_offer(ctx.deps.storage, ctx.info.sender.clone(), sponsor.clone());
+
+  Ok(Response::new()
+    .add_event(SponsorshipsRemoved { sponsor, senders: vec![ctx.info.sender.clone()] }.into())
+    .add_attribute("method", "remove_sponsor")
+  )
+}
+
+fn exec_update_key(mut ctx: ExecuteContext, key: String) -> ContractResult<Response> {
+  let fees = load_fees(ctx.deps.storage, ctx.env.block.time)?;
+  let payment = collect_fee(&mut ctx, &fees, fees.store_keys)?;
+
+  save_enc_key(ctx.deps.storage, ctx.info.sender.clone(), &key.as_bytes().to_owned())?;
+
+  let event = KeyUpdated {
+    address: ctx.info.sender.clone(),
+    key_hash: key_hash(key.as_bytes()),
+    fee: payment.paid.clone(),
+    sponsor: payment.sponsor.clone(),
+  };
+  Ok(payment.apply(Response::new()
+    .add_event(event.into())
+    .add_attribute("method", "update_key")
+  ))
+}
+
+fn exec_store_note(mut ctx: ExecuteContext, recipient: String, note: String) -> ContractResult<Response> {
+  let fees = load_fees(ctx.deps.storage, ctx.env.block.time)?;
+  let payment = collect_fee(&mut ctx, &fees, fees.store_notes)?;
+
   let recipient = ctx.deps.api.addr_validate(recipient.as_str())?;
   let note = Note {
     sender: ctx.info.sender.clone(),
     note: note.as_bytes().to_owned(),
     timestamp: ctx.env.block.time,
+    height: ctx.env.block.height,
   };
-  store_note(ctx.deps.storage, ctx.info.sender.clone(), recipient.clone(), note)?;
+  let size = note.note.len() as u64;
+  let idx = store_note(ctx.deps.storage, ctx.info.sender.clone(), recipient.clone(), note)?;
+  let hook = receive_hook_msg(&ctx, &recipient, idx)?;
+  let notifications = subscriber_msgs(&ctx, &recipient, idx)?;
 
-  Ok(Response::new()
+  let event = NoteStored {
+    sender: ctx.info.sender.clone(),
+    recipient: recipient.clone(),
+    idx,
+    size,
+    key_id: load_enc_key(ctx.deps.storage, recipient.clone())?.map(|key| key_hash(&key)),
+    fee: payment.paid.clone(),
+    sponsor: payment.sponsor.clone(),
+  };
+  Ok(payment.apply(Response::new()
+    .add_event(event.into())
     .add_attribute("method", "store_note")
     .add_attribute("recipient", recipient)
-    .add_messages(msgs)
-  )
+    .add_submessages(hook)
+    .add_submessages(notifications)
+  ))
 }
 
-#[cfg_attr(not(feature = "library"), entry_point)]
-pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> StdResult<Binary> {
-  let ctx = QueryContext { deps, env };
-  let response = match msg {
-    QueryMsg::Fees {} => to_json_binary(&query_fees(&ctx)?)?,
-    QueryMsg::NoteCount { recipient, sender } => to_json_binary(&query_note_count(&ctx, recipient, sender)?)?,
-    QueryMsg::Senders { recipient } => to_json_binary(&query_senders(&ctx, recipient)?)?,
-    QueryMsg::EncryptionKey { address } => to_json_binary(&query_enc_key(&ctx, address)?)?,
-    QueryMsg::Notes { recipient, sender, start_after, limit } =>
-      to_json_binary(&query_notes(&ctx, recipient, sender, start_after, limit)?)?,
-  };
+/// Send a note to a counterpart instance over IBC. The fee is escrowed rather than distributed until
+/// the packet is acknowledged, see `crate::ibc`.
+fn exec_store_remote_note(mut ctx: ExecuteContext, channel: String, recipient: String, note: String) -> ContractResult<Response> {
+  if load_channel(ctx.deps.storage, channel.clone())?.is_none() {
+    return Err(ContractError::UnknownChannel { channel });
+  }
 
-  Ok(response)
+  let fees = load_fees(ctx.deps.storage, ctx.env.block.time)?;
+  let payment = charge_fee(&mut ctx, &fees, fees.store_notes)?;
+  let packet_id = next_packet_id(ctx.deps.storage)?;
+  if let Some(fee) = &payment.paid {
+    let escrow = Escrow { sender: ctx.info.sender.clone(), sponsor: payment.sponsor.clone(), fee: fee.clone() };
+    save_escrow(ctx.deps.storage, channel.clone(), packet_id, &escrow)?;
+  }
+
+  let event = RemoteNoteSent {
+    sender: ctx.info.sender.clone(),
+    channel: channel.clone(),
+    recipient: recipient.clone(),
+    packet_id,
+    size: note.len() as u64,
+    fee: payment.paid.clone(),
+    sponsor: payment.sponsor.clone(),
+  };
+  let packet = PacketMsg::StoreNote(NotePacket {
+    id: packet_id,
+    chain_id: ctx.env.block.chain_id.clone(),
+    sender: ctx.info.sender.to_string(),
+    recipient: recipient.clone(),
+    note,
+  });
+  Ok(payment.apply(Response::new()
+    .add_event(event.into())
+    .add_attribute("method", "store_remote_note")
+    .add_attribute("channel", channel.clone())
+    .add_attribute("recipient", recipient)
+    .add_message(IbcMsg::SendPacket {
+      channel_id: channel,
+      data: to_json_binary(&packet)?,
+      timeout: ctx.env.block.time.plus_seconds(crate::ibc::PACKET_TIMEOUT).into(),
+    })
+  ))
 }
 
-fn query_fees(ctx: &QueryContext) -> ContractResult<Fees> {
-  let fees = load_fees(ctx.deps.storage)?;
-  Ok(fees)
+/// Approve the counterpart instance channels may be opened to over a connection. The handshake is
+/// initiated by relayers, such that channels to any other port are rejected. The chain id is
+/// recorded for every channel opened, as the counterpart is not trusted to report it.
+fn exec_allow_counterparty(ctx: ExecuteContext, connection_id: String, port_id: String, chain_id: String) -> ContractResult<Response> {
+  ensure_admin(&ctx)?;
+  if chain_id.is_empty() || chain_id.contains('/') {
+    return Err(ContractError::InvalidChainId {});
+  }
+  let counterparty = Counterparty { port_id: port_id.clone(), chain_id: chain_id.clone() };
+  save_counterparty(ctx.deps.storage, connection_id.clone(), &counterparty)?;
+
+  Ok(Response::new()
+    .add_event(CounterpartyChanged { connection_id, port_id: Some(port_id), chain_id: Some(chain_id) }.into())
+    .add_attribute("method", "allow_counterparty")
+  )
 }
 
-fn query_note_count(ctx: &QueryContext, recipient: String, sender: String) -> ContractResult<u64> {
-  let recipient = ctx.deps.api.addr_validate(recipient.as_str())?;
-  let sender = ctx.deps.api.addr_validate(sender.as_str())?;
-  let meta = load_note_meta(ctx.deps.storage, sender, recipient)?;
-  Ok(meta.count)
+fn exec_remove_counterparty(ctx: ExecuteContext, connection_id: String) -> ContractResult<Response> {
+  ensure_admin(&ctx)?;
+  remove_counterparty(ctx.deps.storage, connection_id.clone());
+
+  Ok(Response::new()
+    .add_event(CounterpartyChanged { connection_id, port_id: None, chain_id: None }.into())
+    .add_attribute("method", "remove_counterparty")
+  )
 }
 
-fn query_senders(ctx: &QueryContext, recipient: String) -> ContractResult<Vec<String>> {
-  let recipient = ctx.deps.api.addr_validate(recipient.as_str())?;
-  let senders = find_senders(ctx.deps.storage, recipient)?;
-  Ok(senders.iter().map(|a| a.to_string()).collect())
+/// Every lookup costs relayers a packet round-trip, which is paid for with the store keys fee. Keys
+/// still cached for the channel's chain are not looked up again, nor charged for.
+fn exec_lookup_remote_key(mut ctx: ExecuteContext, channel: String, address: String) -> ContractResult<Response> {
+  let chain_id = load_channel_chain(ctx.deps.storage, channel.clone())?
+    .ok_or_else(|| ContractError::UnknownChannel { channel: channel.clone() })?;
+
+  let cached = load_remote_key(ctx.deps.storage, chain_id, address.clone())?;
+  if cached.is_some_and(|cached| ctx.env.block.time < cached.fetched_at.plus_seconds(crate::ibc::REMOTE_KEY_TTL)) {
+    if !ctx.info.funds.is_empty() {
+      return Err(ContractError::UnexpectedFunds {});
+    }
+    return Ok(Response::new()
+      .add_attribute("method", "lookup_remote_key")
+      .add_attribute("channel", channel)
+      .add_attribute("address", address)
+      .add_attribute("cached", "true")
+    );
+  }
+
+  let fees = load_fees(ctx.deps.storage, ctx.env.block.time)?;
+  let payment = collect_fee(&mut ctx, &fees, fees.store_keys)?;
+  let event = RemoteKeyRequested {
+    sender: ctx.info.sender.clone(),
+    channel: channel.clone(),
+    address: address.clone(),
+    fee: payment.paid.clone(),
+    sponsor: payment.sponsor.clone(),
+  };
+  let packet = PacketMsg::KeyLookup(KeyLookupRequest { address: address.clone() });
+  Ok(payment.apply(Response::new()
+    .add_event(event.into())
+    .add_attribute("method", "lookup_remote_key")
+    .add_attribute("channel", channel.clone())
+    .add_attribute("address", address)
+    .add_message(IbcMsg::SendPacket {
+      channel_id: channel,
+      data: to_json_binary(&packet)?,
+      timeout: ctx.env.block.time.plus_seconds(crate::ibc::PACKET_TIMEOUT).into(),
+    })
+  ))
 }
 
-fn query_enc_key(ctx: &QueryContext, address: String) -> ContractResult<EncryptionKeyResponse> {
-  let address = ctx.deps.api.addr_validate(address.as_str())?;
-  let key = load_enc_key(ctx.deps.storage, address)?;
-  Ok(EncryptionKeyResponse { key })
+/// Store a note signed off-chain as if sent by the signer. The funds attached by the relayer stand
+/// in for the signer's, such that fees are paid by the relayer or else on behalf of the signer.
+fn exec_relay_note(mut ctx: ExecuteContext, signed: SignedNote) -> ContractResult<Response> {
+  let sender = pubkey_to_address(&ctx.env.contract.address, &signed.sender_pubkey)?;
+  if ctx.env.block.time >= signed.expires {
+    return Err(ContractError::SignatureExpired {});
+  }
+  let expected = load_relay_nonce(ctx.deps.storage, sender.clone())?;
+  if signed.nonce != expected {
+    return Err(ContractError::InvalidNonce { expected });
+  }
+
+  let sign_doc = RelaySignDoc {
+    chain_id: ctx.env.block.chain_id.clone(),
+    contract: ctx.env.contract.address.to_string(),
+    payload: signed.payload.clone(),
+    nonce: signed.nonce,
+    expires: signed.expires,
+  };
+  let hash = Sha256::digest(sign_doc.sign_bytes()?);
+  if !matches!(ctx.deps.api.secp256k1_verify(&hash, &signed.signature, &signed.sender_pubkey), Ok(true)) {
+    return Err(ContractError::InvalidSignature {});
+  }
+  save_relay_nonce(ctx.deps.storage, sender.clone(), expected + 1)?;
+
+  let relayer = std::mem::replace(&mut ctx.info.sender, sender.clone());
+  let response = exec_store_note(ctx, signed.payload.recipient, signed.payload.note)?;
+  Ok(response
+    .add_event(NoteRelayed { relayer: relayer.clone(), sender, nonce: signed.nonce }.into())
+    .add_attribute("relayer", relayer)
+  )
 }
 
-fn query_notes(ctx: &QueryContext, recipient: String, sender: String, start_after: Option<u64>, limit: Option<u32>) -> ContractResult<Vec<Note>> {
+/// Store a note without recording its sender in contract state or the contract's own events and
+/// attributes. The chain still reveals the sender of the attached funds in its `message` and
+/// `transfer` events. As fee tiers, sponsors and prepaid credits would all link the note to its
+/// sender, the fee must be attached in full. Neither receive hooks nor subscribers are notified of sealed notes; recipients are instead
+/// protected from spam by requiring postage, which is credited to them.
+fn exec_store_sealed_note(ctx: ExecuteContext, recipient: String, envelope: Binary) -> ContractResult<Response> {
+  let fees = load_fees(ctx.deps.storage, ctx.env.block.time)?;
   let recipient = ctx.deps.api.addr_validate(recipient.as_str())?;
-  let sender = ctx.deps.api.addr_validate(sender.as_str())?;
-  let notes = load_notes(ctx.deps.storage, recipient, sender, start_after, limit)?;
-  Ok(notes)
-}
 
-fn find_coin(denom: &str, coins: &Vec<Coin>) -> Option<Coin> {
-  coins.iter().find(|coin| coin.denom == denom).cloned()
-}
+  // split the attached funds into the fee and the postage
+  let mut postage = ctx.info.funds.clone();
+  let mut msgs = vec![];
+  if !fees.store_notes.is_zero() {
+    let coin = postage.iter_mut()
+      .find(|coin| coin.denom == fees.denom && coin.amount >= fees.store_notes)
+      .ok_or(ContractError::InsufficientFunds {})?;
+    coin.amount -= fees.store_notes;
+    msgs = distribute_fee(ctx.deps.storage, &fees, &Coin::new(fees.store_notes.u128(), fees.denom.clone()))?;
+  }
+  postage.retain(|coin| !coin.amount.is_zero());
+
+  // exactly the required postage must be attached, such that overpayments are not lost
+  let required = load_postage(ctx.deps.storage, recipient.clone())?;
+  if let Some(required) = &required {
+    let paid = find_coin(&required.denom, &postage).map(|coin| coin.amount).unwrap_or_default();
+    if paid < required.amount {
+      return Err(ContractError::InsufficientPostage { postage: required.clone() });
+    }
+  }
+  if postage.as_slice() != required.as_slice() {
+    return Err(ContractError::UnexpectedFunds {});
+  }
+  if let Some(postage) = &required {
+    add_credits(ctx.deps.storage, recipient.clone(), postage)?;
+  }
 
-fn get_fee_msg(fees: &Fees, coin: &Coin) -> CosmosMsg {
-  let sendmsg = BankMsg::Send {
-    to_address: fees.admin.clone().map(|a| a.to_string()).unwrap_or("".to_string()),
-    amount: vec![coin.clone()],
+  let note = SealedNote {
+    envelope: envelope.to_vec(),
+    timestamp: ctx.env.block.time,
+    height: ctx.env.block.height,
   };
-  let burnmsg = BankMsg::Burn {
-    amount: vec![coin.clone()],
+  let seq = store_sealed_note(ctx.deps.storage, recipient.clone(), &note)?;
+
+  let event = SealedNoteStored {
+    recipient: recipient.clone(),
+    seq,
+    size: note.envelope.len() as u64,
+    postage: required,
   };
-  match fees.admin {
-    Some(_) => {
-      if fees.burn_fees {
-        CosmosMsg::Bank(burnmsg)
-      } else {
-        CosmosMsg::Bank(sendmsg)
-      }
-    }
-    None => CosmosMsg::Bank(burnmsg),
-  }
+  Ok(Response::new()
+    .add_event(event.into())
+    .add_attribute("method", "store_sealed_note")
+    .add_attribute("recipient", recipient)
+    .add_messages(msgs)
+  )
 }
 
-struct ExecuteContext<'a> {
-  deps: DepsMut<'a>,
-  #[allow(dead_code)]
-  env: Env,
-  info: MessageInfo,
+fn exec_set_postage(ctx: ExecuteContext, postage: Option<Coin>) -> ContractResult<Response> {
+  let postage = postage.filter(|coin| !coin.amount.is_zero());
+  save_postage(ctx.deps.storage, ctx.info.sender.clone(), postage.as_ref())?;
+
+  Ok(Response::new()
+    .add_event(PostageSet { address: ctx.info.sender.clone(), postage }.into())
+    .add_attribute("method", "set_postage")
+  )
 }
 
-struct QueryContext<'a> {
-  deps: Deps<'a>,
-  #[allow(dead_code)]
-  env: Env,
+/// Derive the address of a compressed secp256k1 public key the way the Cosmos SDK does, using the
+/// bech32 prefix of this contract's own address.
+fn pubkey_to_address(contract: &Addr, pubkey: &[u8]) -> ContractResult<Addr> {
+  if pubkey.len() != 33 || !matches!(pubkey[0], 0x02 | 0x03) {
+    return Err(ContractError::InvalidPubkey {});
+  }
+  let (prefix, _, _) = bech32::decode(contract.as_str()).map_err(|err| StdError::generic_err(err.to_string()))?;
+  let hash = Ripemd160::digest(Sha256::digest(pubkey));
+  let address = bech32::encode(&prefix, hash.to_base32(), Variant::Bech32).map_err(|err| StdError::generic_err(err.to_string()))?;
+  Ok(Addr::unchecked(address))
 }
 
-#[cfg(test)]
-mod tests {
-  use super::*;
-  use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info};
-  use cosmwasm_std::{coins, from_json, SubMsg};
+/// Build the message notifying the recipient of a new note, if it has registered a receive hook.
+/// Hooks ignoring failures are dispatched with a reply on error, which absorbs the failure.
+fn receive_hook_msg(ctx: &ExecuteContext, recipient: &Addr, idx: u64) -> ContractResult<Option<SubMsg>> {
+  let Some(hook) = load_receive_hook(ctx.deps.storage, recipient.clone())? else {
+    return Ok(None);
+  };
 
-  #[test]
-  fn init() {
+  let msg = DropnoteReceiveMsg {
+    sender: ctx.info.sender.to_string(),
+    idx,
+    note_ref: NoteRef {
+      contract: ctx.env.contract.address.to_string(),
+      recipient: recipient.to_string(),
+      sender: ctx.info.sender.to_string(),
+      idx,
+    },
+  }.into_cosmos_msg(recipient.clone())?;
+  Ok(Some(match hook.on_error {
+    HookFailure::Revert => SubMsg::new(msg),
+    HookFailure::Ignore => SubMsg::reply_on_error(msg, RECEIVE_HOOK_REPLY_ID),
+  }))
+}
+
+/// Build the notifications of all subscribers. Each is limited to the subscriber's gas limit and
+/// replies either way, such that failures are absorbed and counted.
+fn subscriber_msgs(ctx: &ExecuteContext, recipient: &Addr, idx: u64) -> ContractResult<Vec<SubMsg>> {
+  find_subscribers(ctx.deps.storage, None, MAX_SUBSCRIBERS)?.into_iter()
+    .map(|(address, subscriber)| {
+      let msg = NoteStoredHook {
+        recipient: recipient.to_string(),
+        sender: ctx.info.sender.to_string(),
+        idx,
+      }.into_cosmos_msg(address)?;
+      Ok(SubMsg::reply_always(msg, SUBSCRIBER_REPLY_ID_OFFSET + subscriber.id).with_gas_limit(subscriber.gas_limit))
+    })
+    .collect()
+}
+
+fn exec_set_receive_hook(ctx: ExecuteContext, hook: Option<ReceiveHook>) -> ContractResult<Response> {
+  if hook.is_some() {
+    ensure_contract(&ctx, &ctx.info.sender)?;
+  }
+
+  save_receive_hook(ctx.deps.storage, ctx.info.sender.clone(), hook.as_ref())?;
+  Ok(Response::new()
+    .add_event(ReceiveHookSet { address: ctx.info.sender.clone(), hook }.into())
+    .add_attribute("method", "set_receive_hook")
+  )
+}
+
+fn exec_mark_read(ctx: ExecuteContext, sender: String, until: Option<u64>) -> ContractResult<Response> {
+  let sender = validate_sender(ctx.deps.api, sender.as_str())?;
+  let mut meta = load_note_meta(ctx.deps.storage, sender.clone(), ctx.info.sender.clone())?;
+  meta.read = until.map(|idx| idx.saturating_add(1).min(meta.count)).unwrap_or(meta.count);
+  save_note_meta(ctx.deps.storage, sender.clone(), ctx.info.sender.clone(), &meta)?;
+
+  Ok(Response::new()
+    .add_event(NotesRead { recipient: ctx.info.sender.clone(), sender: sender.clone(), read: meta.read }.into())
+    .add_attribute("method", "mark_read")
+    .add_attribute("sender", sender)
+    .add_attribute("read", meta.read.to_string())
+  )
+}
+
+fn exec_add_subscriber(ctx: ExecuteContext, address: String, gas_limit: u64) -> ContractResult<Response> {
+  ensure_role(&ctx, Role::Moderator)?;
+  let address = ctx.deps.api.addr_validate(address.as_str())?;
+  ensure_contract(&ctx, &address)?;
+  ensure_gas_limit(gas_limit)?;
+
+  let subscriber = match load_subscriber(ctx.deps.storage, address.clone())? {
+    Some(subscriber) => {
+      let subscriber = Subscriber { gas_limit, ..subscriber };
+      save_subscriber(ctx.deps.storage, address.clone(), &subscriber)?;
+      subscriber
+    }
+    None => {
+      ensure_subscriber_capacity(ctx.deps.storage, false)?;
+      add_subscriber(ctx.deps.storage, address.clone(), gas_limit, None)?
+    }
+  };
+
+  Ok(Response::new()
+    .add_event(SubscriberAdded { address: address.clone(), gas_limit, deposit: subscriber.deposit }.into())
+    .add_attribute("method", "add_subscriber")
+    .add_attribute("address", address)
+  )
+}
+
+fn exec_remove_subscriber(ctx: ExecuteContext, address: String) -> ContractResult<Response> {
+  ensure_role(&ctx, Role::Moderator)?;
+  let address = ctx.deps.api.addr_validate(address.as_str())?;
+  let subscriber = remove_subscriber(ctx.deps.storage, address.clone())?.ok_or(ContractError::NotSubscribed {})?;
+
+  Ok(refund_subscriber(Response::new(), address, subscriber, "removed")
+    .add_attribute("method", "remove_subscriber")
+  )
+}
+
+fn exec_subscribe(ctx: ExecuteContext, gas_limit: u64) -> ContractResult<Response> {
+  let deposit = load_subscription_deposit(ctx.deps.storage)?.ok_or(ContractError::SubscriptionClosed {})?;
+  if ctx.info.funds != [deposit.clone()] {
+    return Err(ContractError::InvalidDeposit { deposit });
+  }
+  ensure_contract(&ctx, &ctx.info.sender)?;
+  ensure_gas_limit(gas_limit)?;
+  if load_subscriber(ctx.deps.storage, ctx.info.sender.clone())?.is_some() {
+    return Err(ContractError::AlreadySubscribed {});
+  }
+  ensure_subscriber_capacity(ctx.deps.storage, true)?;
+
+  add_subscriber(ctx.deps.storage, ctx.info.sender.clone(), gas_limit, Some(deposit.clone()))?;
+  Ok(Response::new()
+    .add_event(SubscriberAdded { address: ctx.info.sender.clone(), gas_limit, deposit: Some(deposit) }.into())
+    .add_attribute("method", "subscribe")
+  )
+}
+
+fn exec_unsubscribe(ctx: ExecuteContext) -> ContractResult<Response> {
+  let subscriber = remove_subscriber(ctx.deps.storage, ctx.info.sender.clone())?.ok_or(ContractError::NotSubscribed {})?;
+
+  Ok(refund_subscriber(Response::new(), ctx.info.sender, subscriber, "unsubscribed")
+    .add_attribute("method", "unsubscribe")
+  )
+}
+
+/// Refund the deposit of a subscriber which has been removed regularly.
+fn refund_subscriber(response: Response, address: Addr, subscriber: Subscriber, reason: &'static str) -> Response {
+  let response = match &subscriber.deposit {
+    Some(deposit) => response.add_message(BankMsg::Send {
+      to_address: address.to_string(),
+      amount: vec![deposit.clone()],
+    }),
+    None => response,
+  };
+  response.add_event(SubscriberRemoved { address, reason, refund: subscriber.deposit }.into())
+}
+
+fn exec_set_subscription_deposit(ctx: ExecuteContext, deposit: Option<Coin>) -> ContractResult<Response> {
+  ensure_role(&ctx, Role::Moderator)?;
+  // zero amounts are never attached, such that nobody could subscribe
+  if deposit.as_ref().is_some_and(|deposit| deposit.amount.is_zero()) {
+    return Err(ContractError::ZeroDeposit {});
+  }
+  save_subscription_deposit(ctx.deps.storage, deposit.as_ref())?;
+
+  Ok(Response::new()
+    .add_event(SubscriptionDepositSet { deposit }.into())
+    .add_attribute("method", "set_subscription_deposit")
+  )
+}
+
+fn ensure_gas_limit(gas_limit: u64) -> ContractResult<()> {
+  if gas_limit > MAX_SUBSCRIBER_GAS_LIMIT {
+    return Err(ContractError::GasLimitTooHigh { max: MAX_SUBSCRIBER_GAS_LIMIT });
+  }
+  Ok(())
+}
+
+fn ensure_subscriber_capacity(store: &dyn Storage, self_registered: bool) -> ContractResult<()> {
+  let subscribers = find_subscribers(store, None, MAX_SUBSCRIBERS)?;
+  if subscribers.len() >= MAX_SUBSCRIBERS {
+    return Err(ContractError::TooManySubscribers { max: MAX_SUBSCRIBERS });
+  }
+  let deposited = subscribers.iter().filter(|(_, subscriber)| subscriber.deposit.is_some()).count();
+  if self_registered && deposited >= MAX_SELF_REGISTERED_SUBSCRIBERS {
+    return Err(ContractError::TooManySubscribers { max: MAX_SELF_REGISTERED_SUBSCRIBERS });
+  }
+  Ok(())
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn reply(deps: DepsMut, env: Env, msg: Reply) -> ContractResult<Response> {
+  match (msg.id, msg.result) {
+    (RECEIVE_HOOK_REPLY_ID, SubMsgResult::Err(error)) => Ok(Response::new()
+      .add_event(ReceiveHookFailed { error }.into())
+      .add_attribute("method", "receive_hook_failed")
+    ),
+    (id, result) if id >= SUBSCRIBER_REPLY_ID_OFFSET => reply_subscriber(deps, env, id - SUBSCRIBER_REPLY_ID_OFFSET, result),
+    (id, _) => Err(ContractError::UnknownReply { id }),
+  }
+}
+
+/// Track consecutive failures of a subscriber. Once it has failed too often, it is removed and its
+/// deposit is distributed like a fee.
+fn reply_subscriber(deps: DepsMut, env: Env, id: u64, result: SubMsgResult) -> ContractResult<Response> {
+  // the subscriber may have been removed by an earlier notification in the same transaction
+  let Some((address, mut subscriber)) = load_subscriber_by_id(deps.storage, id)? else {
+    return Ok(Response::new());
+  };
+
+  let error = match result {
+    SubMsgResult::Ok(_) => {
+      if subscriber.failures > 0 {
+        subscriber.failures = 0;
+        save_subscriber(deps.storage, address, &subscriber)?;
+      }
+      return Ok(Response::new());
+    }
+    SubMsgResult::Err(error) => error,
+  };
+
+  subscriber.failures += 1;
+  let response = Response::new()
+    .add_event(SubscriberFailed { address: address.clone(), failures: subscriber.failures, error }.into())
+    .add_attribute("method", "subscriber_failed");
+  if subscriber.failures < MAX_SUBSCRIBER_FAILURES {
+    save_subscriber(deps.storage, address, &subscriber)?;
+    return Ok(response);
+  }
+
+  remove_subscriber(deps.storage, address.clone())?;
+  let msgs = match &subscriber.deposit {
+    Some(deposit) => {
+      let fees = load_fees(deps.storage, env.block.time)?;
+      distribute_fee(deps.storage, &fees, deposit)?
+    }
+    None => vec![],
+  };
+  Ok(response
+    .add_event(SubscriberRemoved { address, reason: "failing", refund: None }.into())
+    .add_messages(msgs)
+  )
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> StdResult<Binary> {
+  let ctx = QueryContext { deps, env };
+  let response = match msg {
+    QueryMsg::Ownership {} => to_json_binary(&query_ownership(&ctx)?)?,
+    QueryMsg::Roles { address } => to_json_binary(&query_roles(&ctx, address)?)?,
+    QueryMsg::RoleMembers { role, start_after, limit } => to_json_binary(&query_role_members(&ctx, role, start_after, limit)?)?,
+    QueryMsg::Paused {} => to_json_binary(&query_paused(&ctx)?)?,
+    QueryMsg::Fees {} => to_json_binary(&query_fees(&ctx)?)?,
+    QueryMsg::SealedNotes { recipient, start_after, limit } => to_json_binary(&query_sealed_notes(&ctx, recipient, start_after, limit)?)?,
+    QueryMsg::Postage { address } => to_json_binary(&query_postage(&ctx, address)?)?,
+    QueryMsg::RelayNonce { address } => to_json_binary(&query_relay_nonce(&ctx, address)?)?,
+    QueryMsg::NoteCount { recipient, sender } => to_json_binary(&query_note_count(&ctx, recipient, sender)?)?,
+    QueryMsg::NoteCounts { pairs } => to_json_binary(&query_note_counts(&ctx, pairs)?)?,
+    QueryMsg::Conversations { recipient, start_after, limit, order_by } =>
+      to_json_binary(&query_conversations(&ctx, recipient, start_after, limit, order_by)?)?,
+    QueryMsg::Senders { recipient, start_after, limit } => to_json_binary(&query_senders(&ctx, recipient, start_after, limit)?)?,
+    QueryMsg::EncryptionKey { address } => to_json_binary(&query_enc_key(&ctx, address)?)?,
+    QueryMsg::EncryptionKeys { addresses } => to_json_binary(&query_enc_keys(&ctx, addresses)?)?,
+    QueryMsg::RemoteEncryptionKey { chain, address } => to_json_binary(&query_remote_enc_key(&ctx, chain, address)?)?,
+    QueryMsg::Notes { recipient, sender, start_after, limit, range } =>
+      to_json_binary(&query_notes(&ctx, recipient, sender, start_after, limit, range.unwrap_or_default())?)?,
+    QueryMsg::EffectiveFees { address } => to_json_binary(&query_effective_fees(&ctx, address)?)?,
+    QueryMsg::FeeBalance { address } => to_json_binary(&query_fee_balance(&ctx, address)?)?,
+    QueryMsg::FeeStats {} => to_json_binary(&query_fee_stats(&ctx)?)?,
+    QueryMsg::Stats {} => to_json_binary(&query_stats(&ctx)?)?,
+    QueryMsg::ReceiveHook { address } => to_json_binary(&query_receive_hook(&ctx, address)?)?,
+    QueryMsg::Subscribers { start_after, limit } => to_json_binary(&query_subscribers(&ctx, start_after, limit)?)?,
+    QueryMsg::SubscriptionDeposit {} => to_json_binary(&query_subscription_deposit(&ctx)?)?,
+    QueryMsg::Channels {} => to_json_binary(&query_channels(&ctx)?)?,
+    QueryMsg::Counterparties {} => to_json_binary(&query_counterparties(&ctx)?)?,
+    QueryMsg::Credits { address } => to_json_binary(&query_credits(&ctx, address)?)?,
+    QueryMsg::Sponsor { address } => to_json_binary(&query_sponsor(&ctx, address)?)?,
+    QueryMsg::Sponsorship { sponsor, sender } => to_json_binary(&query_sponsorship(&ctx, sponsor, sender)?)?,
+  };
+
+  Ok(response)
+}
+
+fn query_ownership(ctx: &QueryContext) -> ContractResult<Ownership> {
+  let ownership = load_ownership(ctx.deps.storage)?;
+  Ok(ownership)
+}
+
+fn query_roles(ctx: &QueryContext, address: String) -> ContractResult<RolesResponse> {
+  let address = ctx.deps.api.addr_validate(address.as_str())?;
+  let ownership = load_ownership(ctx.deps.storage)?;
+  if ownership.owner.is_some_and(|owner| owner == address) {
+    return Ok(RolesResponse { roles: Role::ALL.to_vec() });
+  }
+
+  let mut roles = vec![];
+  for role in Role::ALL {
+    if has_role(ctx.deps.storage, role, address.clone())? {
+      roles.push(role);
+    }
+  }
+  Ok(RolesResponse { roles })
+}
+
+fn query_role_members(ctx: &QueryContext, role: Role, start_after: Option<String>, limit: Option<u32>) -> ContractResult<RoleMembersResponse> {
+  if role == Role::Owner {
+    let owner = load_ownership(ctx.deps.storage)?.owner;
+    let members = match start_after {
+      None => owner.into_iter().map(|a| a.to_string()).collect(),
+      Some(_) => vec![],
+    };
+    return Ok(RoleMembersResponse { members, next_cursor: None });
+  }
+
+  let start_after = start_after.map(|a| ctx.deps.api.addr_validate(a.as_str())).transpose()?;
+  let limit = page_limit(limit);
+  let members = find_role_members(ctx.deps.storage, role, start_after, limit + 1)?;
+  let (members, next_cursor) = paginate(members, limit, |a| a.to_string());
+  Ok(RoleMembersResponse {
+    members: members.iter().map(|a| a.to_string()).collect(),
+    next_cursor,
+  })
+}
+
+fn query_paused(ctx: &QueryContext) -> ContractResult<PausedResponse> {
+  let operations = Operation::ALL.into_iter()
+    .filter(|operation| is_paused(ctx.deps.storage, *operation))
+    .collect();
+  Ok(PausedResponse { operations })
+}
+
+fn query_fees(ctx: &QueryContext) -> ContractResult<FeesResponse> {
+  let fees = load_fees(ctx.deps.storage, ctx.env.block.time)?;
+  let scheduled = load_scheduled_fees(ctx.deps.storage, ctx.env.block.time)?;
+  Ok(FeesResponse { fees, scheduled })
+}
+
+fn query_effective_fees(ctx: &QueryContext, address: String) -> ContractResult<EffectiveFeesResponse> {
+  let address = ctx.deps.api.addr_validate(address.as_str())?;
+  let fees = load_fees(ctx.deps.storage, ctx.env.block.time)?;
+  let tier = load_fee_tier(ctx.deps.storage, address)?;
+  Ok(EffectiveFeesResponse {
+    store_keys: apply_fee_tier(tier.as_ref(), fees.store_keys),
+    store_notes: apply_fee_tier(tier.as_ref(), fees.store_notes),
+    denom: fees.denom,
+    tier,
+  })
+}
+
+fn query_fee_balance(ctx: &QueryContext, address: String) -> ContractResult<FeeBalanceResponse> {
+  let address = ctx.deps.api.addr_validate(address.as_str())?;
+  let balance = load_fee_balances(ctx.deps.storage, address)?;
+  Ok(FeeBalanceResponse { balance })
+}
+
+fn query_fee_stats(ctx: &QueryContext) -> ContractResult<FeeStatsResponse> {
+  let stats = load_fee_stats(ctx.deps.storage)?
+    .into_iter()
+    .map(|(denom, stats)| DenomFeeStats {
+      denom,
+      collected: stats.collected,
+      burned: stats.burned,
+      distributed: stats.distributed,
+      withdrawn: stats.withdrawn,
+    })
+    .collect();
+  Ok(FeeStatsResponse { stats })
+}
+
+fn query_receive_hook(ctx: &QueryContext, address: String) -> ContractResult<ReceiveHookResponse> {
+  let address = ctx.deps.api.addr_validate(address.as_str())?;
+  let hook = load_receive_hook(ctx.deps.storage, address)?;
+  Ok(ReceiveHookResponse { hook })
+}
+
+fn query_subscribers(ctx: &QueryContext, start_after: Option<String>, limit: Option<u32>) -> ContractResult<SubscribersResponse> {
+  let start_after = start_after.map(|a| ctx.deps.api.addr_validate(a.as_str())).transpose()?;
+  let limit = page_limit(limit);
+  let subscribers = find_subscribers(ctx.deps.storage, start_after, limit + 1)?;
+  let (subscribers, next_cursor) = paginate(subscribers, limit, |(address, _)| address.to_string());
+  Ok(SubscribersResponse {
+    subscribers: subscribers.into_iter()
+      .map(|(address, subscriber)| SubscriberEntry {
+        address: address.to_string(),
+        gas_limit: subscriber.gas_limit,
+        deposit: subscriber.deposit,
+        failures: subscriber.failures,
+      })
+      .collect(),
+    next_cursor,
+  })
+}
+
+fn query_subscription_deposit(ctx: &QueryContext) -> ContractResult<SubscriptionDepositResponse> {
+  let deposit = load_subscription_deposit(ctx.deps.storage)?;
+  Ok(SubscriptionDepositResponse { deposit })
+}
+
+fn query_channels(ctx: &QueryContext) -> ContractResult<ChannelsResponse> {
+  let channels = load_channels(ctx.deps.storage)?.into_iter()
+    .map(|(channel, chain_id)| ChannelInfo {
+      channel_id: channel.endpoint.channel_id,
+      counterparty_port: channel.counterparty_endpoint.port_id,
+      counterparty_channel: channel.counterparty_endpoint.channel_id,
+      connection_id: channel.connection_id,
+      chain_id,
+    })
+    .collect();
+  Ok(ChannelsResponse { channels })
+}
+
+fn query_counterparties(ctx: &QueryContext) -> ContractResult<CounterpartiesResponse> {
+  let counterparties = load_counterparties(ctx.deps.storage)?.into_iter()
+    .map(|(connection_id, counterparty)| CounterpartyInfo {
+      connection_id,
+      port_id: counterparty.port_id,
+      chain_id: counterparty.chain_id,
+    })
+    .collect();
+  Ok(CounterpartiesResponse { counterparties })
+}
+
+fn query_stats(ctx: &QueryContext) -> ContractResult<StatsResponse> {
+  let stats = load_stats(ctx.deps.storage)?;
+  let fees_collected = load_fee_stats(ctx.deps.storage)?
+    .into_iter()
+    .map(|(denom, stats)| Coin { denom, amount: stats.collected })
+    .collect();
+  Ok(StatsResponse {
+    notes: stats.notes,
+    bytes: stats.bytes,
+    unique_senders: stats.senders,
+    unique_recipients: stats.recipients,
+    sealed_notes: stats.sealed_notes,
+    keys: stats.keys,
+    fees_collected,
+  })
+}
+
+fn query_credits(ctx: &QueryContext, address: String) -> ContractResult<CreditsResponse> {
+  let address = ctx.deps.api.addr_validate(address.as_str())?;
+  let credits = load_credits(ctx.deps.storage, address)?;
+  Ok(CreditsResponse { credits })
+}
+
+fn query_sponsor(ctx: &QueryContext, address: String) -> ContractResult<SponsorResponse> {
+  let address = ctx.deps.api.addr_validate(address.as_str())?;
+  let rules = load_sponsor(ctx.deps.storage, address.clone())?;
+  let budget = load_credits(ctx.deps.storage, address)?;
+  Ok(SponsorResponse { rules, budget })
+}
+
+fn query_sponsorship(ctx: &QueryContext, sponsor: String, sender: String) -> ContractResult<SponsorshipResponse> {
+  let sponsor = ctx.deps.api.addr_validate(sponsor.as_str())?;
+  let sender = ctx.deps.api.addr_validate(sender.as_str())?;
+  let spent = load_sponsorship(ctx.deps.storage, sender.clone(), sponsor.clone())?;
+  Ok(SponsorshipResponse {
+    sponsored: spent.is_some(),
+    offered: has_sponsorship_offer(ctx.deps.storage, sender, sponsor),
+    spent: spent.unwrap_or_default(),
+  })
+}
+
+fn query_sealed_notes(ctx: &QueryContext, recipient: String, start_after: Option<u64>, limit: Option<u32>) -> ContractResult<SealedNotesResponse> {
+  let recipient = ctx.deps.api.addr_validate(recipient.as_str())?;
+  let limit = page_limit(limit);
+  let start = start_after.map(|seq| seq.saturating_add(1)).unwrap_or(0);
+  let notes = load_sealed_notes(ctx.deps.storage, recipient.clone(), start, limit + 1)?;
+  let (notes, next_cursor) = paginate(notes, limit, |(seq, _)| *seq);
+  Ok(SealedNotesResponse {
+    notes: notes.into_iter().map(|(_, note)| note).collect(),
+    count: load_sealed_count(ctx.deps.storage, recipient)?,
+    next_cursor,
+  })
+}
+
+fn query_postage(ctx: &QueryContext, address: String) -> ContractResult<PostageResponse> {
+  let address = ctx.deps.api.addr_validate(address.as_str())?;
+  let postage = load_postage(ctx.deps.storage, address)?;
+  Ok(PostageResponse { postage })
+}
+
+fn query_relay_nonce(ctx: &QueryContext, address: String) -> ContractResult<u64> {
+  let address = ctx.deps.api.addr_validate(address.as_str())?;
+  load_relay_nonce(ctx.deps.storage, address)
+}
+
+fn query_note_count(ctx: &QueryContext, recipient: String, sender: String) -> ContractResult<u64> {
+  let recipient = ctx.deps.api.addr_validate(recipient.as_str())?;
+  let sender = validate_sender(ctx.deps.api, sender.as_str())?;
+  let meta = load_note_meta(ctx.deps.storage, sender, recipient)?;
+  Ok(meta.count)
+}
+
+fn query_senders(ctx: &QueryContext, recipient: String, start_after: Option<String>, limit: Option<u32>) -> ContractResult<SendersResponse> {
+  let recipient = ctx.deps.api.addr_validate(recipient.as_str())?;
+  let start_after = start_after.map(|a| validate_sender(ctx.deps.api, a.as_str())).transpose()?;
+  let limit = page_limit(limit);
+
+  let senders = find_senders(ctx.deps.storage, recipient.clone(), start_after, limit + 1)?;
+  let (senders, next_cursor) = paginate(senders, limit, |(sender, _)| sender.to_string());
+  let senders = senders.into_iter()
+    .map(|(sender, meta)| SenderSummary {
+      sender: sender.to_string(),
+      count: meta.count,
+      last_timestamp: meta.last_timestamp,
+    })
+    .collect();
+  Ok(SendersResponse { senders, next_cursor })
+}
+
+fn query_conversations(
+  ctx: &QueryContext,
+  recipient: String,
+  start_after: Option<String>,
+  limit: Option<u32>,
+  order_by: Option<ConversationOrder>,
+) -> ContractResult<ConversationsResponse> {
+  let recipient = ctx.deps.api.addr_validate(recipient.as_str())?;
+  let limit = page_limit(limit);
+
+  let (senders, next_cursor) = match order_by.unwrap_or(ConversationOrder::LastActivity) {
+    ConversationOrder::LastActivity => {
+      let start_after = start_after.map(|cursor| parse_activity_cursor(ctx.deps.api, cursor.as_str())).transpose()?;
+      let senders = find_recent_senders(ctx.deps.storage, recipient.clone(), start_after, limit + 1)?;
+      paginate(senders, limit, |(sender, meta)| format!("{}/{}", meta.last_timestamp.nanos(), sender))
+    }
+    ConversationOrder::Sender => {
+      let start_after = start_after.map(|a| validate_sender(ctx.deps.api, a.as_str())).transpose()?;
+      let senders = find_senders(ctx.deps.storage, recipient.clone(), start_after, limit + 1)?;
+      paginate(senders, limit, |(sender, _)| sender.to_string())
+    }
+  };
+
+  let conversations = senders.into_iter()
+    .map(|(sender, meta)| {
+      let mut last = load_note(ctx.deps.storage, recipient.clone(), sender.clone(), meta.count - 1)?;
+      last.note.truncate(NOTE_PREVIEW_LEN);
+      Ok(Conversation {
+        sender: sender.to_string(),
+        count: meta.count,
+        unread: meta.count - meta.read,
+        last_timestamp: meta.last_timestamp,
+        preview: last.note,
+      })
+    })
+    .collect::<ContractResult<_>>()?;
+  Ok(ConversationsResponse { conversations, next_cursor })
+}
+
+fn query_enc_key(ctx: &QueryContext, address: String) -> ContractResult<EncryptionKeyResponse> {
+  let address = ctx.deps.api.addr_validate(address.as_str())?;
+  let key = load_enc_key(ctx.deps.storage, address)?;
+  Ok(EncryptionKeyResponse { key })
+}
+
+fn query_remote_enc_key(ctx: &QueryContext, chain: String, address: String) -> ContractResult<RemoteEncryptionKeyResponse> {
+  let cached = load_remote_key(ctx.deps.storage, chain, address)?
+    .map(|cached| (cached.fetched_at.plus_seconds(crate::ibc::REMOTE_KEY_TTL), cached))
+    .filter(|(expires, _)| ctx.env.block.time < *expires);
+  Ok(match cached {
+    Some((expires, cached)) => RemoteEncryptionKeyResponse {
+      key: cached.key,
+      channel: Some(cached.channel),
+      expires: Some(expires),
+    },
+    None => RemoteEncryptionKeyResponse { key: None, channel: None, expires: None },
+  })
+}
+
+fn query_enc_keys(ctx: &QueryContext, addresses: Vec<String>) -> ContractResult<EncryptionKeysResponse> {
+  ensure_batch_size(addresses.len())?;
+
+  let load = |address: &str| -> ContractResult<Vec<u8>> {
+    let address = ctx.deps.api.addr_validate(address)?;
+    load_enc_key(ctx.deps.storage, address)?.ok_or(ContractError::NoEncryptionKey {})
+  };
+  let keys = addresses.into_iter()
+    .map(|address| match load(address.as_str()) {
+      Ok(key) => EncryptionKeyEntry { address, key: Some(key), error: None },
+      Err(err) => EncryptionKeyEntry { address, key: None, error: Some(err.to_string()) },
+    })
+    .collect();
+  Ok(EncryptionKeysResponse { keys })
+}
+
+fn query_note_counts(ctx: &QueryContext, pairs: Vec<NotePair>) -> ContractResult<NoteCountsResponse> {
+  ensure_batch_size(pairs.len())?;
+
+  let load = |pair: &NotePair| -> ContractResult<u64> {
+    let recipient = ctx.deps.api.addr_validate(pair.recipient.as_str())?;
+    let sender = validate_sender(ctx.deps.api, pair.sender.as_str())?;
+    Ok(find_note_meta(ctx.deps.storage, sender, recipient)?.map(|meta| meta.count).unwrap_or(0))
+  };
+  let counts = pairs.into_iter()
+    .map(|pair| {
+      let (count, error) = match load(&pair) {
+        Ok(count) => (Some(count), None),
+        Err(err) => (None, Some(err.to_string())),
+      };
+      NoteCountEntry { recipient: pair.recipient, sender: pair.sender, count, error }
+    })
+    .collect();
+  Ok(NoteCountsResponse { counts })
+}
+
+fn ensure_batch_size(len: usize) -> ContractResult<()> {
+  if len > MAX_BATCH_SIZE {
+    return Err(ContractError::BatchTooLarge { max: MAX_BATCH_SIZE });
+  }
+  Ok(())
+}
+
+fn query_notes(
+  ctx: &QueryContext,
+  recipient: String,
+  sender: String,
+  start_after: Option<u64>,
+  limit: Option<u32>,
+  range: NoteRange,
+) -> ContractResult<NotesResponse> {
+  let recipient = ctx.deps.api.addr_validate(recipient.as_str())?;
+  let sender = validate_sender(ctx.deps.api, sender.as_str())?;
+  let limit = page_limit(limit);
+
+  // translate the range into note indices, which grow with time & height
+  let store = ctx.deps.storage;
+  let by_time = |time| find_note_index_by_time(store, recipient.clone(), sender.clone(), time);
+  let by_height = |height| find_note_index_by_height(store, recipient.clone(), sender.clone(), height);
+  let start = [
+    start_after.map(|idx| idx.saturating_add(1)),
+    range.since.map(by_time).transpose()?,
+    range.since_height.map(by_height).transpose()?,
+  ].into_iter().flatten().max().unwrap_or(0);
+  let end = [
+    range.until.map(by_time).transpose()?,
+    range.until_height.map(by_height).transpose()?,
+  ].into_iter().flatten().min();
+
+  let notes = load_notes(store, recipient.clone(), sender.clone(), start, end, limit + 1)?;
+  let (notes, next_cursor) = paginate(notes, limit, |(idx, _)| *idx);
+  Ok(NotesResponse {
+    notes: notes.into_iter().map(|(_, note)| note).collect(),
+    next_cursor,
+  })
+}
+
+fn find_coin(denom: &str, coins: &[Coin]) -> Option<Coin> {
+  coins.iter().find(|coin| coin.denom == denom).cloned()
+}
+
+/// Collect a fee and distribute it right away.
+fn collect_fee(ctx: &mut ExecuteContext, fees: &Fees, amount: Uint128) -> ContractResult<FeePayment> {
+  let mut payment = charge_fee(ctx, fees, amount)?;
+  if let Some(coin) = &payment.paid {
+    payment.msgs = distribute_fee(ctx.deps.storage, fees, coin)?;
+  }
+  Ok(payment)
+}
+
+/// Charge a fee to the attached funds. When no funds of the fee denom are attached, the fee is
+/// covered by a sponsor of the sender if any, otherwise drawn from the sender's prepaid credits.
+fn charge_fee(ctx: &mut ExecuteContext, fees: &Fees, amount: Uint128) -> ContractResult<FeePayment> {
+  let mut payment = FeePayment::default();
+  let tier = load_fee_tier(ctx.deps.storage, ctx.info.sender.clone())?;
+  let amount = apply_fee_tier(tier.as_ref(), amount);
+  if amount.is_zero() {
+    return Ok(payment);
+  }
+
+  let coin = match find_coin(&fees.denom, &ctx.info.funds) {
+    Some(coin) => {
+      if coin.amount < amount {
+        return Err(ContractError::InsufficientFunds {});
+      }
+      coin
+    }
+    None => {
+      let coin = Coin::new(amount.u128(), fees.denom.clone());
+      payment.sponsor = charge_sponsor(ctx, &coin)?;
+      if payment.sponsor.is_none() {
+        sub_credits(ctx.deps.storage, ctx.info.sender.clone(), &coin)?;
+      }
+      coin
+    }
+  };
+  payment.paid = Some(coin);
+  Ok(payment)
+}
+
+/// Apply an address' fee tier to a regular fee amount. Discounts are rounded down.
+fn apply_fee_tier(tier: Option<&FeeTier>, amount: Uint128) -> Uint128 {
+  match tier {
+    Some(FeeTier::Exempt {}) => Uint128::zero(),
+    Some(FeeTier::Discount { bps }) => amount - amount.multiply_ratio(*bps, BPS_DENOMINATOR),
+    None => amount,
+  }
+}
+
+/// Charge the fee to the first sponsor of the sender whose rules and budget cover it.
+fn charge_sponsor(ctx: &mut ExecuteContext, coin: &Coin) -> ContractResult<Option<Addr>> {
+  let sender = ctx.info.sender.clone();
+  for (sponsor, spent) in find_sponsorships(ctx.deps.storage, sender.clone())? {
+    let Some(rules) = load_sponsor(ctx.deps.storage, sponsor.clone())? else {
+      continue;
+    };
+    if rules.expires.is_some_and(|expires| ctx.env.block.time >= expires) {
+      continue;
+    }
+    if rules.per_sender_cap.is_some_and(|cap| cap.denom != coin.denom || spent + coin.amount > cap.amount) {
+      continue;
+    }
+    if load_credit(ctx.deps.storage, sponsor.clone(), &coin.denom)? < coin.amount {
+      continue;
+    }
+
+    sub_credits(ctx.deps.storage, sponsor.clone(), coin)?;
+    save_sponsorship(ctx.deps.storage, sender, sponsor.clone(), spent + coin.amount)?;
+    return Ok(Some(sponsor));
+  }
+  Ok(None)
+}
+
+/// Parse a `{nanos}/{sender}` cursor of conversations ordered by last activity. The position stays
+/// valid when the sender's conversation moves to the top in between pages.
+fn parse_activity_cursor(api: &dyn Api, cursor: &str) -> ContractResult<(u64, Addr)> {
+  let (nanos, sender) = cursor.split_once('/').ok_or(ContractError::InvalidCursor {})?;
+  let nanos = nanos.parse().map_err(|_| ContractError::InvalidCursor {})?;
+  Ok((nanos, validate_sender(api, sender)?))
+}
+
+/// Validate the address of a note sender, which may also be the identity of a remote sender.
+fn validate_sender(api: &dyn Api, sender: &str) -> StdResult<Addr> {
+  if crate::ibc::is_remote_sender(sender) {
+    return Ok(Addr::unchecked(sender));
+  }
+  api.addr_validate(sender)
+}
+
+fn ensure_contract(ctx: &ExecuteContext, address: &Addr) -> ContractResult<()> {
+  if ctx.deps.querier.query_wasm_contract_info(address).is_err() {
+    return Err(ContractError::NotAContract {});
+  }
+  Ok(())
+}
+
+fn ensure_admin(ctx: &ExecuteContext) -> ContractResult<Ownership> {
+  let ownership = load_ownership(ctx.deps.storage)?;
+  match &ownership.owner {
+    Some(owner) if *owner == ctx.info.sender => Ok(ownership),
+    _ => Err(ContractError::Unauthorized { role: Role::Owner }),
+  }
+}
+
+/// Ensure the sender holds the given role. The owner implicitly holds all roles.
+fn ensure_role(ctx: &ExecuteContext, role: Role) -> ContractResult<()> {
+  let ownership = load_ownership(ctx.deps.storage)?;
+  if ownership.owner.is_some_and(|owner| owner == ctx.info.sender) {
+    return Ok(());
+  }
+  if role != Role::Owner && has_role(ctx.deps.storage, role, ctx.info.sender.clone())? {
+    return Ok(());
+  }
+  Err(ContractError::Unauthorized { role })
+}
+
+fn validate_fees(api: &dyn Api, fees: FeesMsg) -> ContractResult<Fees> {
+  Ok(Fees {
+    beneficiaries: validate_beneficiaries(api, fees.beneficiaries)?,
+    store_keys: fees.store_keys,
+    store_notes: fees.store_notes,
+    denom: fees.denom,
+    burn_fees: fees.burn_fees,
+    accumulate: fees.accumulate,
+    update_delay: fees.update_delay,
+  })
+}
+
+fn validate_beneficiaries(api: &dyn Api, beneficiaries: Vec<BeneficiaryMsg>) -> ContractResult<Vec<Beneficiary>> {
+  if beneficiaries.is_empty() {
+    return Ok(vec![]);
+  }
+
+  let total: u32 = beneficiaries.iter().map(|b| b.bps as u32).sum();
+  if total != BPS_DENOMINATOR as u32 || beneficiaries.iter().any(|b| b.bps == 0) {
+    return Err(ContractError::InvalidBeneficiaries {});
+  }
+
+  beneficiaries.into_iter()
+    .map(|b| Ok(Beneficiary {
+      address: b.address.map(|a| api.addr_validate(a.as_str())).transpose()?,
+      bps: b.bps,
+    }))
+    .collect()
+}
+
+/// Split a collected fee amount into shares. An unset address means the share is burnt.
+/// The rounding remainder is always attributed to the first beneficiary.
+fn split_fee(fees: &Fees, owner: Option<Addr>, amount: Uint128) -> Vec<(Option<Addr>, Uint128)> {
+  if fees.burn_fees {
+    return vec![(None, amount)];
+  }
+  if fees.beneficiaries.is_empty() {
+    return vec![(owner, amount)];
+  }
+
+  let mut shares: Vec<(Option<Addr>, Uint128)> = fees.beneficiaries.iter()
+    .map(|b| (b.address.clone(), amount.multiply_ratio(b.bps, BPS_DENOMINATOR)))
+    .collect();
+  let distributed: Uint128 = shares.iter().map(|(_, share)| *share).sum();
+  shares[0].1 += amount - distributed;
+  shares
+}
+
+/// Distribute a collected fee among its beneficiaries. Shares are either sent out directly or,
+/// in accumulating mode, credited to the beneficiaries' fee balances. Burnt shares are always
+/// burnt immediately.
+pub(crate) fn distribute_fee(store: &mut dyn Storage, fees: &Fees, coin: &Coin) -> ContractResult<Vec<CosmosMsg>> {
+  let mut msgs: Vec<CosmosMsg> = vec![];
+  let mut burnt = Uint128::zero();
+  let mut sent = Uint128::zero();
+
+  let owner = load_ownership(store)?.owner;
+  for (address, amount) in split_fee(fees, owner, coin.amount) {
+    if amount.is_zero() {
+      continue;
+    }
+    let share = Coin::new(amount.u128(), coin.denom.clone());
+    match address {
+      Some(address) if fees.accumulate => add_fee_balance(store, address, &share)?,
+      Some(address) => {
+        sent += amount;
+        msgs.push(CosmosMsg::Bank(BankMsg::Send {
+          to_address: address.to_string(),
+          amount: vec![share],
+        }));
+      }
+      None => burnt += amount,
+    }
+  }
+
+  if !burnt.is_zero() {
+    msgs.push(CosmosMsg::Bank(BankMsg::Burn {
+      amount: vec![Coin::new(burnt.u128(), coin.denom.clone())],
+    }));
+  }
+
+  update_fee_stats(store, &coin.denom, |stats| {
+    stats.collected += coin.amount;
+    stats.burned += burnt;
+    stats.distributed += sent;
+  })?;
+  Ok(msgs)
+}
+
+#[derive(Default)]
+struct FeePayment {
+  msgs: Vec<CosmosMsg>,
+  paid: Option<Coin>,
+  sponsor: Option<Addr>,
+}
+
+impl FeePayment {
+  fn apply(self, response: Response) -> Response {
+    let response = match self.sponsor {
+      Some(sponsor) => response.add_attribute("sponsor", sponsor),
+      None => response,
+    };
+    response.add_messages(self.msgs)
+  }
+}
+
+struct ExecuteContext<'a> {
+  deps: DepsMut<'a>,
+  #[allow(dead_code)]
+  env: Env,
+  info: MessageInfo,
+}
+
+struct QueryContext<'a> {
+  deps: Deps<'a>,
+  #[allow(dead_code)]
+  env: Env,
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info, MockApi};
+  use cosmwasm_std::{coins, from_json, SubMsg};
+
+  use crate::msg::NotePayload;
+
+  #[test]
+  #[allow(clippy::useless_vec)]
+  fn init() {
     let mut deps = mock_dependencies();
 
-    let msg = InstantiateMsg {
+    let msg = InstantiateMsg {
+      admin: None,
+      denom: "luna".to_string(),
+      store_keys_fee: Uint128::new(1000000), // 1L
+      store_notes_fee: Uint128::new(500000), // 0.5L
+    };
+    let info = mock_info("admin", &vec![]);
+
+    instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+  }
+
+  #[test]
+  #[allow(clippy::useless_vec)]
+  fn store_stuff() {
+    let mut owndeps = mock_dependencies();
+    instantiate_no_fees(owndeps.as_mut());
+
+    let ctx = ExecuteContext {
+      deps: owndeps.as_mut(),
+      env: mock_env(),
+      info: mock_info("alice", &vec![]),
+    };
+    exec_update_key(ctx, "foobar".to_string()).unwrap();
+
+    let ctx = ExecuteContext {
+      deps: owndeps.as_mut(),
+      env: mock_env(),
+      info: mock_info("alice", &vec![]),
+    };
+    exec_store_note(ctx, "bob".to_string(), "barfoo".to_string()).unwrap();
+
+    let msg = QueryMsg::EncryptionKey { address: "alice".to_string() };
+    let bin = query(owndeps.as_ref(), mock_env(), msg).unwrap();
+    let res = from_json::<EncryptionKeyResponse>(&bin).unwrap();
+    assert_eq!(res.key.unwrap(), "foobar".as_bytes().to_owned());
+
+    let msg = QueryMsg::NoteCount { recipient: "bob".to_string(), sender: "alice".to_string() };
+    let bin = query(owndeps.as_ref(), mock_env(), msg).unwrap();
+    let res = from_json::<u64>(&bin).unwrap();
+    assert_eq!(res, 1);
+
+    let msg = QueryMsg::Notes { recipient: "bob".to_string(), sender: "alice".to_string(), start_after: None, limit: None, range: None };
+    let bin = query(owndeps.as_ref(), mock_env(), msg).unwrap();
+    let res = from_json::<NotesResponse>(&bin).unwrap().notes;
+    assert_eq!(res.len(), 1);
+    assert_eq!(res[0].note, "barfoo".as_bytes().to_owned());
+  }
+
+  #[test]
+  #[allow(clippy::useless_vec)]
+  fn fees_update_key() {
+    let mut owndeps = mock_dependencies();
+    instantiate_default(owndeps.as_mut());
+
+    // fails with insufficient fees
+    let ctx = ExecuteContext {
+      deps: owndeps.as_mut(),
+      env: mock_env(),
+      info: mock_info("alice", &coins(1, "luna")),
+    };
+    exec_update_key(ctx, "foobar".to_string()).expect_err("Unexpected success");
+
+    // fails with no funds
+    let ctx = ExecuteContext {
+      deps: owndeps.as_mut(),
+      env: mock_env(),
+      info: mock_info("alice", &vec![]),
+    };
+    exec_update_key(ctx, "foobar".to_string()).expect_err("Unexpected success");
+
+    // success with exact fees
+    let ctx = ExecuteContext {
+      deps: owndeps.as_mut(),
+      env: mock_env(),
+      info: mock_info("alice", &coins(1000000, "luna")),
+    };
+    let res = exec_update_key(ctx, "foobar".to_string()).unwrap();
+    assert!(res.messages.iter().any(|submsg| is_fee_msg(submsg, "admin", "luna", 1000000)));
+
+    // success with excess fees
+    let ctx = ExecuteContext {
+      deps: owndeps.as_mut(),
+      env: mock_env(),
+      info: mock_info("alice", &coins(1500000, "luna")),
+    };
+    let res = exec_update_key(ctx, "foobar".to_string()).unwrap();
+    assert!(res.messages.iter().any(|submsg| is_fee_msg(submsg, "admin", "luna", 1500000)));
+  }
+
+  #[test]
+  #[allow(clippy::useless_vec)]
+  fn fees_store_note() {
+    let mut owndeps = mock_dependencies();
+    instantiate_default(owndeps.as_mut());
+
+    // fails with insufficient fees
+    let ctx = ExecuteContext {
+      deps: owndeps.as_mut(),
+      env: mock_env(),
+      info: mock_info("alice", &coins(1, "luna")),
+    };
+    exec_store_note(ctx, "bob".to_string(), "barfoo".to_string()).expect_err("Unexpected success");
+
+    // fails with no funds
+    let ctx = ExecuteContext {
+      deps: owndeps.as_mut(),
+      env: mock_env(),
+      info: mock_info("alice", &vec![]),
+    };
+    exec_store_note(ctx, "bob".to_string(), "barfoo".to_string()).expect_err("Unexpected success");
+
+    // success with exact fees
+    let ctx = ExecuteContext {
+      deps: owndeps.as_mut(),
+      env: mock_env(),
+      info: mock_info("alice", &coins(500000, "luna")),
+    };
+    let res = exec_store_note(ctx, "bob".to_string(), "barfoo".to_string()).unwrap();
+    assert!(res.messages.iter().any(|submsg| is_fee_msg(submsg, "admin", "luna", 500000)));
+
+    // success with excess fees
+    let ctx = ExecuteContext {
+      deps: owndeps.as_mut(),
+      env: mock_env(),
+      info: mock_info("alice", &coins(750000, "luna")),
+    };
+    let res = exec_store_note(ctx, "bob".to_string(), "barfoo".to_string()).unwrap();
+    assert!(res.messages.iter().any(|submsg| is_fee_msg(submsg, "admin", "luna", 750000)));
+  }
+
+  #[test]
+  fn update_fees() {
+    let mut owndeps = mock_dependencies();
+    instantiate_default(owndeps.as_mut());
+
+    // fails with unauthorized
+    let msg = ExecuteMsg::UpdateFees {
+      store_keys: Uint128::new(1000000),
+      store_notes: Uint128::new(500000),
+      denom: "luna".to_string(),
+      burn_fees: false,
+      beneficiaries: vec![],
+      accumulate: false,
+    };
+    execute(owndeps.as_mut(), mock_env(), mock_info("alice", &[]), msg).expect_err("Unexpected success");
+
+    // success with authorized
+    let msg = ExecuteMsg::UpdateFees {
+      store_keys: Uint128::new(2000000),
+      store_notes: Uint128::new(1000000),
+      denom: "luna".to_string(),
+      burn_fees: false,
+      beneficiaries: vec![],
+      accumulate: false,
+    };
+    execute(owndeps.as_mut(), mock_env(), mock_info("admin", &[]), msg).unwrap();
+
+    // query updated fees
+    let msg = QueryMsg::Fees {};
+    let bin = query(owndeps.as_ref(), mock_env(), msg).unwrap();
+    let res = from_json::<FeesResponse>(&bin).unwrap().fees;
+    assert_eq!(res.store_keys, Uint128::new(2000000));
+    assert_eq!(res.store_notes, Uint128::new(1000000));
+
+    // transfer admin to alice
+    let msg = ExecuteMsg::ProposeAdmin { address: "alice".to_string(), expiry: None };
+    execute(owndeps.as_mut(), mock_env(), mock_info("admin", &[]), msg).unwrap();
+    execute(owndeps.as_mut(), mock_env(), mock_info("alice", &[]), ExecuteMsg::AcceptAdmin {}).unwrap();
+
+    // success with new admin
+    let msg = ExecuteMsg::UpdateFees {
+      store_keys: Uint128::new(3000000),
+      store_notes: Uint128::new(1500000),
+      denom: "luna".to_string(),
+      burn_fees: false,
+      beneficiaries: vec![],
+      accumulate: false,
+    };
+    execute(owndeps.as_mut(), mock_env(), mock_info("alice", &[]), msg).unwrap();
+
+    // failure with old admin
+    let msg = ExecuteMsg::UpdateFees {
+      store_keys: Uint128::new(4000000),
+      store_notes: Uint128::new(2000000),
+      denom: "luna".to_string(),
+      burn_fees: false,
+      beneficiaries: vec![],
+      accumulate: false,
+    };
+    execute(owndeps.as_mut(), mock_env(), mock_info("admin", &[]), msg).expect_err("Unexpected success");
+  }
+
+  #[test]
+  fn scheduled_fee_update() {
+    let mut owndeps = mock_dependencies();
+    instantiate_default(owndeps.as_mut());
+
+    let fees = |store_notes: u128, update_delay: u64| FeesMsg {
+      store_keys: Uint128::new(1000000),
+      store_notes: Uint128::new(store_notes),
       denom: "luna".to_string(),
-      store_keys_fee: Uint128::new(1000000), // 1L
-      store_notes_fee: Uint128::new(500000), // 0.5L
+      burn_fees: false,
+      beneficiaries: vec![],
+      accumulate: false,
+      update_delay,
     };
-    let info = mock_info("admin", &vec![]);
+    let now = mock_env().block.time;
+
+    // instant updates supersede pending scheduled updates
+    let msg = ExecuteMsg::ScheduleFeeUpdate { fees: fees(2000000, 0), effective_at: now.plus_seconds(60) };
+    execute(owndeps.as_mut(), mock_env(), mock_info("admin", &[]), msg).unwrap();
+    let msg = ExecuteMsg::UpdateFees {
+      store_keys: Uint128::new(1000000),
+      store_notes: Uint128::new(500000),
+      denom: "luna".to_string(),
+      burn_fees: false,
+      beneficiaries: vec![],
+      accumulate: false,
+    };
+    let res = execute(owndeps.as_mut(), mock_env(), mock_info("admin", &[]), msg).unwrap();
+    assert!(res.events.iter().any(|event| event.ty == crate::events::FEE_UPDATE_CANCELLED));
+    let bin = query(owndeps.as_ref(), mock_env(), QueryMsg::Fees {}).unwrap();
+    assert!(from_json::<FeesResponse>(&bin).unwrap().scheduled.is_none());
+
+    // without notice, updates may take effect right away
+    let msg = ExecuteMsg::ScheduleFeeUpdate { fees: fees(500000, 3600), effective_at: now };
+    execute(owndeps.as_mut(), mock_env(), mock_info("admin", &[]), msg).unwrap();
+
+    // instant updates are refused once a notice period is configured
+    let msg = ExecuteMsg::UpdateFees {
+      store_keys: Uint128::new(1000000),
+      store_notes: Uint128::new(1),
+      denom: "luna".to_string(),
+      burn_fees: false,
+      beneficiaries: vec![],
+      accumulate: false,
+    };
+    let err = execute(owndeps.as_mut(), mock_env(), mock_info("admin", &[]), msg).unwrap_err();
+    assert!(matches!(err, ContractError::FeeUpdateDelayed { delay: 3600 }));
+
+    // fails with insufficient notice
+    let msg = ExecuteMsg::ScheduleFeeUpdate { fees: fees(1000000, 3600), effective_at: now.plus_seconds(3599) };
+    let err = execute(owndeps.as_mut(), mock_env(), mock_info("admin", &[]), msg).unwrap_err();
+    assert!(matches!(err, ContractError::FeeUpdateTooEarly { .. }));
+
+    let msg = ExecuteMsg::ScheduleFeeUpdate { fees: fees(1000000, 3600), effective_at: now.plus_seconds(3600) };
+    execute(owndeps.as_mut(), mock_env(), mock_info("admin", &[]), msg).unwrap();
+
+    let bin = query(owndeps.as_ref(), mock_env(), QueryMsg::Fees {}).unwrap();
+    let res = from_json::<FeesResponse>(&bin).unwrap();
+    assert_eq!(res.fees.store_notes, Uint128::new(500000));
+    assert_eq!(res.scheduled.unwrap().fees.store_notes, Uint128::new(1000000));
+
+    // old fees apply until the update takes effect
+    let ctx = ExecuteContext {
+      deps: owndeps.as_mut(),
+      env: mock_env(),
+      info: mock_info("alice", &coins(500000, "luna")),
+    };
+    exec_store_note(ctx, "bob".to_string(), "barfoo".to_string()).unwrap();
+
+    let mut later = mock_env();
+    later.block.time = now.plus_seconds(3600);
+
+    let bin = query(owndeps.as_ref(), later.clone(), QueryMsg::Fees {}).unwrap();
+    let res = from_json::<FeesResponse>(&bin).unwrap();
+    assert_eq!(res.fees.store_notes, Uint128::new(1000000));
+    assert!(res.scheduled.is_none());
+
+    let ctx = ExecuteContext {
+      deps: owndeps.as_mut(),
+      env: later.clone(),
+      info: mock_info("alice", &coins(500000, "luna")),
+    };
+    exec_store_note(ctx, "bob".to_string(), "barfoo".to_string()).expect_err("Unexpected success");
+
+    // cancelled updates never take effect
+    let msg = ExecuteMsg::ScheduleFeeUpdate { fees: fees(2000000, 3600), effective_at: now.plus_seconds(7200) };
+    execute(owndeps.as_mut(), later.clone(), mock_info("admin", &[]), msg).unwrap();
+    execute(owndeps.as_mut(), later.clone(), mock_info("alice", &[]), ExecuteMsg::CancelFeeUpdate {}).expect_err("Unexpected success");
+    execute(owndeps.as_mut(), later.clone(), mock_info("admin", &[]), ExecuteMsg::CancelFeeUpdate {}).unwrap();
+    execute(owndeps.as_mut(), later.clone(), mock_info("admin", &[]), ExecuteMsg::CancelFeeUpdate {}).expect_err("Unexpected success");
+
+    later.block.time = now.plus_seconds(7200);
+    let bin = query(owndeps.as_ref(), later, QueryMsg::Fees {}).unwrap();
+    let res = from_json::<FeesResponse>(&bin).unwrap();
+    assert_eq!(res.fees.store_notes, Uint128::new(1000000));
+  }
+
+  #[test]
+  fn admin_transfer() {
+    let mut owndeps = mock_dependencies();
+    instantiate_default(owndeps.as_mut());
+
+    // fails without pending transfer
+    execute(owndeps.as_mut(), mock_env(), mock_info("alice", &[]), ExecuteMsg::AcceptAdmin {}).expect_err("Unexpected success");
+
+    // fails with unauthorized
+    let msg = ExecuteMsg::ProposeAdmin { address: "alice".to_string(), expiry: None };
+    execute(owndeps.as_mut(), mock_env(), mock_info("alice", &[]), msg).expect_err("Unexpected success");
+
+    let expiry = mock_env().block.time.plus_seconds(60);
+    let msg = ExecuteMsg::ProposeAdmin { address: "alice".to_string(), expiry: Some(expiry) };
+    execute(owndeps.as_mut(), mock_env(), mock_info("admin", &[]), msg).unwrap();
+
+    let bin = query(owndeps.as_ref(), mock_env(), QueryMsg::Ownership {}).unwrap();
+    let res = from_json::<Ownership>(&bin).unwrap();
+    assert_eq!(res.owner.unwrap(), "admin");
+    assert_eq!(res.pending_owner.unwrap(), "alice");
+    assert_eq!(res.pending_expiry, Some(expiry));
+
+    // fails with wrong accepter
+    execute(owndeps.as_mut(), mock_env(), mock_info("bob", &[]), ExecuteMsg::AcceptAdmin {}).expect_err("Unexpected success");
+
+    // fails once expired
+    let mut env = mock_env();
+    env.block.time = expiry;
+    execute(owndeps.as_mut(), env, mock_info("alice", &[]), ExecuteMsg::AcceptAdmin {}).expect_err("Unexpected success");
+
+    // cancelled transfers cannot be accepted
+    execute(owndeps.as_mut(), mock_env(), mock_info("admin", &[]), ExecuteMsg::CancelAdminTransfer {}).unwrap();
+    execute(owndeps.as_mut(), mock_env(), mock_info("alice", &[]), ExecuteMsg::AcceptAdmin {}).expect_err("Unexpected success");
+
+    let msg = ExecuteMsg::ProposeAdmin { address: "alice".to_string(), expiry: None };
+    execute(owndeps.as_mut(), mock_env(), mock_info("admin", &[]), msg).unwrap();
+    execute(owndeps.as_mut(), mock_env(), mock_info("alice", &[]), ExecuteMsg::AcceptAdmin {}).unwrap();
+
+    let bin = query(owndeps.as_ref(), mock_env(), QueryMsg::Ownership {}).unwrap();
+    let res = from_json::<Ownership>(&bin).unwrap();
+    assert_eq!(res.owner.unwrap(), "alice");
+    assert_eq!(res.pending_owner, None);
+
+    // fees are directed at the new admin
+    let ctx = ExecuteContext {
+      deps: owndeps.as_mut(),
+      env: mock_env(),
+      info: mock_info("bob", &coins(500000, "luna")),
+    };
+    let res = exec_store_note(ctx, "charlie".to_string(), "barfoo".to_string()).unwrap();
+    assert!(res.messages.iter().any(|submsg| is_fee_msg(submsg, "alice", "luna", 500000)));
+
+    // fees are burnt after renouncing
+    execute(owndeps.as_mut(), mock_env(), mock_info("admin", &[]), ExecuteMsg::RenounceAdmin {}).expect_err("Unexpected success");
+    execute(owndeps.as_mut(), mock_env(), mock_info("alice", &[]), ExecuteMsg::RenounceAdmin {}).unwrap();
+
+    let ctx = ExecuteContext {
+      deps: owndeps.as_mut(),
+      env: mock_env(),
+      info: mock_info("bob", &coins(500000, "luna")),
+    };
+    let res = exec_store_note(ctx, "charlie".to_string(), "barfoo".to_string()).unwrap();
+    assert_eq!(res.messages[0].msg, CosmosMsg::Bank(BankMsg::Burn { amount: coins(500000, "luna") }));
+  }
+
+  #[test]
+  fn roles() {
+    let mut owndeps = mock_dependencies();
+    instantiate_default(owndeps.as_mut());
+
+    let update_fees = || ExecuteMsg::UpdateFees {
+      store_keys: Uint128::new(2000000),
+      store_notes: Uint128::new(1000000),
+      denom: "luna".to_string(),
+      burn_fees: false,
+      beneficiaries: vec![],
+      accumulate: false,
+    };
+
+    let err = execute(owndeps.as_mut(), mock_env(), mock_info("manager", &[]), update_fees()).unwrap_err();
+    assert!(matches!(err, ContractError::Unauthorized { role: Role::FeeManager }));
+
+    // only the owner grants roles
+    let msg = ExecuteMsg::GrantRole { address: "manager".to_string(), role: Role::FeeManager };
+    let err = execute(owndeps.as_mut(), mock_env(), mock_info("manager", &[]), msg).unwrap_err();
+    assert!(matches!(err, ContractError::Unauthorized { role: Role::Owner }));
+
+    // the owner role is only transferred through ownership
+    let msg = ExecuteMsg::GrantRole { address: "manager".to_string(), role: Role::Owner };
+    execute(owndeps.as_mut(), mock_env(), mock_info("admin", &[]), msg).expect_err("Unexpected success");
+
+    for address in ["manager", "pauser1", "pauser2", "pauser3"] {
+      let role = if address == "manager" { Role::FeeManager } else { Role::Pauser };
+      let msg = ExecuteMsg::GrantRole { address: address.to_string(), role };
+      execute(owndeps.as_mut(), mock_env(), mock_info("admin", &[]), msg).unwrap();
+    }
+
+    execute(owndeps.as_mut(), mock_env(), mock_info("manager", &[]), update_fees()).unwrap();
+    let msg = ExecuteMsg::SetFeeTier { address: "bot".to_string(), tier: Some(FeeTier::Exempt {}) };
+    execute(owndeps.as_mut(), mock_env(), mock_info("manager", &[]), msg).unwrap();
+    let err = execute(owndeps.as_mut(), mock_env(), mock_info("pauser1", &[]), update_fees()).unwrap_err();
+    assert!(matches!(err, ContractError::Unauthorized { role: Role::FeeManager }));
+
+    // fee managers cannot administer ownership
+    let msg = ExecuteMsg::ProposeAdmin { address: "manager".to_string(), expiry: None };
+    execute(owndeps.as_mut(), mock_env(), mock_info("manager", &[]), msg).expect_err("Unexpected success");
+
+    let msg = QueryMsg::Roles { address: "manager".to_string() };
+    let bin = query(owndeps.as_ref(), mock_env(), msg).unwrap();
+    let res = from_json::<RolesResponse>(&bin).unwrap();
+    assert_eq!(res.roles, vec![Role::FeeManager]);
+
+    let msg = QueryMsg::Roles { address: "admin".to_string() };
+    let bin = query(owndeps.as_ref(), mock_env(), msg).unwrap();
+    let res = from_json::<RolesResponse>(&bin).unwrap();
+    assert_eq!(res.roles, Role::ALL.to_vec());
+
+    let msg = QueryMsg::RoleMembers { role: Role::Pauser, start_after: None, limit: Some(2) };
+    let bin = query(owndeps.as_ref(), mock_env(), msg).unwrap();
+    let res = from_json::<RoleMembersResponse>(&bin).unwrap();
+    assert_eq!(res.members, vec!["pauser1", "pauser2"]);
+    assert_eq!(res.next_cursor, Some("pauser2".to_string()));
+
+    let msg = QueryMsg::RoleMembers { role: Role::Pauser, start_after: Some("pauser2".to_string()), limit: Some(2) };
+    let bin = query(owndeps.as_ref(), mock_env(), msg).unwrap();
+    let res = from_json::<RoleMembersResponse>(&bin).unwrap();
+    assert_eq!(res.members, vec!["pauser3"]);
+    assert_eq!(res.next_cursor, None);
+
+    let msg = ExecuteMsg::RevokeRole { address: "manager".to_string(), role: Role::FeeManager };
+    execute(owndeps.as_mut(), mock_env(), mock_info("admin", &[]), msg).unwrap();
+    execute(owndeps.as_mut(), mock_env(), mock_info("manager", &[]), update_fees()).expect_err("Unexpected success");
+  }
+
+  #[test]
+  fn pause() {
+    let mut owndeps = mock_dependencies();
+    instantiate_no_fees(owndeps.as_mut());
+
+    let store_note = || ExecuteMsg::StoreNote { recipient: "bob".to_string(), note: "barfoo".to_string() };
+    let update_key = || ExecuteMsg::UpdateKey { key: "foobar".to_string() };
+
+    // fails with unauthorized
+    let msg = ExecuteMsg::Pause { operations: None };
+    let err = execute(owndeps.as_mut(), mock_env(), mock_info("alice", &[]), msg).unwrap_err();
+    assert!(matches!(err, ContractError::Unauthorized { role: Role::Pauser }));
+
+    let msg = ExecuteMsg::GrantRole { address: "pauser".to_string(), role: Role::Pauser };
+    execute(owndeps.as_mut(), mock_env(), mock_info("admin", &[]), msg).unwrap();
+
+    let msg = ExecuteMsg::Pause { operations: Some(vec![Operation::StoreNote]) };
+    execute(owndeps.as_mut(), mock_env(), mock_info("pauser", &[]), msg).unwrap();
+
+    let err = execute(owndeps.as_mut(), mock_env(), mock_info("alice", &[]), store_note()).unwrap_err();
+    assert!(matches!(err, ContractError::Paused { operation: Operation::StoreNote }));
+    execute(owndeps.as_mut(), mock_env(), mock_info("alice", &[]), update_key()).unwrap();
+
+    // queries keep working while paused
+    let bin = query(owndeps.as_ref(), mock_env(), QueryMsg::Paused {}).unwrap();
+    let res = from_json::<PausedResponse>(&bin).unwrap();
+    assert_eq!(res.operations, vec![Operation::StoreNote]);
+    query(owndeps.as_ref(), mock_env(), QueryMsg::EncryptionKey { address: "alice".to_string() }).unwrap();
+
+    let msg = ExecuteMsg::Pause { operations: None };
+    execute(owndeps.as_mut(), mock_env(), mock_info("pauser", &[]), msg).unwrap();
+    let err = execute(owndeps.as_mut(), mock_env(), mock_info("alice", &[]), update_key()).unwrap_err();
+    assert!(matches!(err, ContractError::Paused { operation: Operation::UpdateKey }));
+    let msg = ExecuteMsg::MarkRead { sender: "bob".to_string(), until: None };
+    let err = execute(owndeps.as_mut(), mock_env(), mock_info("alice", &[]), msg).unwrap_err();
+    assert!(matches!(err, ContractError::Paused { operation: Operation::MarkRead }));
+    let msg = ExecuteMsg::SetPostage { postage: None };
+    let err = execute(owndeps.as_mut(), mock_env(), mock_info("alice", &[]), msg).unwrap_err();
+    assert!(matches!(err, ContractError::Paused { operation: Operation::SetPostage }));
+
+    let msg = ExecuteMsg::Unpause { operations: None };
+    execute(owndeps.as_mut(), mock_env(), mock_info("pauser", &[]), msg).unwrap();
+    execute(owndeps.as_mut(), mock_env(), mock_info("alice", &[]), store_note()).unwrap();
+    execute(owndeps.as_mut(), mock_env(), mock_info("alice", &[]), update_key()).unwrap();
+  }
+
+  /// Lay out storage the way 0.1.0 did, with the given number of notes from alice to bob.
+  fn store_v0_1(store: &mut dyn Storage, notes: u64) {
+    use cw_storage_plus::{Item, Map};
+
+    #[derive(serde::Serialize, serde::Deserialize)]
+    struct LegacyNoteMeta {
+      count: u64,
+    }
+
+    #[derive(serde::Serialize, serde::Deserialize)]
+    struct LegacyNote {
+      sender: Addr,
+      note: Vec<u8>,
+      timestamp: Timestamp,
+    }
+
+    #[derive(serde::Serialize, serde::Deserialize)]
+    struct LegacyFees {
+      admin: Option<Addr>,
+      store_keys: Uint128,
+      store_notes: Uint128,
+      denom: String,
+      burn_fees: bool,
+    }
+
+    set_contract_version(store, CONTRACT_NAME, "0.1.0").unwrap();
+    Item::<LegacyFees>::new("fees").save(store, &LegacyFees {
+      admin: Some(Addr::unchecked("admin")),
+      store_keys: Uint128::new(1000000),
+      store_notes: Uint128::new(500000),
+      denom: "luna".to_string(),
+      burn_fees: false,
+    }).unwrap();
+    Map::<Addr, Vec<u8>>::new("state").save(store, Addr::unchecked("alice"), &b"foobar".to_vec()).unwrap();
+    Map::<(Addr, Addr), LegacyNoteMeta>::new("note_counts")
+      .save(store, (Addr::unchecked("bob"), Addr::unchecked("alice")), &LegacyNoteMeta { count: notes }).unwrap();
+    for idx in 0..notes {
+      Map::<(Addr, Addr, u64), LegacyNote>::new("notes").save(store, (Addr::unchecked("bob"), Addr::unchecked("alice"), idx), &LegacyNote {
+        sender: Addr::unchecked("alice"),
+        note: b"barfoo".to_vec(),
+        timestamp: mock_env().block.time,
+      }).unwrap();
+    }
+  }
+
+  #[test]
+  fn migrate_from_v0_1() {
+    let mut owndeps = mock_dependencies();
+    store_v0_1(owndeps.as_mut().storage, 1);
+
+    migrate(owndeps.as_mut(), mock_env(), MigrateMsg { limit: None }).unwrap();
+    assert_eq!(get_contract_version(owndeps.as_ref().storage).unwrap().version, CONTRACT_VERSION);
+
+    let bin = query(owndeps.as_ref(), mock_env(), QueryMsg::Ownership {}).unwrap();
+    let res = from_json::<Ownership>(&bin).unwrap();
+    assert_eq!(res.owner.unwrap(), "admin");
+
+    let bin = query(owndeps.as_ref(), mock_env(), QueryMsg::Fees {}).unwrap();
+    let res = from_json::<FeesResponse>(&bin).unwrap().fees;
+    assert_eq!(res.store_keys, Uint128::new(1000000));
+    assert_eq!(res.store_notes, Uint128::new(500000));
+    assert!(res.beneficiaries.is_empty());
+
+    let msg = QueryMsg::EncryptionKey { address: "alice".to_string() };
+    let bin = query(owndeps.as_ref(), mock_env(), msg).unwrap();
+    let res = from_json::<EncryptionKeyResponse>(&bin).unwrap();
+    assert_eq!(res.key.unwrap(), b"foobar".to_vec());
+
+    let msg = QueryMsg::Notes { recipient: "bob".to_string(), sender: "alice".to_string(), start_after: None, limit: None, range: None };
+    let bin = query(owndeps.as_ref(), mock_env(), msg).unwrap();
+    let res = from_json::<NotesResponse>(&bin).unwrap().notes;
+    assert_eq!(res.len(), 1);
+    assert_eq!(res[0].note, b"barfoo".to_vec());
+
+    let msg = QueryMsg::Conversations { recipient: "bob".to_string(), start_after: None, limit: None, order_by: None };
+    let bin = query(owndeps.as_ref(), mock_env(), msg).unwrap();
+    let res = from_json::<ConversationsResponse>(&bin).unwrap().conversations;
+    assert_eq!(res.len(), 1);
+    assert_eq!(res[0].last_timestamp, mock_env().block.time);
+    assert_eq!(res[0].unread, 1);
+
+    let bin = query(owndeps.as_ref(), mock_env(), QueryMsg::Stats {}).unwrap();
+    let res = from_json::<StatsResponse>(&bin).unwrap();
+    assert_eq!((res.notes, res.bytes, res.unique_senders, res.unique_recipients, res.keys), (1, 6, 1, 1, 1));
+
+    let range = NoteRange { since: Some(mock_env().block.time), ..Default::default() };
+    let msg = QueryMsg::Notes { recipient: "bob".to_string(), sender: "alice".to_string(), start_after: None, limit: None, range: Some(range) };
+    let bin = query(owndeps.as_ref(), mock_env(), msg).unwrap();
+    assert_eq!(from_json::<NotesResponse>(&bin).unwrap().notes.len(), 1);
+
+    // the migrated contract keeps working
+    let ctx = ExecuteContext {
+      deps: owndeps.as_mut(),
+      env: mock_env(),
+      info: mock_info("alice", &coins(500000, "luna")),
+    };
+    let res = exec_store_note(ctx, "bob".to_string(), "foobar".to_string()).unwrap();
+    assert!(res.messages.iter().any(|submsg| is_fee_msg(submsg, "admin", "luna", 500000)));
+
+    // migrating to the same version is a no-op
+    migrate(owndeps.as_mut(), mock_env(), MigrateMsg { limit: None }).unwrap();
+  }
+
+  #[test]
+  fn migrate_in_batches() {
+    let mut owndeps = mock_dependencies();
+    store_v0_1(owndeps.as_mut().storage, 5);
+
+    // 1 ownership split, 1 conversation, 5 notes indexed, 5 notes counted, 1 key
+    let msg = MigrateMsg { limit: Some(4) };
+    for _ in 0..3 {
+      let res = migrate(owndeps.as_mut(), mock_env(), msg.clone()).unwrap();
+      assert!(res.attributes.iter().any(|attr| attr.key == "complete" && attr.value == "false"));
+      assert_eq!(get_contract_version(owndeps.as_ref().storage).unwrap().version, "0.1.0");
+
+      // writes are rejected until the migration completes
+      let msg = ExecuteMsg::UpdateKey { key: "foobar".to_string() };
+      let err = execute(owndeps.as_mut(), mock_env(), mock_info("alice", &coins(1000000, "luna")), msg).unwrap_err();
+      assert!(matches!(err, ContractError::MigrationPending {}));
+    }
+    let res = migrate(owndeps.as_mut(), mock_env(), msg).unwrap();
+    assert!(res.attributes.iter().any(|attr| attr.key == "complete" && attr.value == "true"));
+    assert_eq!(get_contract_version(owndeps.as_ref().storage).unwrap().version, CONTRACT_VERSION);
+
+    let bin = query(owndeps.as_ref(), mock_env(), QueryMsg::Stats {}).unwrap();
+    let res = from_json::<StatsResponse>(&bin).unwrap();
+    assert_eq!((res.notes, res.bytes, res.unique_senders, res.unique_recipients, res.keys), (5, 30, 1, 1, 1));
+
+    let range = NoteRange { since_height: Some(0), until_height: Some(1), ..Default::default() };
+    let msg = QueryMsg::Notes { recipient: "bob".to_string(), sender: "alice".to_string(), start_after: None, limit: None, range: Some(range) };
+    let bin = query(owndeps.as_ref(), mock_env(), msg).unwrap();
+    assert_eq!(from_json::<NotesResponse>(&bin).unwrap().notes.len(), 5);
+
+    let msg = QueryMsg::Conversations { recipient: "bob".to_string(), start_after: None, limit: None, order_by: None };
+    let bin = query(owndeps.as_ref(), mock_env(), msg).unwrap();
+    assert_eq!(from_json::<ConversationsResponse>(&bin).unwrap().conversations[0].unread, 5);
+
+    let msg = ExecuteMsg::UpdateKey { key: "foobar".to_string() };
+    execute(owndeps.as_mut(), mock_env(), mock_info("alice", &coins(1000000, "luna")), msg).unwrap();
+  }
+
+  #[test]
+  fn migrate_refuses_downgrade() {
+    let mut owndeps = mock_dependencies();
+    instantiate_default(owndeps.as_mut());
+
+    set_contract_version(owndeps.as_mut().storage, CONTRACT_NAME, "99.0.0").unwrap();
+    let err = migrate(owndeps.as_mut(), mock_env(), MigrateMsg { limit: None }).unwrap_err();
+    assert!(matches!(err, ContractError::Downgrade { .. }));
+
+    set_contract_version(owndeps.as_mut().storage, "crates.io:other", "0.1.0").unwrap();
+    let err = migrate(owndeps.as_mut(), mock_env(), MigrateMsg { limit: None }).unwrap_err();
+    assert!(matches!(err, ContractError::InvalidMigration { .. }));
+  }
+
+  #[test]
+  fn fee_split() {
+    let mut owndeps = mock_dependencies();
+    instantiate_default(owndeps.as_mut());
+
+    let beneficiaries = |shares: &[(Option<&str>, u16)]| shares.iter()
+      .map(|(address, bps)| BeneficiaryMsg { address: address.map(|a| a.to_string()), bps: *bps })
+      .collect::<Vec<_>>();
+
+    // fails when shares don't sum to 10000 bps
+    let msg = ExecuteMsg::UpdateFees {
+      store_keys: Uint128::new(1000000),
+      store_notes: Uint128::new(500000),
+      denom: "luna".to_string(),
+      burn_fees: false,
+      beneficiaries: beneficiaries(&[(Some("treasury"), 5000), (Some("devs"), 3000)]),
+      accumulate: false,
+    };
+    execute(owndeps.as_mut(), mock_env(), mock_info("admin", &[]), msg).expect_err("Unexpected success");
+
+    let msg = ExecuteMsg::UpdateFees {
+      store_keys: Uint128::new(1000000),
+      store_notes: Uint128::new(500000),
+      denom: "luna".to_string(),
+      burn_fees: false,
+      beneficiaries: beneficiaries(&[(Some("treasury"), 5000), (Some("devs"), 3000), (None, 2000)]),
+      accumulate: false,
+    };
+    execute(owndeps.as_mut(), mock_env(), mock_info("admin", &[]), msg).unwrap();
+
+    let bin = query(owndeps.as_ref(), mock_env(), QueryMsg::Fees {}).unwrap();
+    let res = from_json::<FeesResponse>(&bin).unwrap().fees;
+    assert_eq!(res.beneficiaries.len(), 3);
+
+    // remainder of the split goes to the first beneficiary
+    let ctx = ExecuteContext {
+      deps: owndeps.as_mut(),
+      env: mock_env(),
+      info: mock_info("alice", &coins(500003, "luna")),
+    };
+    let res = exec_store_note(ctx, "bob".to_string(), "barfoo".to_string()).unwrap();
+    assert_eq!(res.messages.len(), 3);
+    assert!(res.messages.iter().any(|submsg| is_fee_msg(submsg, "treasury", "luna", 250003)));
+    assert!(res.messages.iter().any(|submsg| is_fee_msg(submsg, "devs", "luna", 150000)));
+    assert!(res.messages.iter().any(|submsg| submsg.msg == CosmosMsg::Bank(BankMsg::Burn { amount: coins(100000, "luna") })));
+
+    // direct sends are not counted as withdrawals
+    let bin = query(owndeps.as_ref(), mock_env(), QueryMsg::FeeStats {}).unwrap();
+    let res = from_json::<FeeStatsResponse>(&bin).unwrap();
+    assert_eq!(res.stats[0].distributed, Uint128::new(400003));
+    assert_eq!(res.stats[0].withdrawn, Uint128::zero());
+  }
+
+  #[test]
+  fn accumulate_fees() {
+    let mut owndeps = mock_dependencies();
+    instantiate_default(owndeps.as_mut());
+
+    let msg = ExecuteMsg::UpdateFees {
+      store_keys: Uint128::new(1000000),
+      store_notes: Uint128::new(500000),
+      denom: "luna".to_string(),
+      burn_fees: false,
+      beneficiaries: vec![
+        BeneficiaryMsg { address: Some("treasury".to_string()), bps: 8000 },
+        BeneficiaryMsg { address: None, bps: 2000 },
+      ],
+      accumulate: true,
+    };
+    execute(owndeps.as_mut(), mock_env(), mock_info("admin", &[]), msg).unwrap();
+
+    // only the burnt share leaves the contract
+    for _ in 0..2 {
+      let ctx = ExecuteContext {
+        deps: owndeps.as_mut(),
+        env: mock_env(),
+        info: mock_info("alice", &coins(500000, "luna")),
+      };
+      let res = exec_store_note(ctx, "bob".to_string(), "barfoo".to_string()).unwrap();
+      assert_eq!(res.messages.len(), 1);
+      assert_eq!(res.messages[0].msg, CosmosMsg::Bank(BankMsg::Burn { amount: coins(100000, "luna") }));
+    }
+
+    let msg = QueryMsg::FeeBalance { address: "treasury".to_string() };
+    let bin = query(owndeps.as_ref(), mock_env(), msg).unwrap();
+    let res = from_json::<FeeBalanceResponse>(&bin).unwrap();
+    assert_eq!(res.balance, coins(800000, "luna"));
+
+    // fails when withdrawing more than accumulated
+    let msg = ExecuteMsg::WithdrawFees { amount: Coin::new(800001, "luna"), to: None };
+    execute(owndeps.as_mut(), mock_env(), mock_info("treasury", &[]), msg).expect_err("Unexpected success");
+
+    // fails when withdrawing someone else's balance
+    let msg = ExecuteMsg::WithdrawFees { amount: Coin::new(1, "luna"), to: Some("alice".to_string()) };
+    execute(owndeps.as_mut(), mock_env(), mock_info("alice", &[]), msg).expect_err("Unexpected success");
+
+    // fails when withdrawing nothing, which the bank module would reject
+    let msg = ExecuteMsg::WithdrawFees { amount: Coin::new(0, "luna"), to: None };
+    let err = execute(owndeps.as_mut(), mock_env(), mock_info("treasury", &[]), msg).unwrap_err();
+    assert!(matches!(err, ContractError::NoFunds {}));
+
+    let msg = ExecuteMsg::WithdrawFees { amount: Coin::new(300000, "luna"), to: Some("vault".to_string()) };
+    let res = execute(owndeps.as_mut(), mock_env(), mock_info("treasury", &[]), msg).unwrap();
+    assert!(res.messages.iter().any(|submsg| is_fee_msg(submsg, "vault", "luna", 300000)));
+
+    let msg = QueryMsg::FeeBalance { address: "treasury".to_string() };
+    let bin = query(owndeps.as_ref(), mock_env(), msg).unwrap();
+    let res = from_json::<FeeBalanceResponse>(&bin).unwrap();
+    assert_eq!(res.balance, coins(500000, "luna"));
+
+    let bin = query(owndeps.as_ref(), mock_env(), QueryMsg::FeeStats {}).unwrap();
+    let res = from_json::<FeeStatsResponse>(&bin).unwrap();
+    assert_eq!(res.stats, vec![DenomFeeStats {
+      denom: "luna".to_string(),
+      collected: Uint128::new(1000000),
+      burned: Uint128::new(200000),
+      distributed: Uint128::zero(),
+      withdrawn: Uint128::new(300000),
+    }]);
+  }
+
+  #[test]
+  fn prepaid_credits() {
+    let mut owndeps = mock_dependencies();
+    instantiate_default(owndeps.as_mut());
+
+    execute(owndeps.as_mut(), mock_env(), mock_info("alice", &[]), ExecuteMsg::Deposit {}).expect_err("Unexpected success");
+    execute(owndeps.as_mut(), mock_env(), mock_info("alice", &coins(2000000, "luna")), ExecuteMsg::Deposit {}).unwrap();
+
+    // fees are drawn from credits when no funds are attached
+    let ctx = ExecuteContext {
+      deps: owndeps.as_mut(),
+      env: mock_env(),
+      info: mock_info("alice", &[]),
+    };
+    let res = exec_update_key(ctx, "foobar".to_string()).unwrap();
+    assert!(res.messages.iter().any(|submsg| is_fee_msg(submsg, "admin", "luna", 1000000)));
+
+    let ctx = ExecuteContext {
+      deps: owndeps.as_mut(),
+      env: mock_env(),
+      info: mock_info("alice", &[]),
+    };
+    let res = exec_store_note(ctx, "bob".to_string(), "barfoo".to_string()).unwrap();
+    assert!(res.messages.iter().any(|submsg| is_fee_msg(submsg, "admin", "luna", 500000)));
+
+    let msg = QueryMsg::Credits { address: "alice".to_string() };
+    let bin = query(owndeps.as_ref(), mock_env(), msg).unwrap();
+    let res = from_json::<CreditsResponse>(&bin).unwrap();
+    assert_eq!(res.credits, coins(500000, "luna"));
+
+    // fails once credits run out
+    let ctx = ExecuteContext {
+      deps: owndeps.as_mut(),
+      env: mock_env(),
+      info: mock_info("alice", &[]),
+    };
+    exec_update_key(ctx, "foobar".to_string()).expect_err("Unexpected success");
+
+    let msg = ExecuteMsg::Withdraw { amount: Coin::new(500001, "luna") };
+    execute(owndeps.as_mut(), mock_env(), mock_info("alice", &[]), msg).expect_err("Unexpected success");
 
-    instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+    let msg = ExecuteMsg::Withdraw { amount: Coin::new(0, "luna") };
+    let err = execute(owndeps.as_mut(), mock_env(), mock_info("alice", &[]), msg).unwrap_err();
+    assert!(matches!(err, ContractError::NoFunds {}));
+
+    let msg = ExecuteMsg::Withdraw { amount: Coin::new(500000, "luna") };
+    let res = execute(owndeps.as_mut(), mock_env(), mock_info("alice", &[]), msg).unwrap();
+    assert!(res.messages.iter().any(|submsg| is_fee_msg(submsg, "alice", "luna", 500000)));
+
+    let msg = QueryMsg::Credits { address: "alice".to_string() };
+    let bin = query(owndeps.as_ref(), mock_env(), msg).unwrap();
+    let res = from_json::<CreditsResponse>(&bin).unwrap();
+    assert!(res.credits.is_empty());
   }
 
   #[test]
-  fn store_stuff() {
+  fn sponsored_fees() {
     let mut owndeps = mock_dependencies();
-    instantiate_no_fees(owndeps.as_mut());
+    instantiate_default(owndeps.as_mut());
 
+    execute(owndeps.as_mut(), mock_env(), mock_info("sponsor", &coins(1200000, "luna")), ExecuteMsg::Deposit {}).unwrap();
+    let msg = ExecuteMsg::Sponsor {
+      senders: vec!["alice".to_string()],
+      per_sender_cap: Some(Coin::new(1000000, "luna")),
+      expires: Some(mock_env().block.time.plus_seconds(60)),
+    };
+    execute(owndeps.as_mut(), mock_env(), mock_info("sponsor", &[]), msg).unwrap();
+
+    // offers only cover senders once accepted
     let ctx = ExecuteContext {
       deps: owndeps.as_mut(),
       env: mock_env(),
-      info: mock_info("alice", &vec![]),
+      info: mock_info("alice", &[]),
     };
-    exec_update_key(ctx, "foobar".to_string()).unwrap();
+    exec_store_note(ctx, "bob".to_string(), "barfoo".to_string()).expect_err("Unexpected success");
+
+    let msg = ExecuteMsg::AcceptSponsorship { sponsor: "mallory".to_string() };
+    let err = execute(owndeps.as_mut(), mock_env(), mock_info("alice", &[]), msg).unwrap_err();
+    assert!(matches!(err, ContractError::NoSponsorshipOffer { .. }));
+    let msg = ExecuteMsg::AcceptSponsorship { sponsor: "sponsor".to_string() };
+    execute(owndeps.as_mut(), mock_env(), mock_info("alice", &[]), msg).unwrap();
 
+    // uncovered senders still need to pay
     let ctx = ExecuteContext {
       deps: owndeps.as_mut(),
       env: mock_env(),
-      info: mock_info("alice", &vec![]),
+      info: mock_info("charlie", &[]),
     };
-    exec_store_note(ctx, "bob".to_string(), "barfoo".to_string()).unwrap();
-
-    let msg = QueryMsg::EncryptionKey { address: "alice".to_string() };
-    let bin = query(owndeps.as_ref(), mock_env(), msg).unwrap();
-    let res = from_json::<EncryptionKeyResponse>(&bin).unwrap();
-    assert_eq!(res.key.unwrap(), "foobar".as_bytes().to_owned());
+    exec_store_note(ctx, "bob".to_string(), "barfoo".to_string()).expect_err("Unexpected success");
 
-    let msg = QueryMsg::NoteCount { recipient: "bob".to_string(), sender: "alice".to_string() };
-    let bin = query(owndeps.as_ref(), mock_env(), msg).unwrap();
-    let res = from_json::<u64>(&bin).unwrap();
-    assert_eq!(res, 1);
+    for _ in 0..2 {
+      let ctx = ExecuteContext {
+        deps: owndeps.as_mut(),
+        env: mock_env(),
+        info: mock_info("alice", &[]),
+      };
+      let res = exec_store_note(ctx, "bob".to_string(), "barfoo".to_string()).unwrap();
+      assert!(res.attributes.iter().any(|attr| attr.key == "sponsor" && attr.value == "sponsor"));
+      assert!(res.messages.iter().any(|submsg| is_fee_msg(submsg, "admin", "luna", 500000)));
+    }
 
-    let msg = QueryMsg::Notes { recipient: "bob".to_string(), sender: "alice".to_string(), start_after: None, limit: None };
+    let msg = QueryMsg::Sponsorship { sponsor: "sponsor".to_string(), sender: "alice".to_string() };
     let bin = query(owndeps.as_ref(), mock_env(), msg).unwrap();
-    let res = from_json::<Vec<Note>>(&bin).unwrap();
-    assert_eq!(res.len(), 1);
-    assert_eq!(res[0].note, "barfoo".as_bytes().to_owned());
-  }
+    let res = from_json::<SponsorshipResponse>(&bin).unwrap();
+    assert!(res.sponsored);
+    assert!(!res.offered);
+    assert_eq!(res.spent, Uint128::new(1000000));
 
-  #[test]
-  fn fees_update_key() {
-    let mut owndeps = mock_dependencies();
-    instantiate_default(owndeps.as_mut());
+    let bin = query(owndeps.as_ref(), mock_env(), QueryMsg::Sponsor { address: "sponsor".to_string() }).unwrap();
+    let res = from_json::<SponsorResponse>(&bin).unwrap();
+    assert_eq!(res.budget, coins(200000, "luna"));
 
-    // fails with insufficient fees
+    // fails once the per-sender cap is reached
     let ctx = ExecuteContext {
       deps: owndeps.as_mut(),
       env: mock_env(),
-      info: mock_info("alice", &coins(1, "luna")),
+      info: mock_info("alice", &[]),
     };
-    exec_update_key(ctx, "foobar".to_string()).expect_err("Unexpected success");
+    exec_store_note(ctx, "bob".to_string(), "barfoo".to_string()).expect_err("Unexpected success");
 
-    // fails with no funds
+    // fails once the sponsorship expired
+    let msg = ExecuteMsg::Sponsor {
+      senders: vec!["dave".to_string()],
+      per_sender_cap: None,
+      expires: Some(mock_env().block.time.plus_seconds(60)),
+    };
+    execute(owndeps.as_mut(), mock_env(), mock_info("sponsor", &[]), msg).unwrap();
+    let msg = ExecuteMsg::AcceptSponsorship { sponsor: "sponsor".to_string() };
+    execute(owndeps.as_mut(), mock_env(), mock_info("dave", &[]), msg).unwrap();
+
+    let mut env = mock_env();
+    env.block.time = env.block.time.plus_seconds(60);
     let ctx = ExecuteContext {
       deps: owndeps.as_mut(),
-      env: mock_env(),
-      info: mock_info("alice", &vec![]),
+      env,
+      info: mock_info("dave", &[]),
     };
-    exec_update_key(ctx, "foobar".to_string()).expect_err("Unexpected success");
+    exec_store_note(ctx, "bob".to_string(), "barfoo".to_string()).expect_err("Unexpected success");
 
-    // success with exact fees
+    // attached funds take precedence over sponsorship
     let ctx = ExecuteContext {
       deps: owndeps.as_mut(),
       env: mock_env(),
-      info: mock_info("alice", &coins(1000000, "luna")),
+      info: mock_info("dave", &coins(500000, "luna")),
     };
-    let res = exec_update_key(ctx, "foobar".to_string()).unwrap();
-    assert!(res.messages.iter().any(|submsg| is_fee_msg(submsg, "admin", "luna", 1000000)));
+    let res = exec_store_note(ctx, "bob".to_string(), "barfoo".to_string()).unwrap();
+    assert!(!res.attributes.iter().any(|attr| attr.key == "sponsor"));
 
-    // success with excess fees
+    // caps only cover fees in their own denom
+    execute(owndeps.as_mut(), mock_env(), mock_info("admin", &[]), ExecuteMsg::UpdateFees {
+      store_keys: Uint128::new(1000000),
+      store_notes: Uint128::new(100000),
+      denom: "uusd".to_string(),
+      burn_fees: false,
+      beneficiaries: vec![],
+      accumulate: false,
+    }).unwrap();
+    execute(owndeps.as_mut(), mock_env(), mock_info("sponsor", &coins(1000000, "uusd")), ExecuteMsg::Deposit {}).unwrap();
+    let msg = ExecuteMsg::Sponsor {
+      senders: vec!["erin".to_string()],
+      per_sender_cap: Some(Coin::new(1000000, "luna")),
+      expires: None,
+    };
+    execute(owndeps.as_mut(), mock_env(), mock_info("sponsor", &[]), msg).unwrap();
+    let msg = ExecuteMsg::AcceptSponsorship { sponsor: "sponsor".to_string() };
+    execute(owndeps.as_mut(), mock_env(), mock_info("erin", &[]), msg).unwrap();
     let ctx = ExecuteContext {
       deps: owndeps.as_mut(),
       env: mock_env(),
-      info: mock_info("alice", &coins(1500000, "luna")),
+      info: mock_info("erin", &[]),
     };
-    let res = exec_update_key(ctx, "foobar".to_string()).unwrap();
-    assert!(res.messages.iter().any(|submsg| is_fee_msg(submsg, "admin", "luna", 1500000)));
+    exec_store_note(ctx, "bob".to_string(), "barfoo".to_string()).expect_err("Unexpected success");
+
+    // senders accept a bounded number of sponsors
+    for idx in 0..MAX_SPONSORS_PER_SENDER + 1 {
+      let sponsor = format!("sponsor{idx}");
+      let msg = ExecuteMsg::Sponsor { senders: vec!["frank".to_string()], per_sender_cap: None, expires: None };
+      execute(owndeps.as_mut(), mock_env(), mock_info(&sponsor, &[]), msg).unwrap();
+      let msg = ExecuteMsg::AcceptSponsorship { sponsor };
+      let res = execute(owndeps.as_mut(), mock_env(), mock_info("frank", &[]), msg);
+      if idx < MAX_SPONSORS_PER_SENDER {
+        res.unwrap();
+      } else {
+        assert!(matches!(res.unwrap_err(), ContractError::TooManySponsors { .. }));
+      }
+    }
+    let msg = ExecuteMsg::RemoveSponsor { sponsor: "sponsor0".to_string() };
+    execute(owndeps.as_mut(), mock_env(), mock_info("frank", &[]), msg).unwrap();
+    let msg = ExecuteMsg::AcceptSponsorship { sponsor: format!("sponsor{MAX_SPONSORS_PER_SENDER}") };
+    execute(owndeps.as_mut(), mock_env(), mock_info("frank", &[]), msg).unwrap();
   }
 
   #[test]
-  fn fees_store_note() {
+  fn fee_tiers() {
     let mut owndeps = mock_dependencies();
     instantiate_default(owndeps.as_mut());
 
-    // fails with insufficient fees
+    // fails with unauthorized
+    let msg = ExecuteMsg::SetFeeTier { address: "bot".to_string(), tier: Some(FeeTier::Exempt {}) };
+    execute(owndeps.as_mut(), mock_env(), mock_info("alice", &[]), msg).expect_err("Unexpected success");
+
+    // fails with invalid discount
+    let msg = ExecuteMsg::SetFeeTier { address: "alice".to_string(), tier: Some(FeeTier::Discount { bps: 10001 }) };
+    execute(owndeps.as_mut(), mock_env(), mock_info("admin", &[]), msg).expect_err("Unexpected success");
+
+    let msg = ExecuteMsg::SetFeeTier { address: "bot".to_string(), tier: Some(FeeTier::Exempt {}) };
+    execute(owndeps.as_mut(), mock_env(), mock_info("admin", &[]), msg).unwrap();
+    let msg = ExecuteMsg::SetFeeTier { address: "alice".to_string(), tier: Some(FeeTier::Discount { bps: 2500 }) };
+    execute(owndeps.as_mut(), mock_env(), mock_info("admin", &[]), msg).unwrap();
+
+    // exempt addresses pay nothing
     let ctx = ExecuteContext {
       deps: owndeps.as_mut(),
       env: mock_env(),
-      info: mock_info("alice", &coins(1, "luna")),
+      info: mock_info("bot", &[]),
     };
-    exec_store_note(ctx, "bob".to_string(), "barfoo".to_string()).expect_err("Unexpected success");
+    let res = exec_update_key(ctx, "foobar".to_string()).unwrap();
+    assert!(res.messages.is_empty());
 
-    // fails with no funds
     let ctx = ExecuteContext {
       deps: owndeps.as_mut(),
       env: mock_env(),
-      info: mock_info("alice", &vec![]),
+      info: mock_info("bot", &[]),
     };
-    exec_store_note(ctx, "bob".to_string(), "barfoo".to_string()).expect_err("Unexpected success");
+    let res = exec_store_note(ctx, "bob".to_string(), "barfoo".to_string()).unwrap();
+    assert!(res.messages.is_empty());
+
+    // discounted addresses pay the discounted fee
+    let msg = QueryMsg::EffectiveFees { address: "alice".to_string() };
+    let bin = query(owndeps.as_ref(), mock_env(), msg).unwrap();
+    let res = from_json::<EffectiveFeesResponse>(&bin).unwrap();
+    assert_eq!(res.store_keys, Uint128::new(750000));
+    assert_eq!(res.store_notes, Uint128::new(375000));
+    assert_eq!(res.tier, Some(FeeTier::Discount { bps: 2500 }));
 
-    // success with exact fees
     let ctx = ExecuteContext {
       deps: owndeps.as_mut(),
       env: mock_env(),
-      info: mock_info("alice", &coins(500000, "luna")),
+      info: mock_info("alice", &coins(374999, "luna")),
     };
-    let res = exec_store_note(ctx, "bob".to_string(), "barfoo".to_string()).unwrap();
-    assert!(res.messages.iter().any(|submsg| is_fee_msg(submsg, "admin", "luna", 500000)));
+    exec_store_note(ctx, "bob".to_string(), "barfoo".to_string()).expect_err("Unexpected success");
 
-    // success with excess fees
     let ctx = ExecuteContext {
       deps: owndeps.as_mut(),
       env: mock_env(),
-      info: mock_info("alice", &coins(750000, "luna")),
+      info: mock_info("alice", &coins(375000, "luna")),
     };
     let res = exec_store_note(ctx, "bob".to_string(), "barfoo".to_string()).unwrap();
-    assert!(res.messages.iter().any(|submsg| is_fee_msg(submsg, "admin", "luna", 750000)));
+    assert!(res.messages.iter().any(|submsg| is_fee_msg(submsg, "admin", "luna", 375000)));
+
+    // resetting the tier restores regular fees
+    let msg = ExecuteMsg::SetFeeTier { address: "alice".to_string(), tier: None };
+    execute(owndeps.as_mut(), mock_env(), mock_info("admin", &[]), msg).unwrap();
+
+    let msg = QueryMsg::EffectiveFees { address: "alice".to_string() };
+    let bin = query(owndeps.as_ref(), mock_env(), msg).unwrap();
+    let res = from_json::<EffectiveFeesResponse>(&bin).unwrap();
+    assert_eq!(res.store_notes, Uint128::new(500000));
+    assert_eq!(res.tier, None);
   }
 
   #[test]
-  fn update_fees() {
+  #[allow(clippy::useless_vec)]
+  fn query_senders() {
     let mut owndeps = mock_dependencies();
-    instantiate_default(owndeps.as_mut());
+    instantiate_no_fees(owndeps.as_mut());
 
-    // fails with unauthorized
-    let msg = ExecuteMsg::UpdateFees {
-      admin: None,
-      store_keys: Uint128::new(1000000),
-      store_notes: Uint128::new(500000),
-      denom: "luna".to_string(),
-      burn_fees: false,
+    let ctx = ExecuteContext {
+      deps: owndeps.as_mut(),
+      env: mock_env(),
+      info: mock_info("alice", &vec![]),
     };
-    execute(owndeps.as_mut(), mock_env(), mock_info("alice", &[]), msg).expect_err("Unexpected success");
+    exec_store_note(ctx, "bob".to_string(), "barfoo".to_string()).unwrap();
 
-    // success with authorized
-    let msg = ExecuteMsg::UpdateFees {
-      admin: Some("alice".to_string()),
-      store_keys: Uint128::new(2000000),
-      store_notes: Uint128::new(1000000),
-      denom: "luna".to_string(),
-      burn_fees: false,
+    let ctx = ExecuteContext {
+      deps: owndeps.as_mut(),
+      env: mock_env(),
+      info: mock_info("alice", &vec![]),
     };
-    execute(owndeps.as_mut(), mock_env(), mock_info("admin", &[]), msg).unwrap();
+    exec_store_note(ctx, "bob".to_string(), "barfoo".to_string()).unwrap();
 
-    // query updated fees
-    let msg = QueryMsg::Fees {};
+    let ctx = ExecuteContext {
+      deps: owndeps.as_mut(),
+      env: mock_env(),
+      info: mock_info("charlie", &vec![]),
+    };
+    exec_store_note(ctx, "bob".to_string(), "barfoo".to_string()).unwrap();
+
+    let msg = QueryMsg::Senders { recipient: "bob".to_string(), start_after: None, limit: None };
     let bin = query(owndeps.as_ref(), mock_env(), msg).unwrap();
-    let res = from_json::<Fees>(&bin).unwrap();
-    assert_eq!(res.admin.unwrap(), "alice");
-    assert_eq!(res.store_keys, Uint128::new(2000000));
-    assert_eq!(res.store_notes, Uint128::new(1000000));
+    let res = from_json::<SendersResponse>(&bin).unwrap();
+    let senders: Vec<_> = res.senders.iter().map(|s| (s.sender.as_str(), s.count)).collect();
+    assert_eq!(senders, vec![("alice", 2), ("charlie", 1)]);
+    assert_eq!(res.senders[0].last_timestamp, mock_env().block.time);
 
-    // success with new admin
-    let msg = ExecuteMsg::UpdateFees {
-      admin: Some("bob".to_string()),
-      store_keys: Uint128::new(3000000),
-      store_notes: Uint128::new(1500000),
-      denom: "luna".to_string(),
-      burn_fees: false,
-    };
-    execute(owndeps.as_mut(), mock_env(), mock_info("alice", &[]), msg).unwrap();
+    let msg = QueryMsg::Senders { recipient: "bob".to_string(), start_after: None, limit: Some(1) };
+    let bin = query(owndeps.as_ref(), mock_env(), msg).unwrap();
+    let res = from_json::<SendersResponse>(&bin).unwrap();
+    assert_eq!(res.senders.len(), 1);
+    assert_eq!(res.senders[0].sender, "alice");
+    assert_eq!(res.next_cursor, Some("alice".to_string()));
 
-    // failure with old admin
-    let msg = ExecuteMsg::UpdateFees {
-      admin: Some("alice".to_string()),
-      store_keys: Uint128::new(4000000),
-      store_notes: Uint128::new(2000000),
-      denom: "luna".to_string(),
-      burn_fees: false,
+    let msg = QueryMsg::Senders { recipient: "bob".to_string(), start_after: Some("alice".to_string()), limit: Some(1) };
+    let bin = query(owndeps.as_ref(), mock_env(), msg).unwrap();
+    let res = from_json::<SendersResponse>(&bin).unwrap();
+    assert_eq!(res.senders.len(), 1);
+    assert_eq!(res.senders[0].sender, "charlie");
+    assert_eq!(res.next_cursor, None);
+
+    let msg = QueryMsg::Senders { recipient: "bob".to_string(), start_after: Some("charlie".to_string()), limit: None };
+    let bin = query(owndeps.as_ref(), mock_env(), msg).unwrap();
+    assert!(from_json::<SendersResponse>(&bin).unwrap().senders.is_empty());
+  }
+
+  #[test]
+  fn paginate_notes() {
+    let mut owndeps = mock_dependencies();
+    instantiate_no_fees(owndeps.as_mut());
+
+    for note in ["foo", "bar", "baz"] {
+      let ctx = ExecuteContext {
+        deps: owndeps.as_mut(),
+        env: mock_env(),
+        info: mock_info("alice", &[]),
+      };
+      exec_store_note(ctx, "bob".to_string(), note.to_string()).unwrap();
+    }
+
+    let msg = QueryMsg::Notes { recipient: "bob".to_string(), sender: "alice".to_string(), start_after: None, limit: Some(2), range: None };
+    let bin = query(owndeps.as_ref(), mock_env(), msg).unwrap();
+    let res = from_json::<NotesResponse>(&bin).unwrap();
+    assert_eq!(res.notes.iter().map(|n| n.note.clone()).collect::<Vec<_>>(), vec![b"foo".to_vec(), b"bar".to_vec()]);
+    assert_eq!(res.next_cursor, Some(1));
+
+    // cursors are exclusive
+    let msg = QueryMsg::Notes { recipient: "bob".to_string(), sender: "alice".to_string(), start_after: Some(1), limit: Some(2), range: None };
+    let bin = query(owndeps.as_ref(), mock_env(), msg).unwrap();
+    let res = from_json::<NotesResponse>(&bin).unwrap();
+    assert_eq!(res.notes.iter().map(|n| n.note.clone()).collect::<Vec<_>>(), vec![b"baz".to_vec()]);
+    assert_eq!(res.next_cursor, None);
+
+    // limits are capped
+    let msg = QueryMsg::Notes { recipient: "bob".to_string(), sender: "alice".to_string(), start_after: None, limit: Some(u32::MAX), range: None };
+    let bin = query(owndeps.as_ref(), mock_env(), msg).unwrap();
+    assert_eq!(from_json::<NotesResponse>(&bin).unwrap().notes.len(), 3);
+
+    // no notes is not an error
+    let msg = QueryMsg::Notes { recipient: "bob".to_string(), sender: "charlie".to_string(), start_after: None, limit: None, range: None };
+    let bin = query(owndeps.as_ref(), mock_env(), msg).unwrap();
+    let res = from_json::<NotesResponse>(&bin).unwrap();
+    assert!(res.notes.is_empty());
+    assert_eq!(res.next_cursor, None);
+  }
+
+  #[test]
+  fn note_ranges() {
+    let mut owndeps = mock_dependencies();
+    instantiate_no_fees(owndeps.as_mut());
+
+    // notes at (time, height) (+0, +0), (+0, +0), (+60, +10), (+120, +20)
+    let mut env = mock_env();
+    for (i, note) in ["foo", "bar", "baz", "qux"].iter().enumerate() {
+      if i > 1 {
+        env.block.time = env.block.time.plus_seconds(60);
+        env.block.height += 10;
+      }
+      let ctx = ExecuteContext {
+        deps: owndeps.as_mut(),
+        env: env.clone(),
+        info: mock_info("alice", &[]),
+      };
+      exec_store_note(ctx, "bob".to_string(), note.to_string()).unwrap();
+    }
+
+    let time = mock_env().block.time;
+    let height = mock_env().block.height;
+    let notes_in = |start_after: Option<u64>, limit: Option<u32>, range: NoteRange| {
+      let msg = QueryMsg::Notes { recipient: "bob".to_string(), sender: "alice".to_string(), start_after, limit, range: Some(range) };
+      let bin = query(owndeps.as_ref(), mock_env(), msg).unwrap();
+      let res = from_json::<NotesResponse>(&bin).unwrap();
+      let notes: Vec<_> = res.notes.into_iter().map(|n| String::from_utf8(n.note).unwrap()).collect();
+      (notes, res.next_cursor)
     };
-    execute(owndeps.as_mut(), mock_env(), mock_info("alice", &[]), msg).expect_err("Unexpected success");
+
+    let (notes, _) = notes_in(None, None, NoteRange { since: Some(time.plus_seconds(60)), ..Default::default() });
+    assert_eq!(notes, vec!["baz", "qux"]);
+    let (notes, _) = notes_in(None, None, NoteRange { since: Some(time.plus_seconds(1)), ..Default::default() });
+    assert_eq!(notes, vec!["baz", "qux"]);
+    let (notes, _) = notes_in(None, None, NoteRange { until: Some(time.plus_seconds(60)), ..Default::default() });
+    assert_eq!(notes, vec!["foo", "bar"]);
+    let (notes, _) = notes_in(None, None, NoteRange { since: Some(time.plus_seconds(121)), ..Default::default() });
+    assert!(notes.is_empty());
+
+    let (notes, _) = notes_in(None, None, NoteRange { since_height: Some(height + 10), until_height: Some(height + 20), ..Default::default() });
+    assert_eq!(notes, vec!["baz"]);
+
+    // combined with pagination
+    let (notes, next) = notes_in(None, Some(1), NoteRange { since: Some(time), until: Some(time.plus_seconds(120)), ..Default::default() });
+    assert_eq!(notes, vec!["foo"]);
+    assert_eq!(next, Some(0));
+    let (notes, next) = notes_in(next, Some(2), NoteRange { since: Some(time), until: Some(time.plus_seconds(120)), ..Default::default() });
+    assert_eq!(notes, vec!["bar", "baz"]);
+    assert_eq!(next, None);
+
+    // empty ranges
+    let (notes, _) = notes_in(None, None, NoteRange { since: Some(time.plus_seconds(60)), until: Some(time), ..Default::default() });
+    assert!(notes.is_empty());
+    let (notes, next) = notes_in(Some(u64::MAX), None, NoteRange::default());
+    assert!(notes.is_empty());
+    assert_eq!(next, None);
   }
 
   #[test]
-  fn query_senders() {
+  fn conversations() {
     let mut owndeps = mock_dependencies();
     instantiate_no_fees(owndeps.as_mut());
 
+    let mut env = mock_env();
+    for (sender, note) in [("alice", "foo"), ("charlie", "bar"), ("dave", "baz"), ("alice", "qux")] {
+      env.block.time = env.block.time.plus_seconds(60);
+      let ctx = ExecuteContext {
+        deps: owndeps.as_mut(),
+        env: env.clone(),
+        info: mock_info(sender, &[]),
+      };
+      exec_store_note(ctx, "bob".to_string(), note.to_string()).unwrap();
+    }
+
+    let msg = QueryMsg::Conversations { recipient: "bob".to_string(), start_after: None, limit: Some(2), order_by: None };
+    let bin = query(owndeps.as_ref(), mock_env(), msg).unwrap();
+    let res = from_json::<ConversationsResponse>(&bin).unwrap();
+    let senders: Vec<_> = res.conversations.iter().map(|c| c.sender.as_str()).collect();
+    assert_eq!(senders, vec!["alice", "dave"]);
+    assert_eq!(res.conversations[0].count, 2);
+    assert_eq!(res.conversations[0].unread, 2);
+    assert_eq!(res.conversations[0].last_timestamp, env.block.time);
+    assert_eq!(res.conversations[0].preview, b"qux".to_vec());
+    let dave_time = mock_env().block.time.plus_seconds(180);
+    assert_eq!(res.next_cursor, Some(format!("{}/dave", dave_time.nanos())));
+
+    // activity of the cursor's sender in between pages neither repeats nor skips conversations
+    env.block.time = env.block.time.plus_seconds(60);
     let ctx = ExecuteContext {
       deps: owndeps.as_mut(),
-      env: mock_env(),
-      info: mock_info("alice", &vec![]),
+      env: env.clone(),
+      info: mock_info("dave", &[]),
     };
-    exec_store_note(ctx, "bob".to_string(), "barfoo".to_string()).unwrap();
+    exec_store_note(ctx, "bob".to_string(), "quux".to_string()).unwrap();
+
+    let msg = QueryMsg::Conversations { recipient: "bob".to_string(), start_after: res.next_cursor, limit: Some(2), order_by: None };
+    let bin = query(owndeps.as_ref(), mock_env(), msg).unwrap();
+    let res = from_json::<ConversationsResponse>(&bin).unwrap();
+    let senders: Vec<_> = res.conversations.iter().map(|c| c.sender.as_str()).collect();
+    assert_eq!(senders, vec!["charlie"]);
+    assert_eq!(res.next_cursor, None);
+
+    // cursors need not refer to an existing conversation
+    let cursor = format!("{}/erin", dave_time.nanos());
+    let msg = QueryMsg::Conversations { recipient: "bob".to_string(), start_after: Some(cursor), limit: None, order_by: None };
+    let bin = query(owndeps.as_ref(), mock_env(), msg).unwrap();
+    let res = from_json::<ConversationsResponse>(&bin).unwrap();
+    let senders: Vec<_> = res.conversations.iter().map(|c| c.sender.as_str()).collect();
+    assert_eq!(senders, vec!["charlie"]);
+    let msg = QueryMsg::Conversations { recipient: "bob".to_string(), start_after: Some("dave".to_string()), limit: None, order_by: None };
+    query(owndeps.as_ref(), mock_env(), msg).expect_err("Unexpected success");
+
+    let msg = QueryMsg::Conversations {
+      recipient: "bob".to_string(),
+      start_after: Some("alice".to_string()),
+      limit: None,
+      order_by: Some(ConversationOrder::Sender),
+    };
+    let bin = query(owndeps.as_ref(), mock_env(), msg).unwrap();
+    let res = from_json::<ConversationsResponse>(&bin).unwrap();
+    let senders: Vec<_> = res.conversations.iter().map(|c| c.sender.as_str()).collect();
+    assert_eq!(senders, vec!["charlie", "dave"]);
+
+    // only the recipient can mark notes read
+    let msg = ExecuteMsg::MarkRead { sender: "alice".to_string(), until: Some(0) };
+    execute(owndeps.as_mut(), mock_env(), mock_info("charlie", &[]), msg.clone()).expect_err("Unexpected success");
+    execute(owndeps.as_mut(), mock_env(), mock_info("bob", &[]), msg).unwrap();
+
+    let msg = QueryMsg::Conversations {
+      recipient: "bob".to_string(),
+      start_after: None,
+      limit: Some(1),
+      order_by: Some(ConversationOrder::Sender),
+    };
+    let bin = query(owndeps.as_ref(), mock_env(), msg.clone()).unwrap();
+    assert_eq!(from_json::<ConversationsResponse>(&bin).unwrap().conversations[0].unread, 1);
+
+    let read_all = ExecuteMsg::MarkRead { sender: "alice".to_string(), until: None };
+    execute(owndeps.as_mut(), mock_env(), mock_info("bob", &[]), read_all).unwrap();
+    let bin = query(owndeps.as_ref(), mock_env(), msg).unwrap();
+    assert_eq!(from_json::<ConversationsResponse>(&bin).unwrap().conversations[0].unread, 0);
+
+    // indices past the last note mark all notes read
+    let msg = ExecuteMsg::MarkRead { sender: "charlie".to_string(), until: Some(u64::MAX) };
+    let res = execute(owndeps.as_mut(), mock_env(), mock_info("bob", &[]), msg).unwrap();
+    assert!(res.attributes.iter().any(|attr| attr.key == "read" && attr.value == "1"));
+  }
+
+  #[test]
+  fn batch_queries() {
+    let mut owndeps = mock_dependencies();
+    instantiate_no_fees(owndeps.as_mut());
 
     let ctx = ExecuteContext {
       deps: owndeps.as_mut(),
       env: mock_env(),
-      info: mock_info("alice", &vec![]),
+      info: mock_info("alice", &[]),
     };
-    exec_store_note(ctx, "bob".to_string(), "barfoo".to_string()).unwrap();
+    exec_update_key(ctx, "foobar".to_string()).unwrap();
 
     let ctx = ExecuteContext {
       deps: owndeps.as_mut(),
       env: mock_env(),
-      info: mock_info("charlie", &vec![]),
+      info: mock_info("alice", &[]),
     };
     exec_store_note(ctx, "bob".to_string(), "barfoo".to_string()).unwrap();
 
-    let msg = QueryMsg::Senders { recipient: "bob".to_string() };
-    let bin = query(owndeps.as_ref(), mock_env(), msg).unwrap();
-    let res = from_json::<Vec<String>>(&bin).unwrap();
-    assert_eq!(res, vec!["alice", "charlie"]);
+    let addresses = vec!["alice".to_string(), "bob".to_string(), "A".to_string()];
+    let bin = query(owndeps.as_ref(), mock_env(), QueryMsg::EncryptionKeys { addresses }).unwrap();
+    let res = from_json::<EncryptionKeysResponse>(&bin).unwrap().keys;
+    assert_eq!(res.len(), 3);
+    assert_eq!(res[0].key, Some(b"foobar".to_vec()));
+    assert_eq!(res[0].error, None);
+    assert_eq!(res[1].key, None);
+    assert_eq!(res[1].error, Some(ContractError::NoEncryptionKey {}.to_string()));
+    assert_eq!(res[2].address, "A");
+    assert!(res[2].key.is_none() && res[2].error.is_some());
+
+    let pair = |recipient: &str, sender: &str| NotePair { recipient: recipient.to_string(), sender: sender.to_string() };
+    let pairs = vec![pair("bob", "alice"), pair("alice", "bob"), pair("bob", "A")];
+    let bin = query(owndeps.as_ref(), mock_env(), QueryMsg::NoteCounts { pairs }).unwrap();
+    let res = from_json::<NoteCountsResponse>(&bin).unwrap().counts;
+    assert_eq!(res.iter().map(|c| c.count).collect::<Vec<_>>(), vec![Some(1), Some(0), None]);
+    assert!(res[2].error.is_some());
+
+    let addresses = vec!["alice".to_string(); MAX_BATCH_SIZE + 1];
+    query(owndeps.as_ref(), mock_env(), QueryMsg::EncryptionKeys { addresses }).unwrap_err();
+  }
+
+  #[test]
+  fn stats() {
+    let mut owndeps = mock_dependencies();
+    instantiate_default(owndeps.as_mut());
+
+    for (sender, key) in [("alice", "foo"), ("alice", "foobar"), ("bob", "bar")] {
+      let ctx = ExecuteContext {
+        deps: owndeps.as_mut(),
+        env: mock_env(),
+        info: mock_info(sender, &coins(1000000, "luna")),
+      };
+      exec_update_key(ctx, key.to_string()).unwrap();
+    }
+
+    for (sender, recipient, note) in [("alice", "bob", "foo"), ("alice", "bob", "foobar"), ("bob", "alice", "bar"), ("alice", "charlie", "baz")] {
+      let ctx = ExecuteContext {
+        deps: owndeps.as_mut(),
+        env: mock_env(),
+        info: mock_info(sender, &coins(500000, "luna")),
+      };
+      exec_store_note(ctx, recipient.to_string(), note.to_string()).unwrap();
+    }
+
+    let bin = query(owndeps.as_ref(), mock_env(), QueryMsg::Stats {}).unwrap();
+    let res = from_json::<StatsResponse>(&bin).unwrap();
+    assert_eq!(res.notes, 4);
+    assert_eq!(res.bytes, 15);
+    assert_eq!(res.unique_senders, 2);
+    assert_eq!(res.unique_recipients, 3);
+    assert_eq!(res.keys, 2);
+    assert_eq!(res.fees_collected, coins(5000000, "luna"));
+  }
+
+  #[test]
+  fn events() {
+    use crate::events::{self, attr};
+
+    let mut owndeps = mock_dependencies();
+    instantiate_default(owndeps.as_mut());
+
+    let find_event = |res: &Response, ty: &str| res.events.iter().find(|event| event.ty == ty).cloned().unwrap();
+    let find_attr = |event: &Event, key: &str| event.attributes.iter().find(|a| a.key == key).map(|a| a.value.clone());
+
+    let msg = ExecuteMsg::UpdateKey { key: "foobar".to_string() };
+    let res = execute(owndeps.as_mut(), mock_env(), mock_info("bob", &coins(1000000, "luna")), msg).unwrap();
+    let event = find_event(&res, events::KEY_UPDATED);
+    assert_eq!(find_attr(&event, attr::ADDRESS), Some("bob".to_string()));
+    assert_eq!(find_attr(&event, attr::KEY_HASH), Some(events::key_hash(b"foobar")));
+    assert_eq!(find_attr(&event, attr::FEE), Some("1000000luna".to_string()));
+
+    let msg = ExecuteMsg::StoreNote { recipient: "bob".to_string(), note: "barfoo".to_string() };
+    let res = execute(owndeps.as_mut(), mock_env(), mock_info("alice", &coins(500000, "luna")), msg.clone()).unwrap();
+    execute(owndeps.as_mut(), mock_env(), mock_info("alice", &coins(500000, "luna")), msg.clone()).unwrap();
+    let event = find_event(&res, events::NOTE_STORED);
+    assert_eq!(find_attr(&event, attr::SENDER), Some("alice".to_string()));
+    assert_eq!(find_attr(&event, attr::RECIPIENT), Some("bob".to_string()));
+    assert_eq!(find_attr(&event, attr::IDX), Some("0".to_string()));
+    assert_eq!(find_attr(&event, attr::SIZE), Some("6".to_string()));
+    assert_eq!(find_attr(&event, attr::KEY_ID), Some(events::key_hash(b"foobar")));
+    assert_eq!(find_attr(&event, attr::FEE), Some("500000luna".to_string()));
+    assert_eq!(find_attr(&event, attr::SPONSOR), None);
+
+    let res = execute(owndeps.as_mut(), mock_env(), mock_info("alice", &coins(500000, "luna")), msg).unwrap();
+    assert_eq!(find_attr(&find_event(&res, events::NOTE_STORED), attr::IDX), Some("2".to_string()));
+
+    let msg = ExecuteMsg::UpdateFees {
+      store_keys: Uint128::new(2000000),
+      store_notes: Uint128::new(1000000),
+      denom: "luna".to_string(),
+      burn_fees: false,
+      beneficiaries: vec![],
+      accumulate: false,
+    };
+    let res = execute(owndeps.as_mut(), mock_env(), mock_info("admin", &[]), msg).unwrap();
+    let event = find_event(&res, events::FEES_UPDATED);
+    assert_eq!(find_attr(&event, attr::OLD_STORE_NOTES), Some("500000".to_string()));
+    assert_eq!(find_attr(&event, attr::STORE_NOTES), Some("1000000".to_string()));
+    assert_eq!(find_attr(&event, attr::OLD_STORE_KEYS), Some("1000000".to_string()));
+    assert_eq!(find_attr(&event, attr::STORE_KEYS), Some("2000000".to_string()));
+  }
+
+  #[test]
+  fn relay_note() {
+    use k256::ecdsa::signature::Signer;
+    use k256::ecdsa::{Signature, SigningKey};
+
+    let mut owndeps = mock_dependencies();
+    instantiate_default(owndeps.as_mut());
+    let mut env = mock_env();
+    env.contract.address = MockApi::default().with_prefix("terra").addr_make("dropnote");
+
+    let key = SigningKey::from_bytes(&[7u8; 32].into()).unwrap();
+    let pubkey = key.verifying_key().to_encoded_point(true).as_bytes().to_vec();
+    let hash = Ripemd160::digest(Sha256::digest(&pubkey));
+    let sender = bech32::encode("terra", hash.to_base32(), Variant::Bech32).unwrap();
+
+    let sign = |payload: &NotePayload, nonce: u64, expires: Timestamp| -> SignedNote {
+      let sign_doc = RelaySignDoc {
+        chain_id: env.block.chain_id.clone(),
+        contract: env.contract.address.to_string(),
+        payload: payload.clone(),
+        nonce,
+        expires,
+      };
+      let signature: Signature = key.sign(&sign_doc.sign_bytes().unwrap());
+      SignedNote {
+        sender_pubkey: pubkey.clone().into(),
+        payload: payload.clone(),
+        nonce,
+        expires,
+        signature: signature.to_bytes().to_vec().into(),
+      }
+    };
+    let payload = NotePayload { recipient: "bob".to_string(), note: "foobar".to_string() };
+    let expires = env.block.time.plus_seconds(60);
+
+    // the relayer pays the fee
+    let msg = ExecuteMsg::RelayNote { signed: sign(&payload, 0, expires) };
+    let res = execute(owndeps.as_mut(), env.clone(), mock_info("relayer", &coins(500000, "luna")), msg.clone()).unwrap();
+    assert!(res.messages.iter().any(|submsg| is_fee_msg(submsg, "admin", "luna", 500000)));
+    assert!(res.events.iter().any(|event| event.ty == crate::events::NOTE_RELAYED));
+
+    let msg_notes = QueryMsg::Notes { recipient: "bob".to_string(), sender: sender.clone(), start_after: None, limit: None, range: None };
+    let res = from_json::<NotesResponse>(&query(owndeps.as_ref(), env.clone(), msg_notes).unwrap()).unwrap();
+    assert_eq!(res.notes.len(), 1);
+    assert_eq!(res.notes[0].sender, Addr::unchecked(sender.clone()));
+
+    // signatures cannot be replayed
+    let err = execute(owndeps.as_mut(), env.clone(), mock_info("relayer", &coins(500000, "luna")), msg).unwrap_err();
+    assert!(matches!(err, ContractError::InvalidNonce { expected: 1 }));
+
+    // without funds from the relayer, the fee is drawn from the sender's credits
+    execute(owndeps.as_mut(), env.clone(), mock_info(&sender, &coins(500000, "luna")), ExecuteMsg::Deposit {}).unwrap();
+    let msg = ExecuteMsg::RelayNote { signed: sign(&payload, 1, expires) };
+    let res = execute(owndeps.as_mut(), env.clone(), mock_info("relayer", &[]), msg).unwrap();
+    assert!(res.messages.iter().any(|submsg| is_fee_msg(submsg, "admin", "luna", 500000)));
+
+    let bin = query(owndeps.as_ref(), env.clone(), QueryMsg::RelayNonce { address: sender.clone() }).unwrap();
+    assert_eq!(from_json::<u64>(&bin).unwrap(), 2);
+
+    // tampered & expired notes are rejected
+    let mut signed = sign(&payload, 2, expires);
+    signed.payload.note = "barfoo".to_string();
+    let err = execute(owndeps.as_mut(), env.clone(), mock_info("relayer", &coins(500000, "luna")), ExecuteMsg::RelayNote { signed }).unwrap_err();
+    assert!(matches!(err, ContractError::InvalidSignature {}));
+
+    let signed = sign(&payload, 2, env.block.time);
+    let err = execute(owndeps.as_mut(), env.clone(), mock_info("relayer", &coins(500000, "luna")), ExecuteMsg::RelayNote { signed }).unwrap_err();
+    assert!(matches!(err, ContractError::SignatureExpired {}));
+
+    // fails once neither the relayer nor the sender can pay
+    let msg = ExecuteMsg::RelayNote { signed: sign(&payload, 2, expires) };
+    execute(owndeps.as_mut(), env.clone(), mock_info("relayer", &[]), msg).expect_err("Unexpected success");
+  }
+
+  #[test]
+  fn sealed_notes() {
+    let mut owndeps = mock_dependencies();
+    instantiate_default(owndeps.as_mut());
+    let envelope = Binary::from(b"sealed".to_vec());
+
+    // the fee must be attached, even with prepaid credits
+    execute(owndeps.as_mut(), mock_env(), mock_info("alice", &coins(500000, "luna")), ExecuteMsg::Deposit {}).unwrap();
+    let msg = ExecuteMsg::StoreSealedNote { recipient: "bob".to_string(), envelope: envelope.clone() };
+    execute(owndeps.as_mut(), mock_env(), mock_info("alice", &[]), msg.clone()).expect_err("Unexpected success");
+
+    // the sender shows up in neither the event nor the attributes
+    let res = execute(owndeps.as_mut(), mock_env(), mock_info("alice", &coins(500000, "luna")), msg.clone()).unwrap();
+    assert!(res.messages.iter().any(|submsg| is_fee_msg(submsg, "admin", "luna", 500000)));
+    assert!(res.attributes.iter().all(|attr| attr.value != "alice"));
+    assert!(res.events.iter().flat_map(|event| event.attributes.iter()).all(|attr| attr.value != "alice"));
+
+    // funds beyond the fee are rejected rather than handed to the recipient
+    let err = execute(owndeps.as_mut(), mock_env(), mock_info("alice", &coins(500001, "luna")), msg.clone()).unwrap_err();
+    assert!(matches!(err, ContractError::UnexpectedFunds {}));
+
+    // recipients may require postage, which is credited to them
+    let msg_postage = ExecuteMsg::SetPostage { postage: Some(Coin::new(100, "luna")) };
+    execute(owndeps.as_mut(), mock_env(), mock_info("bob", &[]), msg_postage).unwrap();
+    let bin = query(owndeps.as_ref(), mock_env(), QueryMsg::Postage { address: "bob".to_string() }).unwrap();
+    assert_eq!(from_json::<PostageResponse>(&bin).unwrap().postage, Some(Coin::new(100, "luna")));
+
+    let err = execute(owndeps.as_mut(), mock_env(), mock_info("alice", &coins(500099, "luna")), msg.clone()).unwrap_err();
+    assert!(matches!(err, ContractError::InsufficientPostage { .. }));
+    let err = execute(owndeps.as_mut(), mock_env(), mock_info("alice", &coins(500101, "luna")), msg.clone()).unwrap_err();
+    assert!(matches!(err, ContractError::UnexpectedFunds {}));
+    let funds = vec![Coin::new(500100, "luna"), Coin::new(1, "uusd")];
+    let err = execute(owndeps.as_mut(), mock_env(), mock_info("alice", &funds), msg.clone()).unwrap_err();
+    assert!(matches!(err, ContractError::UnexpectedFunds {}));
+    execute(owndeps.as_mut(), mock_env(), mock_info("alice", &coins(500100, "luna")), msg).unwrap();
+
+    let bin = query(owndeps.as_ref(), mock_env(), QueryMsg::Credits { address: "bob".to_string() }).unwrap();
+    assert_eq!(from_json::<CreditsResponse>(&bin).unwrap().credits, coins(100, "luna"));
+
+    let msg_notes = QueryMsg::SealedNotes { recipient: "bob".to_string(), start_after: None, limit: Some(1) };
+    let res = from_json::<SealedNotesResponse>(&query(owndeps.as_ref(), mock_env(), msg_notes).unwrap()).unwrap();
+    assert_eq!(res.notes.len(), 1);
+    assert_eq!(res.notes[0].envelope, envelope.to_vec());
+    assert_eq!(res.count, 2);
+    assert_eq!(res.next_cursor, Some(0));
+
+    let msg_notes = QueryMsg::SealedNotes { recipient: "bob".to_string(), start_after: Some(0), limit: None };
+    let res = from_json::<SealedNotesResponse>(&query(owndeps.as_ref(), mock_env(), msg_notes).unwrap()).unwrap();
+    assert_eq!(res.notes.len(), 1);
+    assert_eq!(res.next_cursor, None);
+
+    // sealed notes are not attributed to any sender
+    let msg_senders = QueryMsg::Senders { recipient: "bob".to_string(), start_after: None, limit: None };
+    let res = from_json::<SendersResponse>(&query(owndeps.as_ref(), mock_env(), msg_senders).unwrap()).unwrap();
+    assert!(res.senders.is_empty());
+
+    // but are included in the totals
+    let res = from_json::<StatsResponse>(&query(owndeps.as_ref(), mock_env(), QueryMsg::Stats {}).unwrap()).unwrap();
+    assert_eq!(res.notes, 2);
+    assert_eq!(res.bytes, 12);
+    assert_eq!(res.sealed_notes, 2);
+    assert_eq!(res.unique_senders, 0);
+    assert_eq!(res.unique_recipients, 1);
   }
 
   #[test]
+  #[allow(clippy::useless_vec)]
   fn query_note_count() {
     let mut owndeps = mock_dependencies();
     instantiate_no_fees(owndeps.as_mut());
@@ -450,22 +3279,27 @@ mod tests {
     assert_eq!(res, 1);
   }
 
+  #[allow(clippy::useless_vec)]
   fn instantiate_no_fees<'a>(deps: DepsMut<'a>) {
     instantiate(deps, mock_env(), mock_info("admin", &vec![]), InstantiateMsg {
+      admin: None,
       denom: "luna".to_string(),
       store_keys_fee: Uint128::zero(),
       store_notes_fee: Uint128::zero(),
     }).unwrap();
   }
 
+  #[allow(clippy::useless_vec)]
   fn instantiate_default<'a>(deps: DepsMut<'a>) {
     instantiate(deps, mock_env(), mock_info("admin", &vec![]), InstantiateMsg {
+      admin: None,
       denom: "luna".to_string(),
       store_keys_fee: Uint128::new(1000000), // 1L
       store_notes_fee: Uint128::new(500000), // 0.5L
     }).unwrap();
   }
 
+  #[allow(clippy::redundant_pattern_matching)]
   fn is_fee_msg(submsg: &SubMsg, recipient: &str, denom: &str, amount: u128) -> bool {
     if let CosmosMsg::Bank(BankMsg::Send { to_address, amount: coins }) = &submsg.msg {
       if to_address != recipient {
@@ -1,17 +1,143 @@
 use cosmwasm_std::StdError;
 use thiserror::Error;
 
+use crate::state::{Operation, Role};
+
 #[derive(Error, Debug)]
 pub enum ContractError {
   #[error("{0}")]
   Std(#[from] StdError),
 
-  #[error("Unauthorized")]
-  Unauthorized {},
+  #[error("{0}")]
+  Semver(#[from] semver::Error),
+
+  /// The sender is neither the admin nor holds the required role. When the admin is a cw3 multisig
+  /// or DAO, its members cannot act on its behalf directly: the message must be wrapped in a
+  /// proposal which, once passed, is executed by the DAO contract itself.
+  #[error("Unauthorized: missing role {role}; must be executed by the admin (e.g. via a passed DAO proposal) or a holder of the role")]
+  Unauthorized { role: Role },
+
+  #[error("Role {role} cannot be granted or revoked")]
+  InvalidRole { role: Role },
+
+  #[error("Operation {operation} is paused")]
+  Paused { operation: Operation },
+
+  #[error("No admin transfer pending")]
+  NoPendingAdmin {},
+
+  /// A DAO proposed as admin accepts the transfer by executing `AcceptAdmin` through a proposal.
+  #[error("Sender is not the pending admin; the pending admin must accept the transfer itself, e.g. via a passed DAO proposal")]
+  NotPendingAdmin {},
+
+  #[error("Admin transfer expired")]
+  AdminTransferExpired {},
+
+  #[error("Cannot migrate from contract {contract}")]
+  InvalidMigration { contract: String },
+
+  #[error("Cannot downgrade from version {from} to {to}")]
+  Downgrade { from: String, to: String },
+
+  #[error("Storage migration pending; continue it by migrating again")]
+  MigrationPending {},
 
   #[error("Insufficient funds")]
   InsufficientFunds {},
 
+  #[error("No funds attached")]
+  NoFunds {},
+
+  #[error("Unexpected funds attached")]
+  UnexpectedFunds {},
+
+  #[error("No sponsorship offered by {sponsor}")]
+  NoSponsorshipOffer { sponsor: String },
+
+  #[error("Too many sponsors: at most {max} per sender")]
+  TooManySponsors { max: usize },
+
+  #[error("Fees can only be changed through scheduled updates with {delay}s notice")]
+  FeeUpdateDelayed { delay: u64 },
+
+  #[error("Scheduled fee update cannot take effect before {earliest}")]
+  FeeUpdateTooEarly { earliest: cosmwasm_std::Timestamp },
+
+  #[error("No fee update scheduled")]
+  NoScheduledFeeUpdate {},
+
+  #[error("Invalid fee tier: discount must be between 1 and 10000 bps")]
+  InvalidFeeTier {},
+
+  #[error("Invalid beneficiaries: shares must be non-zero and sum to 10000 bps")]
+  InvalidBeneficiaries {},
+
+  #[error("Unknown reply id {id}")]
+  UnknownReply { id: u64 },
+
+  #[error("Only contracts can register hooks")]
+  NotAContract {},
+
+  #[error("Too many subscribers: at most {max} allowed")]
+  TooManySubscribers { max: usize },
+
+  #[error("Gas limit too high: at most {max} allowed")]
+  GasLimitTooHigh { max: u64 },
+
+  #[error("Already subscribed")]
+  AlreadySubscribed {},
+
+  #[error("Not subscribed")]
+  NotSubscribed {},
+
+  #[error("Subscriptions are only open to contracts added by a moderator")]
+  SubscriptionClosed {},
+
+  #[error("Subscribing requires a deposit of exactly {deposit}")]
+  InvalidDeposit { deposit: cosmwasm_std::Coin },
+
+  #[error("Subscription deposit must not be zero, unset it to close self-registration instead")]
+  ZeroDeposit {},
+
+  #[error("Invalid IBC channel: must be unordered with version {version}")]
+  InvalidChannel { version: String },
+
+  #[error("IBC channels to port {port_id} over connection {connection_id} are not allowed")]
+  UnknownCounterparty { connection_id: String, port_id: String },
+
+  #[error("Unknown IBC channel {channel}")]
+  UnknownChannel { channel: String },
+
+  #[error("Invalid pagination cursor")]
+  InvalidCursor {},
+
+  #[error("Invalid chain id")]
+  InvalidChainId {},
+
+  #[error("Invalid remote sender")]
+  InvalidRemoteSender {},
+
+  #[error("Invalid public key: must be a compressed secp256k1 key")]
+  InvalidPubkey {},
+
+  #[error("Invalid signature")]
+  InvalidSignature {},
+
+  #[error("Signature expired")]
+  SignatureExpired {},
+
+  #[error("Invalid nonce: expected {expected}")]
+  InvalidNonce { expected: u64 },
+
+  #[error("Insufficient postage: recipient requires {postage}")]
+  InsufficientPostage { postage: cosmwasm_std::Coin },
+
+  #[error("No encryption key registered")]
+  NoEncryptionKey {},
+
+  #[error("Batch too large: at most {max} entries allowed")]
+  BatchTooLarge { max: usize },
+
   // Add any other custom errors you like here.
   // Look at https://docs.rs/thiserror/1.0.21/thiserror/ for details.
 }
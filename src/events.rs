@@ -0,0 +1,634 @@
+use cosmwasm_std::{Addr, Coin, Event, HexBinary, IbcChannel, Timestamp};
+use sha2::{Digest, Sha256};
+
+use crate::state::{FeeTier, Fees, HookFailure, Operation, ReceiveHook, Role};
+
+// Event types emitted for every state change. The chain prefixes them with `wasm-`, e.g.
+// `wasm-dropnote_note_stored`. Optional attributes are omitted when unset.
+pub const NOTE_STORED: &str = "dropnote_note_stored";
+pub const NOTES_READ: &str = "dropnote_notes_read";
+pub const RECEIVE_HOOK_SET: &str = "dropnote_receive_hook_set";
+pub const RECEIVE_HOOK_FAILED: &str = "dropnote_receive_hook_failed";
+pub const SUBSCRIBER_ADDED: &str = "dropnote_subscriber_added";
+pub const SUBSCRIBER_REMOVED: &str = "dropnote_subscriber_removed";
+pub const SUBSCRIBER_FAILED: &str = "dropnote_subscriber_failed";
+pub const SUBSCRIPTION_DEPOSIT_SET: &str = "dropnote_subscription_deposit_set";
+pub const REMOTE_NOTE_SENT: &str = "dropnote_remote_note_sent";
+pub const REMOTE_NOTE_DELIVERED: &str = "dropnote_remote_note_delivered";
+pub const REMOTE_NOTE_FAILED: &str = "dropnote_remote_note_failed";
+pub const REMOTE_KEY_REQUESTED: &str = "dropnote_remote_key_requested";
+pub const REMOTE_KEY_CACHED: &str = "dropnote_remote_key_cached";
+pub const REMOTE_KEY_LOOKUP_FAILED: &str = "dropnote_remote_key_lookup_failed";
+pub const CHANNEL_CONNECTED: &str = "dropnote_channel_connected";
+pub const CHANNEL_CLOSED: &str = "dropnote_channel_closed";
+pub const COUNTERPARTY_ALLOWED: &str = "dropnote_counterparty_allowed";
+pub const COUNTERPARTY_REMOVED: &str = "dropnote_counterparty_removed";
+pub const SEALED_NOTE_STORED: &str = "dropnote_sealed_note_stored";
+pub const POSTAGE_SET: &str = "dropnote_postage_set";
+pub const NOTE_RELAYED: &str = "dropnote_note_relayed";
+pub const KEY_UPDATED: &str = "dropnote_key_updated";
+pub const FEES_UPDATED: &str = "dropnote_fees_updated";
+pub const FEE_UPDATE_SCHEDULED: &str = "dropnote_fee_update_scheduled";
+pub const FEE_UPDATE_CANCELLED: &str = "dropnote_fee_update_cancelled";
+pub const FEE_TIER_SET: &str = "dropnote_fee_tier_set";
+pub const FEES_WITHDRAWN: &str = "dropnote_fees_withdrawn";
+pub const CREDITS_DEPOSITED: &str = "dropnote_credits_deposited";
+pub const CREDITS_WITHDRAWN: &str = "dropnote_credits_withdrawn";
+pub const SPONSOR_UPDATED: &str = "dropnote_sponsor_updated";
+pub const SPONSORSHIPS_REMOVED: &str = "dropnote_sponsorships_removed";
+pub const SPONSORSHIP_ACCEPTED: &str = "dropnote_sponsorship_accepted";
+pub const ADMIN_TRANSFER_PROPOSED: &str = "dropnote_admin_transfer_proposed";
+pub const ADMIN_TRANSFER_CANCELLED: &str = "dropnote_admin_transfer_cancelled";
+pub const ADMIN_CHANGED: &str = "dropnote_admin_changed";
+pub const ROLE_GRANTED: &str = "dropnote_role_granted";
+pub const ROLE_REVOKED: &str = "dropnote_role_revoked";
+pub const PAUSED: &str = "dropnote_paused";
+pub const UNPAUSED: &str = "dropnote_unpaused";
+
+/// Attribute keys shared across events.
+pub mod attr {
+  pub const SENDER: &str = "sender";
+  pub const RECIPIENT: &str = "recipient";
+  pub const IDX: &str = "idx";
+  pub const SIZE: &str = "size";
+  pub const KEY_ID: &str = "key_id";
+  pub const KEY_HASH: &str = "key_hash";
+  pub const FEE: &str = "fee";
+  pub const SPONSOR: &str = "sponsor";
+  pub const READ: &str = "read";
+  pub const ADDRESS: &str = "address";
+  pub const DENOM: &str = "denom";
+  pub const STORE_KEYS: &str = "store_keys";
+  pub const STORE_NOTES: &str = "store_notes";
+  pub const OLD_DENOM: &str = "old_denom";
+  pub const OLD_STORE_KEYS: &str = "old_store_keys";
+  pub const OLD_STORE_NOTES: &str = "old_store_notes";
+  pub const BURN_FEES: &str = "burn_fees";
+  pub const ACCUMULATE: &str = "accumulate";
+  pub const UPDATE_DELAY: &str = "update_delay";
+  pub const EFFECTIVE_AT: &str = "effective_at";
+  pub const TIER: &str = "tier";
+  pub const TO: &str = "to";
+  pub const AMOUNT: &str = "amount";
+  pub const SENDERS: &str = "senders";
+  pub const PER_SENDER_CAP: &str = "per_sender_cap";
+  pub const EXPIRES: &str = "expires";
+  pub const ADMIN: &str = "admin";
+  pub const PREVIOUS_ADMIN: &str = "previous_admin";
+  pub const PENDING_ADMIN: &str = "pending_admin";
+  pub const EXPIRY: &str = "expiry";
+  pub const ROLE: &str = "role";
+  pub const OPERATIONS: &str = "operations";
+  pub const ON_ERROR: &str = "on_error";
+  pub const ERROR: &str = "error";
+  pub const GAS_LIMIT: &str = "gas_limit";
+  pub const DEPOSIT: &str = "deposit";
+  pub const REFUND: &str = "refund";
+  pub const REASON: &str = "reason";
+  pub const FAILURES: &str = "failures";
+  pub const CHANNEL: &str = "channel";
+  pub const PACKET_ID: &str = "packet_id";
+  pub const COUNTERPARTY_PORT: &str = "counterparty_port";
+  pub const COUNTERPARTY_CHANNEL: &str = "counterparty_channel";
+  pub const CONNECTION_ID: &str = "connection_id";
+  pub const PORT_ID: &str = "port_id";
+  pub const CHAIN_ID: &str = "chain_id";
+  pub const RELAYER: &str = "relayer";
+  pub const NONCE: &str = "nonce";
+  pub const SEQ: &str = "seq";
+  pub const POSTAGE: &str = "postage";
+}
+
+/// Identify an encryption key by the hex encoded SHA-256 hash of its bytes.
+pub fn key_hash(key: &[u8]) -> String {
+  HexBinary::from(Sha256::digest(key).as_slice()).to_hex()
+}
+
+fn with_optional(event: Event, key: &str, value: Option<impl ToString>) -> Event {
+  match value {
+    Some(value) => event.add_attribute(key, value.to_string()),
+    None => event,
+  }
+}
+
+fn join<T: AsRef<str>>(items: &[T]) -> String {
+  items.iter().map(|item| item.as_ref()).collect::<Vec<_>>().join(",")
+}
+
+pub struct NoteStored {
+  pub sender: Addr,
+  pub recipient: Addr,
+  pub idx: u64,
+  pub size: u64,
+  /// Hash of the recipient's encryption key at the time the note was stored.
+  pub key_id: Option<String>,
+  pub fee: Option<Coin>,
+  pub sponsor: Option<Addr>,
+}
+
+impl From<NoteStored> for Event {
+  fn from(ev: NoteStored) -> Event {
+    let event = Event::new(NOTE_STORED)
+      .add_attribute(attr::SENDER, ev.sender)
+      .add_attribute(attr::RECIPIENT, ev.recipient)
+      .add_attribute(attr::IDX, ev.idx.to_string())
+      .add_attribute(attr::SIZE, ev.size.to_string());
+    let event = with_optional(event, attr::KEY_ID, ev.key_id);
+    let event = with_optional(event, attr::FEE, ev.fee);
+    with_optional(event, attr::SPONSOR, ev.sponsor)
+  }
+}
+
+pub struct NotesRead {
+  pub recipient: Addr,
+  pub sender: Addr,
+  /// Number of notes read, oldest first.
+  pub read: u64,
+}
+
+impl From<NotesRead> for Event {
+  fn from(ev: NotesRead) -> Event {
+    Event::new(NOTES_READ)
+      .add_attribute(attr::RECIPIENT, ev.recipient)
+      .add_attribute(attr::SENDER, ev.sender)
+      .add_attribute(attr::READ, ev.read.to_string())
+  }
+}
+
+/// Deliberately carries neither the sender nor the fee payer.
+pub struct SealedNoteStored {
+  pub recipient: Addr,
+  pub seq: u64,
+  pub size: u64,
+  pub postage: Option<Coin>,
+}
+
+impl From<SealedNoteStored> for Event {
+  fn from(ev: SealedNoteStored) -> Event {
+    let event = Event::new(SEALED_NOTE_STORED)
+      .add_attribute(attr::RECIPIENT, ev.recipient)
+      .add_attribute(attr::SEQ, ev.seq.to_string())
+      .add_attribute(attr::SIZE, ev.size.to_string());
+    with_optional(event, attr::POSTAGE, ev.postage)
+  }
+}
+
+pub struct PostageSet {
+  pub address: Addr,
+  pub postage: Option<Coin>,
+}
+
+impl From<PostageSet> for Event {
+  fn from(ev: PostageSet) -> Event {
+    let event = Event::new(POSTAGE_SET).add_attribute(attr::ADDRESS, ev.address);
+    with_optional(event, attr::POSTAGE, ev.postage)
+  }
+}
+
+/// Emitted alongside `NoteStored` for notes submitted by a relayer on behalf of their sender.
+pub struct NoteRelayed {
+  pub relayer: Addr,
+  pub sender: Addr,
+  pub nonce: u64,
+}
+
+impl From<NoteRelayed> for Event {
+  fn from(ev: NoteRelayed) -> Event {
+    Event::new(NOTE_RELAYED)
+      .add_attribute(attr::RELAYER, ev.relayer)
+      .add_attribute(attr::SENDER, ev.sender)
+      .add_attribute(attr::NONCE, ev.nonce.to_string())
+  }
+}
+
+pub struct ReceiveHookSet {
+  pub address: Addr,
+  pub hook: Option<ReceiveHook>,
+}
+
+impl From<ReceiveHookSet> for Event {
+  fn from(ev: ReceiveHookSet) -> Event {
+    let on_error = match ev.hook.map(|hook| hook.on_error) {
+      Some(HookFailure::Revert) => "revert",
+      Some(HookFailure::Ignore) => "ignore",
+      None => "none",
+    };
+    Event::new(RECEIVE_HOOK_SET)
+      .add_attribute(attr::ADDRESS, ev.address)
+      .add_attribute(attr::ON_ERROR, on_error)
+  }
+}
+
+/// A receive hook configured to ignore failures has failed. The note was stored regardless.
+pub struct ReceiveHookFailed {
+  pub error: String,
+}
+
+impl From<ReceiveHookFailed> for Event {
+  fn from(ev: ReceiveHookFailed) -> Event {
+    Event::new(RECEIVE_HOOK_FAILED).add_attribute(attr::ERROR, ev.error)
+  }
+}
+
+pub struct SubscriberAdded {
+  pub address: Addr,
+  pub gas_limit: u64,
+  pub deposit: Option<Coin>,
+}
+
+impl From<SubscriberAdded> for Event {
+  fn from(ev: SubscriberAdded) -> Event {
+    let event = Event::new(SUBSCRIBER_ADDED)
+      .add_attribute(attr::ADDRESS, ev.address)
+      .add_attribute(attr::GAS_LIMIT, ev.gas_limit.to_string());
+    with_optional(event, attr::DEPOSIT, ev.deposit)
+  }
+}
+
+/// A subscriber was removed by a moderator, unsubscribed itself, or was removed for failing
+/// repeatedly, in which case its deposit is forfeited rather than refunded.
+pub struct SubscriberRemoved {
+  pub address: Addr,
+  pub reason: &'static str,
+  pub refund: Option<Coin>,
+}
+
+impl From<SubscriberRemoved> for Event {
+  fn from(ev: SubscriberRemoved) -> Event {
+    let event = Event::new(SUBSCRIBER_REMOVED)
+      .add_attribute(attr::ADDRESS, ev.address)
+      .add_attribute(attr::REASON, ev.reason);
+    with_optional(event, attr::REFUND, ev.refund)
+  }
+}
+
+pub struct SubscriberFailed {
+  pub address: Addr,
+  /// Consecutive failures, including this one.
+  pub failures: u32,
+  pub error: String,
+}
+
+impl From<SubscriberFailed> for Event {
+  fn from(ev: SubscriberFailed) -> Event {
+    Event::new(SUBSCRIBER_FAILED)
+      .add_attribute(attr::ADDRESS, ev.address)
+      .add_attribute(attr::FAILURES, ev.failures.to_string())
+      .add_attribute(attr::ERROR, ev.error)
+  }
+}
+
+pub struct SubscriptionDepositSet {
+  pub deposit: Option<Coin>,
+}
+
+impl From<SubscriptionDepositSet> for Event {
+  fn from(ev: SubscriptionDepositSet) -> Event {
+    with_optional(Event::new(SUBSCRIPTION_DEPOSIT_SET), attr::DEPOSIT, ev.deposit)
+  }
+}
+
+pub struct RemoteNoteSent {
+  pub sender: Addr,
+  pub channel: String,
+  pub recipient: String,
+  pub packet_id: u64,
+  pub size: u64,
+  pub fee: Option<Coin>,
+  pub sponsor: Option<Addr>,
+}
+
+impl From<RemoteNoteSent> for Event {
+  fn from(ev: RemoteNoteSent) -> Event {
+    let event = Event::new(REMOTE_NOTE_SENT)
+      .add_attribute(attr::SENDER, ev.sender)
+      .add_attribute(attr::CHANNEL, ev.channel)
+      .add_attribute(attr::RECIPIENT, ev.recipient)
+      .add_attribute(attr::PACKET_ID, ev.packet_id.to_string())
+      .add_attribute(attr::SIZE, ev.size.to_string());
+    let event = with_optional(event, attr::FEE, ev.fee);
+    with_optional(event, attr::SPONSOR, ev.sponsor)
+  }
+}
+
+/// A note sent over IBC was either delivered, releasing its fee, or failed to be delivered, in
+/// which case its fee was refunded.
+pub struct RemoteNoteSettled {
+  pub channel: String,
+  pub packet_id: u64,
+  pub sender: String,
+  pub recipient: String,
+  pub error: Option<String>,
+  pub refund: Option<Coin>,
+}
+
+impl From<RemoteNoteSettled> for Event {
+  fn from(ev: RemoteNoteSettled) -> Event {
+    let event = Event::new(if ev.error.is_none() { REMOTE_NOTE_DELIVERED } else { REMOTE_NOTE_FAILED })
+      .add_attribute(attr::CHANNEL, ev.channel)
+      .add_attribute(attr::PACKET_ID, ev.packet_id.to_string())
+      .add_attribute(attr::SENDER, ev.sender)
+      .add_attribute(attr::RECIPIENT, ev.recipient);
+    let event = with_optional(event, attr::ERROR, ev.error);
+    with_optional(event, attr::REFUND, ev.refund)
+  }
+}
+
+pub struct RemoteKeyRequested {
+  pub sender: Addr,
+  pub channel: String,
+  pub address: String,
+  pub fee: Option<Coin>,
+  pub sponsor: Option<Addr>,
+}
+
+impl From<RemoteKeyRequested> for Event {
+  fn from(ev: RemoteKeyRequested) -> Event {
+    let event = Event::new(REMOTE_KEY_REQUESTED)
+      .add_attribute(attr::SENDER, ev.sender)
+      .add_attribute(attr::CHANNEL, ev.channel)
+      .add_attribute(attr::ADDRESS, ev.address);
+    let event = with_optional(event, attr::FEE, ev.fee);
+    with_optional(event, attr::SPONSOR, ev.sponsor)
+  }
+}
+
+/// A key lookup was either answered and cached, or failed. Addresses without a key are cached
+/// without a key hash.
+pub struct RemoteKeyLookedUp {
+  pub channel: String,
+  pub address: String,
+  pub chain_id: Option<String>,
+  pub key_hash: Option<String>,
+  pub error: Option<String>,
+}
+
+impl From<RemoteKeyLookedUp> for Event {
+  fn from(ev: RemoteKeyLookedUp) -> Event {
+    let event = Event::new(if ev.error.is_none() { REMOTE_KEY_CACHED } else { REMOTE_KEY_LOOKUP_FAILED })
+      .add_attribute(attr::CHANNEL, ev.channel)
+      .add_attribute(attr::ADDRESS, ev.address);
+    let event = with_optional(event, attr::CHAIN_ID, ev.chain_id);
+    let event = with_optional(event, attr::KEY_HASH, ev.key_hash);
+    with_optional(event, attr::ERROR, ev.error)
+  }
+}
+
+pub struct ChannelChanged<'a> {
+  pub channel: &'a IbcChannel,
+  pub connected: bool,
+}
+
+impl From<ChannelChanged<'_>> for Event {
+  fn from(ev: ChannelChanged) -> Event {
+    Event::new(if ev.connected { CHANNEL_CONNECTED } else { CHANNEL_CLOSED })
+      .add_attribute(attr::CHANNEL, ev.channel.endpoint.channel_id.as_str())
+      .add_attribute(attr::COUNTERPARTY_PORT, ev.channel.counterparty_endpoint.port_id.as_str())
+      .add_attribute(attr::COUNTERPARTY_CHANNEL, ev.channel.counterparty_endpoint.channel_id.as_str())
+  }
+}
+
+pub struct CounterpartyChanged {
+  pub connection_id: String,
+  /// Unset once removed.
+  pub port_id: Option<String>,
+  pub chain_id: Option<String>,
+}
+
+impl From<CounterpartyChanged> for Event {
+  fn from(ev: CounterpartyChanged) -> Event {
+    let event = Event::new(if ev.port_id.is_some() { COUNTERPARTY_ALLOWED } else { COUNTERPARTY_REMOVED })
+      .add_attribute(attr::CONNECTION_ID, ev.connection_id);
+    let event = with_optional(event, attr::PORT_ID, ev.port_id);
+    with_optional(event, attr::CHAIN_ID, ev.chain_id)
+  }
+}
+
+pub struct KeyUpdated {
+  pub address: Addr,
+  pub key_hash: String,
+  pub fee: Option<Coin>,
+  pub sponsor: Option<Addr>,
+}
+
+impl From<KeyUpdated> for Event {
+  fn from(ev: KeyUpdated) -> Event {
+    let event = Event::new(KEY_UPDATED)
+      .add_attribute(attr::ADDRESS, ev.address)
+      .add_attribute(attr::KEY_HASH, ev.key_hash);
+    let event = with_optional(event, attr::FEE, ev.fee);
+    with_optional(event, attr::SPONSOR, ev.sponsor)
+  }
+}
+
+pub struct FeesUpdated<'a> {
+  pub old: &'a Fees,
+  pub new: &'a Fees,
+}
+
+impl From<FeesUpdated<'_>> for Event {
+  fn from(ev: FeesUpdated) -> Event {
+    Event::new(FEES_UPDATED)
+      .add_attribute(attr::OLD_DENOM, ev.old.denom.as_str())
+      .add_attribute(attr::OLD_STORE_KEYS, ev.old.store_keys.to_string())
+      .add_attribute(attr::OLD_STORE_NOTES, ev.old.store_notes.to_string())
+      .add_attributes(fees_attributes(ev.new))
+  }
+}
+
+pub struct FeeUpdateScheduled<'a> {
+  pub fees: &'a Fees,
+  pub effective_at: Timestamp,
+}
+
+impl From<FeeUpdateScheduled<'_>> for Event {
+  fn from(ev: FeeUpdateScheduled) -> Event {
+    Event::new(FEE_UPDATE_SCHEDULED)
+      .add_attributes(fees_attributes(ev.fees))
+      .add_attribute(attr::EFFECTIVE_AT, ev.effective_at.to_string())
+  }
+}
+
+fn fees_attributes(fees: &Fees) -> Vec<(&'static str, String)> {
+  vec![
+    (attr::DENOM, fees.denom.clone()),
+    (attr::STORE_KEYS, fees.store_keys.to_string()),
+    (attr::STORE_NOTES, fees.store_notes.to_string()),
+    (attr::BURN_FEES, fees.burn_fees.to_string()),
+    (attr::ACCUMULATE, fees.accumulate.to_string()),
+    (attr::UPDATE_DELAY, fees.update_delay.to_string()),
+  ]
+}
+
+pub struct FeeUpdateCancelled;
+
+impl From<FeeUpdateCancelled> for Event {
+  fn from(_: FeeUpdateCancelled) -> Event {
+    Event::new(FEE_UPDATE_CANCELLED)
+  }
+}
+
+pub struct FeeTierSet {
+  pub address: Addr,
+  pub tier: Option<FeeTier>,
+}
+
+impl From<FeeTierSet> for Event {
+  fn from(ev: FeeTierSet) -> Event {
+    let tier = match ev.tier {
+      Some(FeeTier::Exempt {}) => "exempt".to_string(),
+      Some(FeeTier::Discount { bps }) => format!("discount:{bps}"),
+      None => "none".to_string(),
+    };
+    Event::new(FEE_TIER_SET)
+      .add_attribute(attr::ADDRESS, ev.address)
+      .add_attribute(attr::TIER, tier)
+  }
+}
+
+pub struct FeesWithdrawn {
+  pub address: Addr,
+  pub to: Addr,
+  pub amount: Coin,
+}
+
+impl From<FeesWithdrawn> for Event {
+  fn from(ev: FeesWithdrawn) -> Event {
+    Event::new(FEES_WITHDRAWN)
+      .add_attribute(attr::ADDRESS, ev.address)
+      .add_attribute(attr::TO, ev.to)
+      .add_attribute(attr::AMOUNT, ev.amount.to_string())
+  }
+}
+
+pub struct CreditsDeposited {
+  pub address: Addr,
+  pub amount: Vec<Coin>,
+}
+
+impl From<CreditsDeposited> for Event {
+  fn from(ev: CreditsDeposited) -> Event {
+    let amount: Vec<_> = ev.amount.iter().map(|coin| coin.to_string()).collect();
+    Event::new(CREDITS_DEPOSITED)
+      .add_attribute(attr::ADDRESS, ev.address)
+      .add_attribute(attr::AMOUNT, join(&amount))
+  }
+}
+
+pub struct CreditsWithdrawn {
+  pub address: Addr,
+  pub amount: Coin,
+}
+
+impl From<CreditsWithdrawn> for Event {
+  fn from(ev: CreditsWithdrawn) -> Event {
+    Event::new(CREDITS_WITHDRAWN)
+      .add_attribute(attr::ADDRESS, ev.address)
+      .add_attribute(attr::AMOUNT, ev.amount.to_string())
+  }
+}
+
+pub struct SponsorUpdated {
+  pub sponsor: Addr,
+  /// Senders offered sponsorship by this update.
+  pub senders: Vec<Addr>,
+  pub per_sender_cap: Option<Coin>,
+  pub expires: Option<Timestamp>,
+}
+
+impl From<SponsorUpdated> for Event {
+  fn from(ev: SponsorUpdated) -> Event {
+    let event = Event::new(SPONSOR_UPDATED)
+      .add_attribute(attr::SPONSOR, ev.sponsor)
+      .add_attribute(attr::SENDERS, join(&ev.senders));
+    let event = with_optional(event, attr::PER_SENDER_CAP, ev.per_sender_cap);
+    with_optional(event, attr::EXPIRES, ev.expires)
+  }
+}
+
+pub struct SponsorshipsRemoved {
+  pub sponsor: Addr,
+  pub senders: Vec<Addr>,
+}
+
+impl From<SponsorshipsRemoved> for Event {
+  fn from(ev: SponsorshipsRemoved) -> Event {
+    Event::new(SPONSORSHIPS_REMOVED)
+      .add_attribute(attr::SPONSOR, ev.sponsor)
+      .add_attribute(attr::SENDERS, join(&ev.senders))
+  }
+}
+
+pub struct SponsorshipAccepted {
+  pub sponsor: Addr,
+  pub sender: Addr,
+}
+
+impl From<SponsorshipAccepted> for Event {
+  fn from(ev: SponsorshipAccepted) -> Event {
+    Event::new(SPONSORSHIP_ACCEPTED)
+      .add_attribute(attr::SPONSOR, ev.sponsor)
+      .add_attribute(attr::SENDER, ev.sender)
+  }
+}
+
+pub struct AdminTransferProposed {
+  pub pending_admin: Addr,
+  pub expiry: Option<Timestamp>,
+}
+
+impl From<AdminTransferProposed> for Event {
+  fn from(ev: AdminTransferProposed) -> Event {
+    let event = Event::new(ADMIN_TRANSFER_PROPOSED).add_attribute(attr::PENDING_ADMIN, ev.pending_admin);
+    with_optional(event, attr::EXPIRY, ev.expiry)
+  }
+}
+
+pub struct AdminTransferCancelled;
+
+impl From<AdminTransferCancelled> for Event {
+  fn from(_: AdminTransferCancelled) -> Event {
+    Event::new(ADMIN_TRANSFER_CANCELLED)
+  }
+}
+
+/// The admin changed through an accepted transfer, renouncement or sudo. An unset admin means
+/// the contract has no admin anymore.
+pub struct AdminChanged {
+  pub previous_admin: Option<Addr>,
+  pub admin: Option<Addr>,
+}
+
+impl From<AdminChanged> for Event {
+  fn from(ev: AdminChanged) -> Event {
+    let event = with_optional(Event::new(ADMIN_CHANGED), attr::PREVIOUS_ADMIN, ev.previous_admin);
+    with_optional(event, attr::ADMIN, ev.admin)
+  }
+}
+
+pub struct RoleChanged {
+  pub role: Role,
+  pub address: Addr,
+  pub granted: bool,
+}
+
+impl From<RoleChanged> for Event {
+  fn from(ev: RoleChanged) -> Event {
+    Event::new(if ev.granted { ROLE_GRANTED } else { ROLE_REVOKED })
+      .add_attribute(attr::ROLE, ev.role.as_str())
+      .add_attribute(attr::ADDRESS, ev.address)
+  }
+}
+
+pub struct PauseChanged {
+  pub operations: Vec<Operation>,
+  pub paused: bool,
+}
+
+impl From<PauseChanged> for Event {
+  fn from(ev: PauseChanged) -> Event {
+    let operations: Vec<_> = ev.operations.iter().map(|op| op.as_str()).collect();
+    Event::new(if ev.paused { PAUSED } else { UNPAUSED })
+      .add_attribute(attr::OPERATIONS, join(&operations))
+  }
+}
@@ -0,0 +1,616 @@
+#[cfg(not(feature = "library"))]
+use cosmwasm_std::entry_point;
+use cosmwasm_std::{
+  from_json, to_json_binary, Addr, Binary, DepsMut, Env, Ibc3ChannelOpenResponse, IbcBasicResponse, IbcChannel,
+  IbcChannelCloseMsg, IbcChannelConnectMsg, IbcChannelOpenMsg, IbcChannelOpenResponse, IbcOrder, IbcPacket,
+  IbcPacketAckMsg, IbcPacketReceiveMsg, IbcPacketTimeoutMsg, IbcReceiveResponse, StdResult, Storage,
+};
+
+use crate::ContractResult;
+use crate::contract::distribute_fee;
+use crate::error::ContractError;
+use crate::events::{key_hash, ChannelChanged, NoteStored, RemoteKeyLookedUp, RemoteNoteSettled};
+use crate::msg::{KeyLookupRequest, KeyLookupResponse, NotePacket, NotePacketResult, PacketAck, PacketMsg};
+use crate::state::{
+  add_credits, is_paused, load_channel_chain, load_counterparty, load_enc_key, load_fees, load_sponsorship,
+  remove_channel, save_channel, save_remote_key, save_sponsorship, store_note, take_escrow, Counterparty, Escrow, Note,
+  Operation, RemoteKey,
+};
+
+pub const IBC_VERSION: &str = "dropnote-1";
+// seconds after which packets time out
+pub const PACKET_TIMEOUT: u64 = 60 * 60;
+// seconds for which looked up remote keys are cached
+pub const REMOTE_KEY_TTL: u64 = 24 * 60 * 60;
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn ibc_channel_open(deps: DepsMut, _env: Env, msg: IbcChannelOpenMsg) -> ContractResult<IbcChannelOpenResponse> {
+  let channel = msg.channel();
+  let counterparty_version = msg.counterparty_version().unwrap_or(IBC_VERSION);
+  if channel.order != IbcOrder::Unordered || channel.version != IBC_VERSION || counterparty_version != IBC_VERSION {
+    return Err(ContractError::InvalidChannel { version: IBC_VERSION.to_string() });
+  }
+  ensure_counterparty(deps.storage, channel)?;
+  Ok(Some(Ibc3ChannelOpenResponse { version: IBC_VERSION.to_string() }))
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn ibc_channel_connect(deps: DepsMut, _env: Env, msg: IbcChannelConnectMsg) -> ContractResult<IbcBasicResponse> {
+  let channel = msg.channel();
+  let counterparty = ensure_counterparty(deps.storage, channel)?;
+  save_channel(deps.storage, channel, counterparty.chain_id)?;
+
+  Ok(IbcBasicResponse::new()
+    .add_event(ChannelChanged { channel, connected: true }.into())
+    .add_attribute("method", "ibc_channel_connect")
+    .add_attribute("channel", channel.endpoint.channel_id.as_str())
+  )
+}
+
+/// Only channels to the counterpart instance approved by the admin for the connection are accepted.
+fn ensure_counterparty(store: &dyn Storage, channel: &IbcChannel) -> ContractResult<Counterparty> {
+  let port_id = &channel.counterparty_endpoint.port_id;
+  match load_counterparty(store, channel.connection_id.clone())? {
+    Some(counterparty) if counterparty.port_id == *port_id => Ok(counterparty),
+    _ => Err(ContractError::UnknownCounterparty { connection_id: channel.connection_id.clone(), port_id: port_id.clone() }),
+  }
+}
+
+/// Pending packets of a closed channel time out, which refunds their fees.
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn ibc_channel_close(deps: DepsMut, _env: Env, msg: IbcChannelCloseMsg) -> ContractResult<IbcBasicResponse> {
+  let channel = msg.channel();
+  remove_channel(deps.storage, channel.endpoint.channel_id.clone());
+
+  Ok(IbcBasicResponse::new()
+    .add_event(ChannelChanged { channel, connected: false }.into())
+    .add_attribute("method", "ibc_channel_close")
+    .add_attribute("channel", channel.endpoint.channel_id.as_str())
+  )
+}
+
+/// Handle packets of counterpart instances. Failures are reported in an error acknowledgement rather
+/// than aborting the transaction, such that the source can e.g. refund the fee.
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn ibc_packet_receive(deps: DepsMut, env: Env, msg: IbcPacketReceiveMsg) -> ContractResult<IbcReceiveResponse> {
+  let result = from_json(&msg.packet.data)
+    .map_err(ContractError::from)
+    .and_then(|packet| match packet {
+      PacketMsg::StoreNote(packet) => receive_note(deps, env, &msg.packet.dest.channel_id, packet),
+      PacketMsg::KeyLookup(request) => receive_key_lookup(deps, env, request),
+    });
+
+  match result {
+    Ok(response) => Ok(response),
+    Err(err) => Ok(IbcReceiveResponse::new()
+      .set_ack(to_json_binary(&PacketAck::Error(err.to_string()))?)
+      .add_attribute("method", "ibc_packet_receive")
+      .add_attribute("error", err.to_string())
+    ),
+  }
+}
+
+/// Store a remote note under the sender's remote identity, which is qualified by the chain id
+/// approved for the channel. Remote notes do not trigger receive hooks or subscriber notifications.
+fn receive_note(deps: DepsMut, env: Env, channel: &str, packet: NotePacket) -> ContractResult<IbcReceiveResponse> {
+  if crate::migrations::is_pending(deps.storage) {
+    return Err(ContractError::MigrationPending {});
+  }
+  if is_paused(deps.storage, Operation::StoreNote) {
+    return Err(ContractError::Paused { operation: Operation::StoreNote });
+  }
+  let chain_id = load_channel_chain(deps.storage, channel.to_string())?
+    .ok_or_else(|| ContractError::UnknownChannel { channel: channel.to_string() })?;
+  if packet.chain_id != chain_id {
+    return Err(ContractError::InvalidRemoteSender {});
+  }
+  let recipient = deps.api.addr_validate(packet.recipient.as_str())?;
+  let sender = remote_sender(chain_id.as_str(), channel, packet.sender.as_str())?;
+
+  let note = Note {
+    sender: sender.clone(),
+    note: packet.note.into_bytes(),
+    timestamp: env.block.time,
+    height: env.block.height,
+  };
+  let size = note.note.len() as u64;
+  let idx = store_note(deps.storage, sender.clone(), recipient.clone(), note)?;
+
+  let event = NoteStored {
+    sender,
+    recipient: recipient.clone(),
+    idx,
+    size,
+    key_id: load_enc_key(deps.storage, recipient.clone())?.map(|key| key_hash(&key)),
+    fee: None,
+    sponsor: None,
+  };
+  Ok(IbcReceiveResponse::new()
+    .set_ack(ack_result(&NotePacketResult { idx })?)
+    .add_event(event.into())
+    .add_attribute("method", "ibc_packet_receive")
+    .add_attribute("recipient", recipient)
+  )
+}
+
+fn receive_key_lookup(deps: DepsMut, env: Env, request: KeyLookupRequest) -> ContractResult<IbcReceiveResponse> {
+  let address = deps.api.addr_validate(request.address.as_str())?;
+  let key = load_enc_key(deps.storage, address.clone())?;
+
+  let response = KeyLookupResponse { chain_id: env.block.chain_id, address: address.to_string(), key };
+  Ok(IbcReceiveResponse::new()
+    .set_ack(ack_result(&response)?)
+    .add_attribute("method", "ibc_packet_receive")
+    .add_attribute("address", address)
+  )
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn ibc_packet_ack(deps: DepsMut, env: Env, msg: IbcPacketAckMsg) -> ContractResult<IbcBasicResponse> {
+  // an undecodable acknowledgement is settled as a failure, such that escrowed fees are refunded
+  let result = match from_json(&msg.acknowledgement.data) {
+    Ok(PacketAck::Result(result)) => Ok(result),
+    Ok(PacketAck::Error(error)) => Err(error),
+    Err(err) => Err(format!("invalid acknowledgement: {err}")),
+  };
+  settle_packet(deps, env, &msg.original_packet, result)
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn ibc_packet_timeout(deps: DepsMut, env: Env, msg: IbcPacketTimeoutMsg) -> ContractResult<IbcBasicResponse> {
+  settle_packet(deps, env, &msg.packet, Err("timeout".to_string()))
+}
+
+fn settle_packet(deps: DepsMut, env: Env, packet: &IbcPacket, result: Result<Binary, String>) -> ContractResult<IbcBasicResponse> {
+  let channel = packet.src.channel_id.clone();
+  match from_json(&packet.data)? {
+    PacketMsg::StoreNote(note) => settle_note(deps, env, channel, note, result.err()),
+    PacketMsg::KeyLookup(request) => settle_key_lookup(deps, env, channel, request, result),
+  }
+}
+
+/// Release the escrowed fee of a delivered note, or refund it to the payer's prepaid credits.
+fn settle_note(deps: DepsMut, env: Env, channel: String, note: NotePacket, error: Option<String>) -> ContractResult<IbcBasicResponse> {
+  let mut response = IbcBasicResponse::new();
+  let mut refund = None;
+  match (take_escrow(deps.storage, channel.clone(), note.id)?, &error) {
+    (Some(escrow), None) => {
+      let fees = load_fees(deps.storage, env.block.time)?;
+      response = response.add_messages(distribute_fee(deps.storage, &fees, &escrow.fee)?);
+    }
+    (Some(escrow), Some(_)) => {
+      refund_escrow(deps, &escrow)?;
+      refund = Some(escrow.fee);
+    }
+    (None, _) => {}
+  }
+
+  let event = RemoteNoteSettled {
+    channel,
+    packet_id: note.id,
+    sender: note.sender,
+    recipient: note.recipient,
+    error,
+    refund,
+  };
+  Ok(response
+    .add_event(event.into())
+    .add_attribute("method", "ibc_packet_settle")
+  )
+}
+
+/// Cache the result of a key lookup, keyed by the chain id approved for the channel. Responses for
+/// another chain or address are treated as failed lookups.
+fn settle_key_lookup(
+  deps: DepsMut,
+  env: Env,
+  channel: String,
+  request: KeyLookupRequest,
+  result: Result<Binary, String>,
+) -> ContractResult<IbcBasicResponse> {
+  let chain_id = load_channel_chain(deps.storage, channel.clone())?;
+  let response = result.and_then(|response| {
+    let response = from_json::<KeyLookupResponse>(&response).map_err(|err| err.to_string())?;
+    if Some(&response.chain_id) != chain_id.as_ref() || response.address != request.address {
+      return Err("mismatched key lookup response".to_string());
+    }
+    Ok(response)
+  });
+  let response = match response {
+    Ok(response) => response,
+    Err(error) => {
+      let event = RemoteKeyLookedUp { channel, address: request.address, chain_id: None, key_hash: None, error: Some(error) };
+      return Ok(IbcBasicResponse::new()
+        .add_event(event.into())
+        .add_attribute("method", "ibc_packet_settle")
+      );
+    }
+  };
+
+  let cached = RemoteKey { key: response.key, channel: channel.clone(), fetched_at: env.block.time };
+  save_remote_key(deps.storage, response.chain_id.clone(), request.address.clone(), &cached)?;
+
+  let event = RemoteKeyLookedUp {
+    channel,
+    address: request.address,
+    chain_id: Some(response.chain_id),
+    key_hash: cached.key.as_deref().map(key_hash),
+    error: None,
+  };
+  Ok(IbcBasicResponse::new()
+    .add_event(event.into())
+    .add_attribute("method", "ibc_packet_settle")
+  )
+}
+
+/// Credit an escrowed fee back to its payer. Refunded sponsors also regain the sender's allowance.
+fn refund_escrow(deps: DepsMut, escrow: &Escrow) -> ContractResult<()> {
+  let Some(sponsor) = &escrow.sponsor else {
+    return add_credits(deps.storage, escrow.sender.clone(), &escrow.fee);
+  };
+
+  add_credits(deps.storage, sponsor.clone(), &escrow.fee)?;
+  if let Some(spent) = load_sponsorship(deps.storage, escrow.sender.clone(), sponsor.clone())? {
+    save_sponsorship(deps.storage, escrow.sender.clone(), sponsor.clone(), spent.saturating_sub(escrow.fee.amount))?;
+  }
+  Ok(())
+}
+
+fn ack_result(result: &impl serde::Serialize) -> StdResult<Binary> {
+  to_json_binary(&PacketAck::Result(to_json_binary(result)?))
+}
+
+/// Identity of a sender on another chain, in the form `{chain_id}/{channel}/{address}` where the
+/// channel is the local end of the channel the note arrived through. Bech32 addresses never
+/// contain slashes, such that remote identities cannot collide with local addresses.
+pub fn remote_sender(chain_id: &str, channel: &str, address: &str) -> ContractResult<Addr> {
+  if [chain_id, channel, address].iter().any(|part| part.is_empty() || part.contains('/')) {
+    return Err(ContractError::InvalidRemoteSender {});
+  }
+  Ok(Addr::unchecked(format!("{chain_id}/{channel}/{address}")))
+}
+
+pub fn is_remote_sender(sender: &str) -> bool {
+  let parts: Vec<_> = sender.split('/').collect();
+  parts.len() == 3 && parts.iter().all(|part| !part.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use cosmwasm_std::testing::{
+    mock_dependencies, mock_env, mock_ibc_channel_close_confirm, mock_ibc_channel_connect_ack, mock_ibc_channel_open_init,
+    mock_ibc_channel_open_try, mock_ibc_packet_ack, mock_ibc_packet_recv, mock_ibc_packet_timeout, mock_info,
+  };
+  use cosmwasm_std::{coins, BankMsg, CosmosMsg, IbcAcknowledgement, IbcMsg, Uint128};
+
+  use crate::contract::{execute, instantiate, query};
+  use crate::msg::{
+    ChannelsResponse, CounterpartiesResponse, CounterpartyInfo, CreditsResponse, ExecuteMsg, InstantiateMsg, NotesResponse,
+    QueryMsg, RemoteEncryptionKeyResponse,
+  };
+
+  #[test]
+  fn channel_handshake() {
+    let mut deps = mock_dependencies();
+    instantiate_default(deps.as_mut());
+
+    let msg = mock_ibc_channel_open_init("channel-0", IbcOrder::Ordered, IBC_VERSION);
+    ibc_channel_open(deps.as_mut(), mock_env(), msg).unwrap_err();
+    let msg = mock_ibc_channel_open_try("channel-0", IbcOrder::Unordered, "ics20-1");
+    ibc_channel_open(deps.as_mut(), mock_env(), msg).unwrap_err();
+
+    // only channels to counterparts approved by the admin are accepted
+    let msg = mock_ibc_channel_open_init("channel-0", IbcOrder::Unordered, IBC_VERSION);
+    let err = ibc_channel_open(deps.as_mut(), mock_env(), msg.clone()).unwrap_err();
+    assert!(matches!(err, ContractError::UnknownCounterparty { .. }));
+    let allow = ExecuteMsg::AllowCounterparty {
+      connection_id: "connection-2".to_string(),
+      port_id: "other_port".to_string(),
+      chain_id: "phoenix-1".to_string(),
+    };
+    let err = execute(deps.as_mut(), mock_env(), mock_info("alice", &[]), allow.clone()).unwrap_err();
+    assert!(matches!(err, ContractError::Unauthorized { .. }));
+    let invalid = ExecuteMsg::AllowCounterparty {
+      connection_id: "connection-2".to_string(),
+      port_id: "their_port".to_string(),
+      chain_id: "phoenix-1/channel-0".to_string(),
+    };
+    let err = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), invalid).unwrap_err();
+    assert!(matches!(err, ContractError::InvalidChainId {}));
+    execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), allow).unwrap();
+    let err = ibc_channel_open(deps.as_mut(), mock_env(), msg.clone()).unwrap_err();
+    assert!(matches!(err, ContractError::UnknownCounterparty { .. }));
+    allow_counterparty(deps.as_mut());
+    let res = ibc_channel_open(deps.as_mut(), mock_env(), msg).unwrap();
+    assert_eq!(res, Some(Ibc3ChannelOpenResponse { version: IBC_VERSION.to_string() }));
+    let res: CounterpartiesResponse = from_json(query(deps.as_ref(), mock_env(), QueryMsg::Counterparties {}).unwrap()).unwrap();
+    assert_eq!(res.counterparties, vec![CounterpartyInfo {
+      connection_id: "connection-2".to_string(),
+      port_id: "their_port".to_string(),
+      chain_id: "phoenix-1".to_string(),
+    }]);
+
+    connect(deps.as_mut(), "channel-0");
+    let res: ChannelsResponse = from_json(query(deps.as_ref(), mock_env(), QueryMsg::Channels {}).unwrap()).unwrap();
+    assert_eq!(res.channels.len(), 1);
+    assert_eq!(res.channels[0].channel_id, "channel-0");
+    assert_eq!(res.channels[0].counterparty_channel, "channel-7");
+    assert_eq!(res.channels[0].chain_id, "phoenix-1");
+
+    let msg = mock_ibc_channel_close_confirm("channel-0", IbcOrder::Unordered, IBC_VERSION);
+    ibc_channel_close(deps.as_mut(), mock_env(), msg).unwrap();
+    let res: ChannelsResponse = from_json(query(deps.as_ref(), mock_env(), QueryMsg::Channels {}).unwrap()).unwrap();
+    assert!(res.channels.is_empty());
+
+    let remove = ExecuteMsg::RemoveCounterparty { connection_id: "connection-2".to_string() };
+    execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), remove).unwrap();
+    let msg = mock_ibc_channel_connect_ack("channel-1", IbcOrder::Unordered, IBC_VERSION);
+    let err = ibc_channel_connect(deps.as_mut(), mock_env(), msg).unwrap_err();
+    assert!(matches!(err, ContractError::UnknownCounterparty { .. }));
+  }
+
+  #[test]
+  fn send_remote_notes() {
+    let mut deps = mock_dependencies();
+    instantiate_default(deps.as_mut());
+    let info = mock_info("alice", &coins(500000, "luna"));
+
+    let err = execute(deps.as_mut(), mock_env(), info.clone(), remote_note_msg("channel-0")).unwrap_err();
+    assert!(matches!(err, ContractError::UnknownChannel { .. }));
+    connect(deps.as_mut(), "channel-0");
+
+    // the fee is escrowed until the note is delivered
+    let res = execute(deps.as_mut(), mock_env(), info.clone(), remote_note_msg("channel-0")).unwrap();
+    assert_eq!(res.messages.len(), 1);
+    let CosmosMsg::Ibc(IbcMsg::SendPacket { channel_id, data, .. }) = &res.messages[0].msg else {
+      panic!("expected packet");
+    };
+    assert_eq!(channel_id, "channel-0");
+    let delivered: PacketMsg = from_json(data).unwrap();
+    let PacketMsg::StoreNote(packet) = &delivered else {
+      panic!("expected note packet");
+    };
+    assert_eq!(packet.chain_id, mock_env().block.chain_id);
+    assert_eq!(packet.sender, "alice");
+    assert_eq!(packet.recipient, "terra1bob");
+
+    let ack = IbcAcknowledgement::new(ack_result(&NotePacketResult { idx: 0 }).unwrap());
+    let msg = mock_ibc_packet_ack("channel-0", &delivered, ack.clone()).unwrap();
+    let res = ibc_packet_ack(deps.as_mut(), mock_env(), msg).unwrap();
+    assert_eq!(res.messages.len(), 1);
+    assert_eq!(res.messages[0].msg, CosmosMsg::Bank(BankMsg::Send {
+      to_address: "creator".to_string(),
+      amount: coins(500000, "luna"),
+    }));
+
+    // acknowledging again does not release the fee twice
+    let msg = mock_ibc_packet_ack("channel-0", &delivered, ack).unwrap();
+    let res = ibc_packet_ack(deps.as_mut(), mock_env(), msg).unwrap();
+    assert!(res.messages.is_empty());
+
+    // failed & timed out notes are refunded to credits
+    let res = execute(deps.as_mut(), mock_env(), info.clone(), remote_note_msg("channel-0")).unwrap();
+    let CosmosMsg::Ibc(IbcMsg::SendPacket { data, .. }) = &res.messages[0].msg else {
+      panic!("expected packet");
+    };
+    let failed: PacketMsg = from_json(data).unwrap();
+    let ack = IbcAcknowledgement::new(to_json_binary(&PacketAck::Error("invalid recipient".to_string())).unwrap());
+    let msg = mock_ibc_packet_ack("channel-0", &failed, ack).unwrap();
+    let res = ibc_packet_ack(deps.as_mut(), mock_env(), msg).unwrap();
+    assert!(res.messages.is_empty());
+
+    let res = execute(deps.as_mut(), mock_env(), info.clone(), remote_note_msg("channel-0")).unwrap();
+    let CosmosMsg::Ibc(IbcMsg::SendPacket { data, .. }) = &res.messages[0].msg else {
+      panic!("expected packet");
+    };
+    let timed_out: PacketMsg = from_json(data).unwrap();
+    let msg = mock_ibc_packet_timeout("channel-0", &timed_out).unwrap();
+    ibc_packet_timeout(deps.as_mut(), mock_env(), msg).unwrap();
+
+    // undecodable acknowledgements count as failures
+    let res = execute(deps.as_mut(), mock_env(), info, remote_note_msg("channel-0")).unwrap();
+    let CosmosMsg::Ibc(IbcMsg::SendPacket { data, .. }) = &res.messages[0].msg else {
+      panic!("expected packet");
+    };
+    let malformed: PacketMsg = from_json(data).unwrap();
+    let msg = mock_ibc_packet_ack("channel-0", &malformed, IbcAcknowledgement::new(b"garbage")).unwrap();
+    let res = ibc_packet_ack(deps.as_mut(), mock_env(), msg).unwrap();
+    assert_eq!(res.events[0].ty, crate::events::REMOTE_NOTE_FAILED);
+
+    let msg = QueryMsg::Credits { address: "alice".to_string() };
+    let res: CreditsResponse = from_json(query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap();
+    assert_eq!(res.credits, coins(1500000, "luna"));
+  }
+
+  #[test]
+  fn receive_remote_notes() {
+    let mut deps = mock_dependencies();
+    instantiate_default(deps.as_mut());
+    connect(deps.as_mut(), "channel-0");
+
+    let packet = PacketMsg::StoreNote(NotePacket {
+      id: 3,
+      chain_id: "phoenix-1".to_string(),
+      sender: "terra1alice".to_string(),
+      recipient: "bob".to_string(),
+      note: "foobar".to_string(),
+    });
+    let msg = mock_ibc_packet_recv("channel-0", &packet).unwrap();
+    let res = ibc_packet_receive(deps.as_mut(), mock_env(), msg).unwrap();
+    let ack: PacketAck = from_json(&res.acknowledgement).unwrap();
+    assert_eq!(ack, PacketAck::Result(to_json_binary(&NotePacketResult { idx: 0 }).unwrap()));
+
+    let msg = QueryMsg::Notes {
+      recipient: "bob".to_string(),
+      sender: "phoenix-1/channel-0/terra1alice".to_string(),
+      start_after: None,
+      limit: None,
+      range: None,
+    };
+    let res: NotesResponse = from_json(query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap();
+    assert_eq!(res.notes.len(), 1);
+    assert_eq!(res.notes[0].sender, Addr::unchecked("phoenix-1/channel-0/terra1alice"));
+    assert_eq!(res.notes[0].note, b"foobar".to_vec());
+
+    // invalid packets are rejected through error acknowledgements
+    let packet = PacketMsg::StoreNote(NotePacket {
+      id: 4,
+      chain_id: "phoenix-1".to_string(),
+      sender: "terra1alice/evil".to_string(),
+      recipient: "bob".to_string(),
+      note: "foobar".to_string(),
+    });
+    let msg = mock_ibc_packet_recv("channel-0", &packet).unwrap();
+    let res = ibc_packet_receive(deps.as_mut(), mock_env(), msg).unwrap();
+    let ack: PacketAck = from_json(&res.acknowledgement).unwrap();
+    assert!(matches!(ack, PacketAck::Error(_)));
+
+    // senders cannot claim to be on another chain than the one approved for the channel
+    let packet = PacketMsg::StoreNote(NotePacket {
+      id: 4,
+      chain_id: "columbus-5".to_string(),
+      sender: "terra1alice".to_string(),
+      recipient: "bob".to_string(),
+      note: "foobar".to_string(),
+    });
+    let msg = mock_ibc_packet_recv("channel-0", &packet).unwrap();
+    let res = ibc_packet_receive(deps.as_mut(), mock_env(), msg).unwrap();
+    let ack: PacketAck = from_json(&res.acknowledgement).unwrap();
+    assert!(matches!(ack, PacketAck::Error(_)));
+
+    let pause = ExecuteMsg::Pause { operations: Some(vec![Operation::StoreNote]) };
+    execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), pause).unwrap();
+    let packet = PacketMsg::StoreNote(NotePacket {
+      id: 5,
+      chain_id: "phoenix-1".to_string(),
+      sender: "terra1alice".to_string(),
+      recipient: "bob".to_string(),
+      note: "foobar".to_string(),
+    });
+    let msg = mock_ibc_packet_recv("channel-0", &packet).unwrap();
+    let res = ibc_packet_receive(deps.as_mut(), mock_env(), msg).unwrap();
+    let ack: PacketAck = from_json(&res.acknowledgement).unwrap();
+    assert!(matches!(ack, PacketAck::Error(_)));
+  }
+
+  #[test]
+  fn remote_key_lookup() {
+    let mut deps = mock_dependencies();
+    instantiate_default(deps.as_mut());
+
+    let lookup = ExecuteMsg::LookupRemoteKey { channel: "channel-0".to_string(), address: "terra1bob".to_string() };
+    let err = execute(deps.as_mut(), mock_env(), mock_info("alice", &[]), lookup.clone()).unwrap_err();
+    assert!(matches!(err, ContractError::UnknownChannel { .. }));
+    connect(deps.as_mut(), "channel-0");
+
+    // lookups are charged the store keys fee
+    execute(deps.as_mut(), mock_env(), mock_info("alice", &[]), lookup.clone()).expect_err("Unexpected success");
+    let fee = coins(1000000, "luna");
+    let res = execute(deps.as_mut(), mock_env(), mock_info("alice", &fee), lookup.clone()).unwrap();
+    let CosmosMsg::Ibc(IbcMsg::SendPacket { data, .. }) = &res.messages[0].msg else {
+      panic!("expected packet");
+    };
+    let request: PacketMsg = from_json(data).unwrap();
+    assert_eq!(request, PacketMsg::KeyLookup(KeyLookupRequest { address: "terra1bob".to_string() }));
+    assert_eq!(res.messages[1].msg, CosmosMsg::Bank(BankMsg::Send { to_address: "creator".to_string(), amount: fee.clone() }));
+
+    // the counterpart answers with the key registered on its chain
+    let update_key = ExecuteMsg::UpdateKey { key: "bobkey".to_string() };
+    execute(deps.as_mut(), mock_env(), mock_info("bob", &coins(1000000, "luna")), update_key).unwrap();
+    let msg = mock_ibc_packet_recv("channel-0", &PacketMsg::KeyLookup(KeyLookupRequest { address: "bob".to_string() })).unwrap();
+    let res = ibc_packet_receive(deps.as_mut(), mock_env(), msg).unwrap();
+    let PacketAck::Result(result) = from_json(&res.acknowledgement).unwrap() else {
+      panic!("expected result");
+    };
+    let response: KeyLookupResponse = from_json(&result).unwrap();
+    assert_eq!(response.chain_id, mock_env().block.chain_id);
+    assert_eq!(response.key, Some(b"bobkey".to_vec()));
+
+    // responses for another chain or address than requested are not cached
+    for (chain_id, address) in [("columbus-5", "terra1bob"), ("phoenix-1", "terra1eve")] {
+      let response = KeyLookupResponse {
+        chain_id: chain_id.to_string(),
+        address: address.to_string(),
+        key: Some(b"evilkey".to_vec()),
+      };
+      let ack = IbcAcknowledgement::new(ack_result(&response).unwrap());
+      let msg = mock_ibc_packet_ack("channel-0", &request, ack).unwrap();
+      let res = ibc_packet_ack(deps.as_mut(), mock_env(), msg).unwrap();
+      assert_eq!(res.events[0].ty, crate::events::REMOTE_KEY_LOOKUP_FAILED);
+    }
+
+    let response = KeyLookupResponse {
+      chain_id: "phoenix-1".to_string(),
+      address: "terra1bob".to_string(),
+      key: Some(b"bobkey".to_vec()),
+    };
+    let ack = IbcAcknowledgement::new(ack_result(&response).unwrap());
+    let msg = mock_ibc_packet_ack("channel-0", &request, ack).unwrap();
+    ibc_packet_ack(deps.as_mut(), mock_env(), msg).unwrap();
+
+    let query_key = |deps: cosmwasm_std::Deps, env: Env| -> RemoteEncryptionKeyResponse {
+      let msg = QueryMsg::RemoteEncryptionKey { chain: "phoenix-1".to_string(), address: "terra1bob".to_string() };
+      from_json(query(deps, env, msg).unwrap()).unwrap()
+    };
+    let res = query_key(deps.as_ref(), mock_env());
+    assert_eq!(res.key, Some(b"bobkey".to_vec()));
+    assert_eq!(res.channel, Some("channel-0".to_string()));
+    assert_eq!(res.expires, Some(mock_env().block.time.plus_seconds(REMOTE_KEY_TTL)));
+
+    // cached keys are neither looked up again nor charged for
+    let res = execute(deps.as_mut(), mock_env(), mock_info("alice", &[]), lookup.clone()).unwrap();
+    assert!(res.messages.is_empty());
+    assert!(res.attributes.iter().any(|attr| attr.key == "cached"));
+    let err = execute(deps.as_mut(), mock_env(), mock_info("alice", &fee), lookup.clone()).unwrap_err();
+    assert!(matches!(err, ContractError::UnexpectedFunds {}));
+
+    // cached keys expire
+    let mut env = mock_env();
+    env.block.time = env.block.time.plus_seconds(REMOTE_KEY_TTL);
+    assert_eq!(query_key(deps.as_ref(), env.clone()), RemoteEncryptionKeyResponse { key: None, channel: None, expires: None });
+
+    // failed lookups are not cached
+    let res = execute(deps.as_mut(), env, mock_info("alice", &fee), lookup).unwrap();
+    let CosmosMsg::Ibc(IbcMsg::SendPacket { data, .. }) = &res.messages[0].msg else {
+      panic!("expected packet");
+    };
+    let request: PacketMsg = from_json(data).unwrap();
+    let msg = mock_ibc_packet_timeout("channel-0", &request).unwrap();
+    let res = ibc_packet_timeout(deps.as_mut(), mock_env(), msg).unwrap();
+    assert_eq!(res.events[0].ty, crate::events::REMOTE_KEY_LOOKUP_FAILED);
+  }
+
+  fn allow_counterparty(deps: DepsMut) {
+    let msg = ExecuteMsg::AllowCounterparty {
+      connection_id: "connection-2".to_string(),
+      port_id: "their_port".to_string(),
+      chain_id: "phoenix-1".to_string(),
+    };
+    execute(deps, mock_env(), mock_info("creator", &[]), msg).unwrap();
+  }
+
+  fn connect(mut deps: DepsMut, channel_id: &str) {
+    allow_counterparty(deps.branch());
+    let msg = mock_ibc_channel_connect_ack(channel_id, IbcOrder::Unordered, IBC_VERSION);
+    ibc_channel_connect(deps, mock_env(), msg).unwrap();
+  }
+
+  fn remote_note_msg(channel: &str) -> ExecuteMsg {
+    ExecuteMsg::StoreRemoteNote {
+      channel: channel.to_string(),
+      recipient: "terra1bob".to_string(),
+      note: "foobar".to_string(),
+    }
+  }
+
+  fn instantiate_default(deps: DepsMut) {
+    let msg = InstantiateMsg {
+      admin: None,
+      denom: "luna".to_string(),
+      store_keys_fee: Uint128::new(1000000),
+      store_notes_fee: Uint128::new(500000),
+    };
+    instantiate(deps, mock_env(), mock_info("creator", &[]), msg).unwrap();
+  }
+}
@@ -0,0 +1,555 @@
+use cosmwasm_std::{coins, to_json_binary, Addr, Coin, CosmosMsg, Empty, Event, Uint128, WasmMsg};
+use cw3::Vote;
+use cw_multi_test::{App, Contract, ContractWrapper, Executor};
+use cw_utils::{Duration, Threshold};
+use cw3_fixed_multisig::msg::{ExecuteMsg as MultisigExecuteMsg, InstantiateMsg as MultisigInstantiateMsg, Voter};
+
+use crate::ContractError;
+use crate::msg::{
+  DropnoteReceiveMsg, ExecuteMsg, FeesMsg, FeesResponse, InstantiateMsg, NoteStoredHook, PausedResponse, QueryMsg,
+  SubscribersResponse, SudoMsg,
+};
+use crate::state::{HookFailure, Operation, Ownership, ReceiveHook, Role};
+
+fn dropnote_contract() -> Box<dyn Contract<Empty>> {
+  Box::new(ContractWrapper::new(
+    crate::contract::execute,
+    crate::contract::instantiate,
+    crate::contract::query,
+  ).with_sudo(crate::contract::sudo).with_reply(crate::contract::reply))
+}
+
+fn multisig_contract() -> Box<dyn Contract<Empty>> {
+  Box::new(ContractWrapper::new(
+    cw3_fixed_multisig::contract::execute,
+    cw3_fixed_multisig::contract::instantiate,
+    cw3_fixed_multisig::contract::query,
+  ))
+}
+
+/// Instantiate a 2-of-3 multisig of alice, bob & carol.
+fn instantiate_multisig(app: &mut App) -> Addr {
+  let code_id = app.store_code(multisig_contract());
+  let msg = MultisigInstantiateMsg {
+    voters: ["alice", "bob", "carol"].iter().map(|addr| Voter {
+      addr: addr.to_string(),
+      weight: 1,
+    }).collect(),
+    threshold: Threshold::AbsoluteCount { weight: 2 },
+    max_voting_period: Duration::Time(3600),
+  };
+  app.instantiate_contract(code_id, Addr::unchecked("creator"), &msg, &[], "multisig", None).unwrap()
+}
+
+fn instantiate_dropnote(app: &mut App, admin: Option<&Addr>) -> Addr {
+  let code_id = app.store_code(dropnote_contract());
+  let msg = InstantiateMsg {
+    admin: admin.map(|admin| admin.to_string()),
+    denom: "luna".to_string(),
+    store_keys_fee: Uint128::new(1000000),
+    store_notes_fee: Uint128::new(500000),
+  };
+  app.instantiate_contract(code_id, Addr::unchecked("creator"), &msg, &[], "dropnote", None).unwrap()
+}
+
+/// Propose `msg` to the multisig as alice, approve it as bob and execute it.
+fn pass_proposal(app: &mut App, multisig: &Addr, dropnote: &Addr, msg: &ExecuteMsg) -> u64 {
+  let proposal = MultisigExecuteMsg::Propose {
+    title: "dropnote".to_string(),
+    description: "configure dropnote".to_string(),
+    msgs: vec![CosmosMsg::Wasm(WasmMsg::Execute {
+      contract_addr: dropnote.to_string(),
+      msg: to_json_binary(msg).unwrap(),
+      funds: vec![],
+    })],
+    latest: None,
+  };
+  let res = app.execute_contract(Addr::unchecked("alice"), multisig.clone(), &proposal, &[]).unwrap();
+  let proposal_id = res.events.iter()
+    .flat_map(|event| event.attributes.iter())
+    .find(|attr| attr.key == "proposal_id")
+    .map(|attr| attr.value.parse::<u64>().unwrap())
+    .unwrap();
+
+  let vote = MultisigExecuteMsg::Vote { proposal_id, vote: Vote::Yes };
+  app.execute_contract(Addr::unchecked("bob"), multisig.clone(), &vote, &[]).unwrap();
+
+  let execute = MultisigExecuteMsg::Execute { proposal_id };
+  app.execute_contract(Addr::unchecked("carol"), multisig.clone(), &execute, &[]).unwrap();
+  proposal_id
+}
+
+fn update_fees_msg(store_notes: u128) -> ExecuteMsg {
+  ExecuteMsg::UpdateFees {
+    store_keys: Uint128::new(1000000),
+    store_notes: Uint128::new(store_notes),
+    denom: "luna".to_string(),
+    burn_fees: true,
+    beneficiaries: vec![],
+    accumulate: false,
+  }
+}
+
+fn query_fees(app: &App, dropnote: &Addr) -> FeesResponse {
+  app.wrap().query_wasm_smart(dropnote, &QueryMsg::Fees {}).unwrap()
+}
+
+#[test]
+fn dao_updates_fees() {
+  let mut app = App::default();
+  let multisig = instantiate_multisig(&mut app);
+  let dropnote = instantiate_dropnote(&mut app, Some(&multisig));
+
+  let ownership: Ownership = app.wrap().query_wasm_smart(&dropnote, &QueryMsg::Ownership {}).unwrap();
+  assert_eq!(ownership.owner, Some(multisig.clone()));
+
+  // members cannot bypass the DAO, not even the creator
+  for sender in ["alice", "creator"] {
+    let err = app.execute_contract(Addr::unchecked(sender), dropnote.clone(), &update_fees_msg(1), &[]).unwrap_err();
+    assert!(matches!(err.downcast().unwrap(), ContractError::Unauthorized { role: Role::FeeManager }));
+  }
+
+  pass_proposal(&mut app, &multisig, &dropnote, &update_fees_msg(1));
+
+  let res = query_fees(&app, &dropnote);
+  assert_eq!(res.fees.store_notes, Uint128::new(1));
+  assert!(res.fees.burn_fees);
+}
+
+#[test]
+fn dao_accepts_admin_transfer() {
+  let mut app = App::default();
+  let multisig = instantiate_multisig(&mut app);
+  let dropnote = instantiate_dropnote(&mut app, None);
+
+  let msg = ExecuteMsg::ProposeAdmin {
+    address: multisig.to_string(),
+    expiry: None,
+  };
+  app.execute_contract(Addr::unchecked("creator"), dropnote.clone(), &msg, &[]).unwrap();
+
+  // members cannot accept on behalf of the DAO
+  let err = app.execute_contract(Addr::unchecked("alice"), dropnote.clone(), &ExecuteMsg::AcceptAdmin {}, &[]).unwrap_err();
+  assert!(matches!(err.downcast().unwrap(), ContractError::NotPendingAdmin {}));
+
+  pass_proposal(&mut app, &multisig, &dropnote, &ExecuteMsg::AcceptAdmin {});
+
+  let ownership: Ownership = app.wrap().query_wasm_smart(&dropnote, &QueryMsg::Ownership {}).unwrap();
+  assert_eq!(ownership.owner, Some(multisig.clone()));
+
+  // the previous admin lost its privileges
+  let err = app.execute_contract(Addr::unchecked("creator"), dropnote.clone(), &update_fees_msg(1), &[]).unwrap_err();
+  assert!(matches!(err.downcast().unwrap(), ContractError::Unauthorized { role: Role::FeeManager }));
+
+  pass_proposal(&mut app, &multisig, &dropnote, &update_fees_msg(1));
+  assert_eq!(query_fees(&app, &dropnote).fees.store_notes, Uint128::new(1));
+}
+
+fn sudo_event_action(res: &cw_multi_test::AppResponse) -> Option<String> {
+  res.events.iter()
+    .find(|event| event.ty == "wasm-sudo")
+    .and_then(|event| event.attributes.iter().find(|attr| attr.key == "action"))
+    .map(|attr| attr.value.clone())
+}
+
+#[test]
+fn sudo_set_admin() {
+  let mut app = App::default();
+  let dropnote = instantiate_dropnote(&mut app, None);
+
+  // pending transfers are discarded
+  let msg = ExecuteMsg::ProposeAdmin {
+    address: "mallory".to_string(),
+    expiry: None,
+  };
+  app.execute_contract(Addr::unchecked("creator"), dropnote.clone(), &msg, &[]).unwrap();
+
+  let res = app.wasm_sudo(dropnote.clone(), &SudoMsg::SetAdmin { address: Some("governance".to_string()) }).unwrap();
+  assert_eq!(sudo_event_action(&res), Some("set_admin".to_string()));
+  assert!(res.has_event(&Event::new("wasm-sudo").add_attribute("admin", "governance")));
+  assert!(res.events.iter().any(|event| event.ty == format!("wasm-{}", crate::events::ADMIN_CHANGED)));
+
+  let ownership: Ownership = app.wrap().query_wasm_smart(&dropnote, &QueryMsg::Ownership {}).unwrap();
+  assert_eq!(ownership.owner, Some(Addr::unchecked("governance")));
+  assert_eq!(ownership.pending_owner, None);
+
+  app.execute_contract(Addr::unchecked("mallory"), dropnote.clone(), &ExecuteMsg::AcceptAdmin {}, &[]).unwrap_err();
+  app.execute_contract(Addr::unchecked("creator"), dropnote.clone(), &update_fees_msg(1), &[]).unwrap_err();
+  app.execute_contract(Addr::unchecked("governance"), dropnote.clone(), &update_fees_msg(1), &[]).unwrap();
+
+  // even a renounced admin can be restored
+  app.execute_contract(Addr::unchecked("governance"), dropnote.clone(), &ExecuteMsg::RenounceAdmin {}, &[]).unwrap();
+  app.wasm_sudo(dropnote.clone(), &SudoMsg::SetAdmin { address: Some("creator".to_string()) }).unwrap();
+
+  let ownership: Ownership = app.wrap().query_wasm_smart(&dropnote, &QueryMsg::Ownership {}).unwrap();
+  assert_eq!(ownership.owner, Some(Addr::unchecked("creator")));
+}
+
+#[test]
+fn sudo_pause() {
+  let mut app = App::default();
+  let dropnote = instantiate_dropnote(&mut app, None);
+
+  let res = app.wasm_sudo(dropnote.clone(), &SudoMsg::Pause { operations: None }).unwrap();
+  assert_eq!(sudo_event_action(&res), Some("pause".to_string()));
+
+  let res: PausedResponse = app.wrap().query_wasm_smart(&dropnote, &QueryMsg::Paused {}).unwrap();
+  assert_eq!(res.operations, Operation::ALL.to_vec());
+
+  let msg = ExecuteMsg::UpdateKey { key: "foobar".to_string() };
+  let err = app.execute_contract(Addr::unchecked("alice"), dropnote.clone(), &msg, &[]).unwrap_err();
+  assert!(matches!(err.downcast().unwrap(), ContractError::Paused { operation: Operation::UpdateKey }));
+
+  let res = app.wasm_sudo(dropnote.clone(), &SudoMsg::Unpause { operations: Some(vec![Operation::UpdateKey]) }).unwrap();
+  assert_eq!(sudo_event_action(&res), Some("unpause".to_string()));
+
+  let res: PausedResponse = app.wrap().query_wasm_smart(&dropnote, &QueryMsg::Paused {}).unwrap();
+  assert!(!res.operations.contains(&Operation::UpdateKey));
+  assert!(res.operations.contains(&Operation::StoreNote));
+}
+
+#[test]
+fn sudo_set_fees() {
+  let mut app = App::default();
+  let dropnote = instantiate_dropnote(&mut app, None);
+
+  // a notice period and a pending update do not hold back governance
+  let msg = ExecuteMsg::ScheduleFeeUpdate {
+    fees: FeesMsg {
+      store_keys: Uint128::new(1000000),
+      store_notes: Uint128::new(2000000),
+      denom: "luna".to_string(),
+      burn_fees: false,
+      beneficiaries: vec![],
+      accumulate: false,
+      update_delay: 86400,
+    },
+    effective_at: app.block_info().time,
+  };
+  app.execute_contract(Addr::unchecked("creator"), dropnote.clone(), &msg, &[]).unwrap();
+  let msg = ExecuteMsg::ScheduleFeeUpdate {
+    fees: FeesMsg {
+      store_keys: Uint128::new(1000000),
+      store_notes: Uint128::new(3000000),
+      denom: "luna".to_string(),
+      burn_fees: false,
+      beneficiaries: vec![],
+      accumulate: false,
+      update_delay: 86400,
+    },
+    effective_at: app.block_info().time.plus_seconds(86400),
+  };
+  app.execute_contract(Addr::unchecked("creator"), dropnote.clone(), &msg, &[]).unwrap();
+
+  let msg = SudoMsg::SetFees {
+    fees: FeesMsg {
+      store_keys: Uint128::zero(),
+      store_notes: Uint128::zero(),
+      denom: "luna".to_string(),
+      burn_fees: false,
+      beneficiaries: vec![],
+      accumulate: false,
+      update_delay: 0,
+    },
+  };
+  let res = app.wasm_sudo(dropnote.clone(), &msg).unwrap();
+  assert_eq!(sudo_event_action(&res), Some("set_fees".to_string()));
+
+  let res = query_fees(&app, &dropnote);
+  assert_eq!(res.fees.store_notes, Uint128::zero());
+  assert!(res.scheduled.is_none());
+}
+
+/// A recipient or subscriber contract recording every note it is notified of.
+mod receiver {
+  use cosmwasm_schema::cw_serde;
+  use cosmwasm_std::{to_json_binary, Binary, Deps, DepsMut, Env, MessageInfo, Response, StdError, StdResult};
+  use cw_storage_plus::Item;
+
+  use crate::msg::{DropnoteReceiveMsg, NoteStoredHook};
+
+  const FAIL: Item<bool> = Item::new("fail");
+  const RECEIVED: Item<Vec<DropnoteReceiveMsg>> = Item::new("received");
+  const NOTIFIED: Item<Vec<NoteStoredHook>> = Item::new("notified");
+
+  #[cw_serde]
+  pub struct InstantiateMsg {
+    pub fail: bool,
+  }
+
+  #[cw_serde]
+  pub enum ExecuteMsg {
+    DropnoteReceive(DropnoteReceiveMsg),
+    NoteStoredHook(NoteStoredHook),
+    SetFail { fail: bool },
+  }
+
+  #[cw_serde]
+  pub enum QueryMsg {
+    Received {},
+    Notified {},
+  }
+
+  pub fn instantiate(deps: DepsMut, _env: Env, _info: MessageInfo, msg: InstantiateMsg) -> StdResult<Response> {
+    FAIL.save(deps.storage, &msg.fail)?;
+    RECEIVED.save(deps.storage, &vec![])?;
+    NOTIFIED.save(deps.storage, &vec![])?;
+    Ok(Response::new())
+  }
+
+  pub fn execute(deps: DepsMut, _env: Env, _info: MessageInfo, msg: ExecuteMsg) -> StdResult<Response> {
+    if let ExecuteMsg::SetFail { fail } = msg {
+      FAIL.save(deps.storage, &fail)?;
+      return Ok(Response::new());
+    }
+    if FAIL.load(deps.storage)? {
+      return Err(StdError::generic_err("hook failed"));
+    }
+    match msg {
+      ExecuteMsg::DropnoteReceive(msg) => push(RECEIVED, deps, msg)?,
+      ExecuteMsg::NoteStoredHook(msg) => push(NOTIFIED, deps, msg)?,
+      ExecuteMsg::SetFail { .. } => unreachable!(),
+    }
+    Ok(Response::new())
+  }
+
+  fn push<T: serde::Serialize + serde::de::DeserializeOwned>(item: Item<Vec<T>>, deps: DepsMut, msg: T) -> StdResult<()> {
+    let mut items = item.load(deps.storage)?;
+    items.push(msg);
+    item.save(deps.storage, &items)
+  }
+
+  pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<Binary> {
+    match msg {
+      QueryMsg::Received {} => to_json_binary(&RECEIVED.load(deps.storage)?),
+      QueryMsg::Notified {} => to_json_binary(&NOTIFIED.load(deps.storage)?),
+    }
+  }
+}
+
+fn instantiate_receiver(app: &mut App, fail: bool) -> Addr {
+  let code_id = app.store_code(Box::new(ContractWrapper::new(receiver::execute, receiver::instantiate, receiver::query)));
+  let msg = receiver::InstantiateMsg { fail };
+  app.instantiate_contract(code_id, Addr::unchecked("creator"), &msg, &[], "receiver", None).unwrap()
+}
+
+fn store_note_msg(recipient: &Addr) -> ExecuteMsg {
+  ExecuteMsg::StoreNote { recipient: recipient.to_string(), note: "foobar".to_string() }
+}
+
+fn note_count(app: &App, dropnote: &Addr, recipient: &Addr) -> u64 {
+  let msg = QueryMsg::NoteCounts {
+    pairs: vec![crate::msg::NotePair { recipient: recipient.to_string(), sender: "alice".to_string() }],
+  };
+  let res: crate::msg::NoteCountsResponse = app.wrap().query_wasm_smart(dropnote, &msg).unwrap();
+  res.counts[0].count.unwrap()
+}
+
+#[test]
+fn receive_hooks() {
+  let mut app = App::new(|router, _, storage| {
+    router.bank.init_balance(storage, &Addr::unchecked("alice"), coins(10000000, "luna")).unwrap();
+  });
+  let dropnote = instantiate_dropnote(&mut app, None);
+  let receiver = instantiate_receiver(&mut app, false);
+  let fee = coins(500000, "luna");
+
+  // only contracts can register hooks
+  let msg = ExecuteMsg::SetReceiveHook { hook: Some(ReceiveHook { on_error: HookFailure::Revert }) };
+  let err = app.execute_contract(Addr::unchecked("alice"), dropnote.clone(), &msg, &[]).unwrap_err();
+  assert!(matches!(err.downcast().unwrap(), ContractError::NotAContract {}));
+
+  // notes to contracts without hooks are stored silently
+  app.execute_contract(Addr::unchecked("alice"), dropnote.clone(), &store_note_msg(&receiver), &fee).unwrap();
+  let received: Vec<DropnoteReceiveMsg> = app.wrap().query_wasm_smart(&receiver, &receiver::QueryMsg::Received {}).unwrap();
+  assert!(received.is_empty());
+
+  app.execute_contract(receiver.clone(), dropnote.clone(), &msg, &[]).unwrap();
+  app.execute_contract(Addr::unchecked("alice"), dropnote.clone(), &store_note_msg(&receiver), &fee).unwrap();
+
+  let received: Vec<DropnoteReceiveMsg> = app.wrap().query_wasm_smart(&receiver, &receiver::QueryMsg::Received {}).unwrap();
+  assert_eq!(received.len(), 1);
+  assert_eq!(received[0].sender, "alice");
+  assert_eq!(received[0].idx, 1);
+  assert_eq!(received[0].note_ref.contract, dropnote.to_string());
+  assert_eq!(received[0].note_ref.recipient, receiver.to_string());
+
+  // unregistered hooks are no longer notified
+  let unset = ExecuteMsg::SetReceiveHook { hook: None };
+  app.execute_contract(receiver.clone(), dropnote.clone(), &unset, &[]).unwrap();
+  app.execute_contract(Addr::unchecked("alice"), dropnote.clone(), &store_note_msg(&receiver), &fee).unwrap();
+  let received: Vec<DropnoteReceiveMsg> = app.wrap().query_wasm_smart(&receiver, &receiver::QueryMsg::Received {}).unwrap();
+  assert_eq!(received.len(), 1);
+}
+
+#[test]
+fn failing_receive_hooks() {
+  let mut app = App::new(|router, _, storage| {
+    router.bank.init_balance(storage, &Addr::unchecked("alice"), coins(10000000, "luna")).unwrap();
+  });
+  let dropnote = instantiate_dropnote(&mut app, None);
+  let reverting = instantiate_receiver(&mut app, true);
+  let ignoring = instantiate_receiver(&mut app, true);
+  let fee = coins(500000, "luna");
+
+  let msg = ExecuteMsg::SetReceiveHook { hook: Some(ReceiveHook { on_error: HookFailure::Revert }) };
+  app.execute_contract(reverting.clone(), dropnote.clone(), &msg, &[]).unwrap();
+  let msg = ExecuteMsg::SetReceiveHook { hook: Some(ReceiveHook { on_error: HookFailure::Ignore }) };
+  app.execute_contract(ignoring.clone(), dropnote.clone(), &msg, &[]).unwrap();
+
+  app.execute_contract(Addr::unchecked("alice"), dropnote.clone(), &store_note_msg(&reverting), &fee).unwrap_err();
+  assert_eq!(note_count(&app, &dropnote, &reverting), 0);
+  assert_eq!(app.wrap().query_balance("alice", "luna").unwrap().amount, Uint128::new(10000000));
+
+  let res = app.execute_contract(Addr::unchecked("alice"), dropnote.clone(), &store_note_msg(&ignoring), &fee).unwrap();
+  assert!(res.events.iter().any(|event| event.ty == format!("wasm-{}", crate::events::RECEIVE_HOOK_FAILED)));
+  assert_eq!(note_count(&app, &dropnote, &ignoring), 1);
+}
+
+fn query_subscribers(app: &App, dropnote: &Addr) -> SubscribersResponse {
+  let msg = QueryMsg::Subscribers { start_after: None, limit: None };
+  app.wrap().query_wasm_smart(dropnote, &msg).unwrap()
+}
+
+fn query_notified(app: &App, subscriber: &Addr) -> Vec<NoteStoredHook> {
+  app.wrap().query_wasm_smart(subscriber, &receiver::QueryMsg::Notified {}).unwrap()
+}
+
+#[test]
+fn subscribers() {
+  let mut app = App::new(|router, _, storage| {
+    router.bank.init_balance(storage, &Addr::unchecked("alice"), coins(10000000, "luna")).unwrap();
+  });
+  let dropnote = instantiate_dropnote(&mut app, None);
+  let added = instantiate_receiver(&mut app, false);
+  let subscribed = instantiate_receiver(&mut app, false);
+  let moderator = Addr::unchecked("moderator");
+  let deposit = coins(1000, "luna");
+  app.send_tokens(Addr::unchecked("alice"), subscribed.clone(), &coins(2000, "luna")).unwrap();
+
+  let grant = ExecuteMsg::GrantRole { address: moderator.to_string(), role: Role::Moderator };
+  app.execute_contract(Addr::unchecked("creator"), dropnote.clone(), &grant, &[]).unwrap();
+
+  // only moderators add subscribers, which must be contracts with bounded gas limits
+  let msg = ExecuteMsg::AddSubscriber { address: added.to_string(), gas_limit: 200000 };
+  let err = app.execute_contract(Addr::unchecked("alice"), dropnote.clone(), &msg, &[]).unwrap_err();
+  assert!(matches!(err.downcast().unwrap(), ContractError::Unauthorized { role: Role::Moderator }));
+  let invalid = ExecuteMsg::AddSubscriber { address: "alice".to_string(), gas_limit: 200000 };
+  let err = app.execute_contract(moderator.clone(), dropnote.clone(), &invalid, &[]).unwrap_err();
+  assert!(matches!(err.downcast().unwrap(), ContractError::NotAContract {}));
+  let invalid = ExecuteMsg::AddSubscriber { address: added.to_string(), gas_limit: 100000000 };
+  let err = app.execute_contract(moderator.clone(), dropnote.clone(), &invalid, &[]).unwrap_err();
+  assert!(matches!(err.downcast().unwrap(), ContractError::GasLimitTooHigh { .. }));
+  app.execute_contract(moderator.clone(), dropnote.clone(), &msg, &[]).unwrap();
+
+  // self-registration requires the configured deposit
+  let subscribe = ExecuteMsg::Subscribe { gas_limit: 200000 };
+  let err = app.execute_contract(subscribed.clone(), dropnote.clone(), &subscribe, &deposit).unwrap_err();
+  assert!(matches!(err.downcast().unwrap(), ContractError::SubscriptionClosed {}));
+  let msg = ExecuteMsg::SetSubscriptionDeposit { deposit: Some(Coin::new(0, "luna")) };
+  let err = app.execute_contract(moderator.clone(), dropnote.clone(), &msg, &[]).unwrap_err();
+  assert!(matches!(err.downcast().unwrap(), ContractError::ZeroDeposit {}));
+  let msg = ExecuteMsg::SetSubscriptionDeposit { deposit: Some(deposit[0].clone()) };
+  app.execute_contract(moderator.clone(), dropnote.clone(), &msg, &[]).unwrap();
+  let err = app.execute_contract(subscribed.clone(), dropnote.clone(), &subscribe, &coins(999, "luna")).unwrap_err();
+  assert!(matches!(err.downcast().unwrap(), ContractError::InvalidDeposit { .. }));
+  app.execute_contract(subscribed.clone(), dropnote.clone(), &subscribe, &deposit).unwrap();
+  let err = app.execute_contract(subscribed.clone(), dropnote.clone(), &subscribe, &deposit).unwrap_err();
+  assert!(matches!(err.downcast().unwrap(), ContractError::AlreadySubscribed {}));
+
+  let res = query_subscribers(&app, &dropnote);
+  assert_eq!(res.subscribers.len(), 2);
+  let entry = res.subscribers.iter().find(|entry| entry.address == subscribed.as_str()).unwrap();
+  assert_eq!(entry.deposit, Some(deposit[0].clone()));
+  assert_eq!(entry.gas_limit, 200000);
+
+  let bob = Addr::unchecked("bob");
+  app.execute_contract(Addr::unchecked("alice"), dropnote.clone(), &store_note_msg(&bob), &coins(500000, "luna")).unwrap();
+  let hook = NoteStoredHook { recipient: "bob".to_string(), sender: "alice".to_string(), idx: 0 };
+  assert_eq!(query_notified(&app, &added), vec![hook.clone()]);
+  assert_eq!(query_notified(&app, &subscribed), vec![hook]);
+
+  // unsubscribing refunds the deposit
+  app.execute_contract(subscribed.clone(), dropnote.clone(), &ExecuteMsg::Unsubscribe {}, &[]).unwrap();
+  assert_eq!(app.wrap().query_balance(&subscribed, "luna").unwrap().amount, Uint128::new(2000));
+  let msg = ExecuteMsg::RemoveSubscriber { address: added.to_string() };
+  app.execute_contract(moderator.clone(), dropnote.clone(), &msg, &[]).unwrap();
+  assert!(query_subscribers(&app, &dropnote).subscribers.is_empty());
+
+  app.execute_contract(Addr::unchecked("alice"), dropnote.clone(), &store_note_msg(&bob), &coins(500000, "luna")).unwrap();
+  assert_eq!(query_notified(&app, &added).len(), 1);
+}
+
+#[test]
+fn failing_subscribers() {
+  let mut app = App::new(|router, _, storage| {
+    router.bank.init_balance(storage, &Addr::unchecked("alice"), coins(10000000, "luna")).unwrap();
+  });
+  let dropnote = instantiate_dropnote(&mut app, None);
+  let subscriber = instantiate_receiver(&mut app, true);
+  let fee = coins(500000, "luna");
+  app.send_tokens(Addr::unchecked("alice"), subscriber.clone(), &coins(1000, "luna")).unwrap();
+
+  let msg = ExecuteMsg::SetSubscriptionDeposit { deposit: Some(Coin::new(1000, "luna")) };
+  app.execute_contract(Addr::unchecked("creator"), dropnote.clone(), &msg, &[]).unwrap();
+  let msg = ExecuteMsg::Subscribe { gas_limit: 200000 };
+  app.execute_contract(subscriber.clone(), dropnote.clone(), &msg, &coins(1000, "luna")).unwrap();
+
+  // failures do not affect the note, and are reset by a successful notification
+  let bob = Addr::unchecked("bob");
+  for _ in 0..2 {
+    let res = app.execute_contract(Addr::unchecked("alice"), dropnote.clone(), &store_note_msg(&bob), &fee).unwrap();
+    assert!(res.events.iter().any(|event| event.ty == format!("wasm-{}", crate::events::SUBSCRIBER_FAILED)));
+  }
+  assert_eq!(note_count(&app, &dropnote, &bob), 2);
+  assert_eq!(query_subscribers(&app, &dropnote).subscribers[0].failures, 2);
+
+  let set_fail = |fail| receiver::ExecuteMsg::SetFail { fail };
+  app.execute_contract(Addr::unchecked("alice"), subscriber.clone(), &set_fail(false), &[]).unwrap();
+  app.execute_contract(Addr::unchecked("alice"), dropnote.clone(), &store_note_msg(&bob), &fee).unwrap();
+  assert_eq!(query_subscribers(&app, &dropnote).subscribers[0].failures, 0);
+  assert_eq!(query_notified(&app, &subscriber).len(), 1);
+
+  // repeatedly failing subscribers are removed and forfeit their deposit to the admin
+  app.execute_contract(Addr::unchecked("alice"), subscriber.clone(), &set_fail(true), &[]).unwrap();
+  for _ in 0..3 {
+    app.execute_contract(Addr::unchecked("alice"), dropnote.clone(), &store_note_msg(&bob), &fee).unwrap();
+  }
+  assert!(query_subscribers(&app, &dropnote).subscribers.is_empty());
+  assert_eq!(note_count(&app, &dropnote, &bob), 6);
+  assert_eq!(app.wrap().query_balance(&subscriber, "luna").unwrap().amount, Uint128::zero());
+  assert_eq!(app.wrap().query_balance("creator", "luna").unwrap().amount, Uint128::new(6 * 500000 + 1000));
+}
+
+#[test]
+fn subscriber_slots() {
+  let mut app = App::new(|router, _, storage| {
+    router.bank.init_balance(storage, &Addr::unchecked("alice"), coins(10000000, "luna")).unwrap();
+  });
+  let dropnote = instantiate_dropnote(&mut app, None);
+  let receivers = (0..6).map(|_| instantiate_receiver(&mut app, false)).collect::<Vec<_>>();
+  let deposit = coins(1000, "luna");
+  let msg = ExecuteMsg::SetSubscriptionDeposit { deposit: Some(deposit[0].clone()) };
+  app.execute_contract(Addr::unchecked("creator"), dropnote.clone(), &msg, &[]).unwrap();
+
+  // self-registration is limited to a share of the slots
+  let subscribe = ExecuteMsg::Subscribe { gas_limit: 200000 };
+  for receiver in &receivers[..3] {
+    app.send_tokens(Addr::unchecked("alice"), receiver.clone(), &deposit).unwrap();
+  }
+  for receiver in &receivers[..2] {
+    app.execute_contract(receiver.clone(), dropnote.clone(), &subscribe, &deposit).unwrap();
+  }
+  let err = app.execute_contract(receivers[2].clone(), dropnote.clone(), &subscribe, &deposit).unwrap_err();
+  assert!(matches!(err.downcast().unwrap(), ContractError::TooManySubscribers { max: 2 }));
+
+  // the remaining slots are reserved for moderators
+  for receiver in &receivers[2..5] {
+    let msg = ExecuteMsg::AddSubscriber { address: receiver.to_string(), gas_limit: 200000 };
+    app.execute_contract(Addr::unchecked("creator"), dropnote.clone(), &msg, &[]).unwrap();
+  }
+  let msg = ExecuteMsg::AddSubscriber { address: receivers[5].to_string(), gas_limit: 200000 };
+  let err = app.execute_contract(Addr::unchecked("creator"), dropnote.clone(), &msg, &[]).unwrap_err();
+  assert!(matches!(err.downcast().unwrap(), ContractError::TooManySubscribers { max: 5 }));
+  assert_eq!(query_subscribers(&app, &dropnote).subscribers.len(), 5);
+}
@@ -1,7 +1,13 @@
 pub mod contract;
 mod error;
+pub mod events;
 pub mod helpers;
+pub mod ibc;
+#[cfg(test)]
+mod integration_tests;
+mod migrations;
 pub mod msg;
+mod pagination;
 pub mod state;
 
 pub use crate::error::ContractError;
@@ -0,0 +1,187 @@
+use cosmwasm_std::{Addr, Binary, Empty, Order, Storage, Uint128};
+use cw_storage_plus::{Bound, Item, PrimaryKey};
+use schemars::JsonSchema;
+use semver::Version;
+use serde::{Deserialize, Serialize};
+
+use crate::ContractResult;
+use crate::state::{count_note, index_note, Fees, Ownership, CONVERSATIONS, ENCRYPTION_KEYS, FEES, NOTES, NOTE_META, OWNERSHIP, STATS};
+
+/// Run a step on at most `limit` entries following the cursor.
+type MigrationStep = fn(&mut dyn Storage, Option<Vec<u8>>, usize) -> ContractResult<Batch>;
+
+/// Ordered migration steps. Every step whose version is newer than the stored contract version
+/// is run in order, such that each step may rely on the layout produced by its predecessors.
+const MIGRATIONS: &[(&str, MigrationStep)] = &[
+  ("0.2.0", v0_2_0::split_ownership),
+  ("0.2.0", v0_2_0::index_conversations),
+  ("0.2.0", v0_2_0::index_notes),
+  ("0.2.0", v0_2_0::count_notes),
+  ("0.2.0", v0_2_0::count_keys),
+];
+
+// progress of a migration spanning multiple `migrate` calls
+const PROGRESS: Item<Progress> = Item::new("migration_progress");
+
+#[derive(Default, Serialize, Deserialize, JsonSchema)]
+struct Progress {
+  // index of the next step to run in `MIGRATIONS`.
+  step: usize,
+  // raw key of the last entry processed by that step, if any.
+  cursor: Option<Binary>,
+}
+
+struct Batch {
+  processed: usize,
+  // raw key of the last entry processed, if more entries may follow.
+  cursor: Option<Vec<u8>>,
+}
+
+impl Batch {
+  /// Conclude a step which processed the given keys, out of a page of at most `limit`.
+  fn of(keys: Vec<Vec<u8>>, limit: usize) -> Batch {
+    let processed = keys.len();
+    let cursor = if processed < limit { None } else { keys.into_iter().last() };
+    Batch { processed, cursor }
+  }
+}
+
+/// Run the pending migration steps on at most `limit` entries in total. Returns whether the
+/// migration is complete; otherwise it is continued by the next call, which must pass the same
+/// version.
+pub fn run(store: &mut dyn Storage, from: &Version, limit: usize) -> ContractResult<bool> {
+  let progress = PROGRESS.may_load(store)?.unwrap_or_default();
+  let mut cursor = progress.cursor.map(|cursor| cursor.to_vec());
+  let mut budget = limit.max(1);
+
+  for (idx, (version, step)) in MIGRATIONS.iter().enumerate().skip(progress.step) {
+    if *from >= Version::parse(version)? {
+      continue;
+    }
+    if budget == 0 {
+      PROGRESS.save(store, &Progress { step: idx, cursor: None })?;
+      return Ok(false);
+    }
+
+    let batch = step(store, cursor.take(), budget)?;
+    budget -= batch.processed;
+    if let Some(cursor) = batch.cursor {
+      PROGRESS.save(store, &Progress { step: idx, cursor: Some(cursor.into()) })?;
+      return Ok(false);
+    }
+  }
+
+  PROGRESS.remove(store);
+  Ok(true)
+}
+
+/// Whether a migration has been started but not yet completed, during which the storage layout is
+/// inconsistent.
+pub fn is_pending(store: &dyn Storage) -> bool {
+  PROGRESS.exists(store)
+}
+
+fn start_after<'a, K: PrimaryKey<'a>>(cursor: Option<Vec<u8>>) -> Option<Bound<'a, K>> {
+  cursor.map(Bound::ExclusiveRaw)
+}
+
+mod v0_2_0 {
+  use super::*;
+
+  // fees as stored by 0.1.x, which also held the admin.
+  #[derive(Serialize, Deserialize)]
+  struct LegacyFees {
+    admin: Option<Addr>,
+    store_keys: Uint128,
+    store_notes: Uint128,
+    denom: String,
+    burn_fees: bool,
+  }
+
+  const LEGACY_FEES: Item<LegacyFees> = Item::new("fees");
+
+  /// Move the admin out of the fees into the separate ownership config.
+  pub fn split_ownership(store: &mut dyn Storage, _cursor: Option<Vec<u8>>, _limit: usize) -> ContractResult<Batch> {
+    let legacy = LEGACY_FEES.load(store)?;
+    OWNERSHIP.save(store, &Ownership {
+      owner: legacy.admin,
+      pending_owner: None,
+      pending_expiry: None,
+    })?;
+    FEES.save(store, &Fees {
+      store_keys: legacy.store_keys,
+      store_notes: legacy.store_notes,
+      denom: legacy.denom,
+      burn_fees: legacy.burn_fees,
+      beneficiaries: vec![],
+      accumulate: false,
+      update_delay: 0,
+    })?;
+    Ok(Batch { processed: 1, cursor: None })
+  }
+
+  /// Note metadata did not track the last activity, which is now also indexed per recipient.
+  pub fn index_conversations(store: &mut dyn Storage, cursor: Option<Vec<u8>>, limit: usize) -> ContractResult<Batch> {
+    let metas = NOTE_META
+      .range(store, start_after(cursor), None, Order::Ascending)
+      .take(limit)
+      .collect::<Result<Vec<_>, _>>()?;
+
+    let mut keys = vec![];
+    for ((recipient, sender), mut meta) in metas {
+      keys.push((recipient.clone(), sender.clone()).joined_key());
+      if meta.count == 0 {
+        continue;
+      }
+      let last = NOTES.load(store, (recipient.clone(), sender.clone(), meta.count - 1))?;
+      meta.last_timestamp = last.timestamp;
+      NOTE_META.save(store, (recipient.clone(), sender.clone()), &meta)?;
+      CONVERSATIONS.save(store, (recipient, last.timestamp.nanos(), sender), &Empty {})?;
+    }
+    Ok(Batch::of(keys, limit))
+  }
+
+  /// Notes are now indexed by time & block height. Legacy notes did not record their height and
+  /// are all indexed at height zero.
+  pub fn index_notes(store: &mut dyn Storage, cursor: Option<Vec<u8>>, limit: usize) -> ContractResult<Batch> {
+    let notes = NOTES
+      .range(store, start_after(cursor), None, Order::Ascending)
+      .take(limit)
+      .collect::<Result<Vec<_>, _>>()?;
+
+    let mut keys = vec![];
+    for ((recipient, sender, idx), note) in notes {
+      keys.push((recipient.clone(), sender.clone(), idx).joined_key());
+      index_note(store, recipient, sender, idx, &note)?;
+    }
+    Ok(Batch::of(keys, limit))
+  }
+
+  /// Usage statistics were not tracked before. Fee statistics cannot be recovered and start
+  /// from zero.
+  pub fn count_notes(store: &mut dyn Storage, cursor: Option<Vec<u8>>, limit: usize) -> ContractResult<Batch> {
+    let notes = NOTES
+      .range(store, start_after(cursor), None, Order::Ascending)
+      .take(limit)
+      .collect::<Result<Vec<_>, _>>()?;
+
+    let mut keys = vec![];
+    for ((recipient, sender, idx), note) in notes {
+      keys.push((recipient.clone(), sender.clone(), idx).joined_key());
+      count_note(store, recipient, sender, &note)?;
+    }
+    Ok(Batch::of(keys, limit))
+  }
+
+  pub fn count_keys(store: &mut dyn Storage, cursor: Option<Vec<u8>>, limit: usize) -> ContractResult<Batch> {
+    let keys = ENCRYPTION_KEYS
+      .keys_raw(store, start_after(cursor), None, Order::Ascending)
+      .take(limit)
+      .collect::<Vec<_>>();
+
+    let mut stats = STATS.may_load(store)?.unwrap_or_default();
+    stats.keys += keys.len() as u64;
+    STATS.save(store, &stats)?;
+    Ok(Batch::of(keys, limit))
+  }
+}
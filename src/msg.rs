@@ -1,27 +1,326 @@
 use cosmwasm_schema::{cw_serde, QueryResponses};
-use cosmwasm_std::Uint128;
+use cosmwasm_std::{to_json_binary, to_json_vec, Binary, Coin, CosmosMsg, StdResult, Timestamp, Uint128, WasmMsg};
+
+use crate::state::{Operation, Role};
 
 #[cw_serde]
 pub struct InstantiateMsg {
+  /// Initial admin, e.g. a cw3 multisig or DAO contract. Defaults to the sender.
+  pub admin: Option<String>,
   pub denom: String,
   pub store_keys_fee: Uint128,
   pub store_notes_fee: Uint128,
 }
 
+/// Storage migrations run in batches. A migration which does not complete within one batch is
+/// continued by migrating again, during which all writes are rejected.
+#[cw_serde]
+pub struct MigrateMsg {
+  /// Maximum number of storage entries to migrate in this call.
+  pub limit: Option<u32>,
+}
+
 #[cw_serde]
 pub enum ExecuteMsg {
+  /// Propose a new admin, who must accept the transfer before it takes effect.
+  ProposeAdmin {
+    address: String,
+    expiry: Option<Timestamp>,
+  },
+  /// Accept a pending admin transfer proposed to the sender.
+  AcceptAdmin {},
+  /// Cancel a pending admin transfer.
+  CancelAdminTransfer {},
+  /// Permanently give up admin control. Fees not directed at beneficiaries are burnt thereafter.
+  RenounceAdmin {},
+  /// Grant an operator role to an address. The owner role is transferred via `ProposeAdmin` instead.
+  GrantRole {
+    address: String,
+    role: Role,
+  },
+  RevokeRole {
+    address: String,
+    role: Role,
+  },
+  /// Pause the given operations, or all of them when omitted.
+  Pause { operations: Option<Vec<Operation>> },
+  /// Unpause the given operations, or all of them when omitted.
+  Unpause { operations: Option<Vec<Operation>> },
   UpdateFees {
-    admin: Option<String>,
     store_keys: Uint128,
     store_notes: Uint128,
     denom: String,
     burn_fees: bool,
+    #[serde(default)]
+    beneficiaries: Vec<BeneficiaryMsg>,
+    #[serde(default)]
+    accumulate: bool,
   },
+  /// Schedule a fee update taking effect no sooner than the currently configured update delay.
+  ScheduleFeeUpdate {
+    fees: FeesMsg,
+    effective_at: Timestamp,
+  },
+  /// Cancel a scheduled fee update which has not taken effect yet.
+  CancelFeeUpdate {},
+  /// Assign a fee tier to an address, or reset it to regular fees.
+  SetFeeTier {
+    address: String,
+    tier: Option<crate::state::FeeTier>,
+  },
+  /// Withdraw accumulated fees of the sender, optionally to another address.
+  WithdrawFees {
+    amount: Coin,
+    to: Option<String>,
+  },
+  /// Deposit the attached funds as prepaid credits for future fees.
+  Deposit {},
+  /// Withdraw unused prepaid credits.
+  Withdraw { amount: Coin },
+  /// Offer to cover fees of the given senders from the sender's prepaid credits, and update the
+  /// sponsorship rules. Senders are only covered once they accept the offer.
+  Sponsor {
+    senders: Vec<String>,
+    per_sender_cap: Option<Coin>,
+    expires: Option<Timestamp>,
+  },
+  /// Stop covering fees of the given senders, and withdraw pending offers to them.
+  Unsponsor { senders: Vec<String> },
+  /// Accept a sponsorship offered to the sender.
+  AcceptSponsorship { sponsor: String },
+  /// Stop being covered by the given sponsor, or decline its pending offer.
+  RemoveSponsor { sponsor: String },
   UpdateKey { key: String },
+  /// Store a note for the recipient. Every subscriber is notified within the same transaction, so
+  /// the sender pays for up to 5 subscriber calls of at most 200,000 gas each.
   StoreNote {
     recipient: String,
     note: String,
   },
+  /// Register the sender, which must be a contract, to receive a `DropnoteReceiveMsg` for every
+  /// note sent to it. Removes the hook if unset.
+  SetReceiveHook { hook: Option<crate::state::ReceiveHook> },
+  /// Mark the sender's notes to the sender of this message as read, up to and including the
+  /// given note index, or all notes if unset.
+  MarkRead {
+    sender: String,
+    until: Option<u64>,
+  },
+  /// Notify a contract of every stored note with a `NoteStoredHook`, or update the gas limit of an
+  /// existing subscriber. The gas limit of at most 200,000 is paid by the sender of each note.
+  /// Requires the moderator role.
+  AddSubscriber {
+    address: String,
+    gas_limit: u64,
+  },
+  /// Remove a subscriber, refunding its deposit. Requires the moderator role.
+  RemoveSubscriber { address: String },
+  /// Subscribe the sender, which must be a contract, to every stored note. The current
+  /// subscription deposit must be attached. Only 2 of the 5 subscriber slots are open to
+  /// self-registration.
+  Subscribe { gas_limit: u64 },
+  /// Unsubscribe the sender, refunding its deposit.
+  Unsubscribe {},
+  /// Set the deposit required to `Subscribe`, or disable self-registration when unset. The deposit
+  /// must not be zero. Requires the moderator role.
+  SetSubscriptionDeposit { deposit: Option<Coin> },
+  /// Send a note to a recipient on another chain through an IBC channel to a counterpart instance.
+  /// The fee is held in escrow until the note is delivered. If delivery fails or times out, it is
+  /// refunded to the prepaid credits of whoever paid it.
+  StoreRemoteNote {
+    channel: String,
+    recipient: String,
+    note: String,
+  },
+  /// Allow IBC channels over the connection to the counterpart instance bound to the given port,
+  /// replacing any port allowed before. Remote senders and keys are attributed to the given chain
+  /// id for channels opened from then on. Requires the admin.
+  AllowCounterparty {
+    connection_id: String,
+    port_id: String,
+    chain_id: String,
+  },
+  /// Reject new IBC channels over the connection. Open channels stay connected until closed.
+  /// Requires the admin.
+  RemoveCounterparty { connection_id: String },
+  /// Look up the encryption key of an address on the chain at the other end of an IBC channel.
+  /// Once acknowledged, the result is cached and available via `QueryMsg::RemoteEncryptionKey`.
+  /// Charges the store keys fee, unless the key is still cached, in which case no funds may be
+  /// attached and nothing is sent.
+  LookupRemoteKey {
+    channel: String,
+    address: String,
+  },
+  /// Store a note signed off-chain on behalf of its sender, e.g. one without gas tokens. The fee is
+  /// paid from the funds attached by the relayer if any, otherwise covered by a sponsor of the
+  /// sender or drawn from the sender's prepaid credits.
+  RelayNote { signed: SignedNote },
+  /// Store a note in the recipient's anonymous inbox, without recording its sender. The envelope
+  /// should carry the sender's identity encrypted for the recipient. The store notes fee must be
+  /// attached, along with exactly the postage the recipient requires if any, which is credited to
+  /// the recipient's prepaid credits. Any other funds are rejected.
+  StoreSealedNote {
+    recipient: String,
+    envelope: Binary,
+  },
+  /// Require the given postage with every sealed note sent to the sender, or none if unset.
+  SetPostage { postage: Option<Coin> },
+}
+
+#[cw_serde]
+pub struct SignedNote {
+  /// Compressed secp256k1 public key of the sender, from which its address is derived.
+  pub sender_pubkey: Binary,
+  pub payload: NotePayload,
+  /// Must equal the sender's current relay nonce, see `QueryMsg::RelayNonce`.
+  pub nonce: u64,
+  pub expires: Timestamp,
+  /// Signature over the SHA-256 hash of the `RelaySignDoc` sign bytes.
+  pub signature: Binary,
+}
+
+#[cw_serde]
+pub struct NotePayload {
+  pub recipient: String,
+  pub note: String,
+}
+
+/// The document signed by the sender of a relayed note. It binds the signature to one contract on
+/// one chain.
+#[cw_serde]
+pub struct RelaySignDoc {
+  pub chain_id: String,
+  pub contract: String,
+  pub payload: NotePayload,
+  pub nonce: u64,
+  pub expires: Timestamp,
+}
+
+impl RelaySignDoc {
+  /// Compact JSON serialization of the document, with fields in declaration order.
+  pub fn sign_bytes(&self) -> StdResult<Vec<u8>> {
+    to_json_vec(self)
+  }
+}
+
+/// Sent to recipient contracts with a receive hook whenever they receive a note. Recipients must
+/// accept it as the `dropnote_receive` variant of their execute message.
+#[cw_serde]
+pub struct DropnoteReceiveMsg {
+  pub sender: String,
+  pub idx: u64,
+  pub note_ref: NoteRef,
+}
+
+/// Locates a note, e.g. to fetch it with `QueryMsg::Notes`.
+#[cw_serde]
+pub struct NoteRef {
+  /// Address of the dropnote contract storing the note.
+  pub contract: String,
+  pub recipient: String,
+  pub sender: String,
+  pub idx: u64,
+}
+
+impl DropnoteReceiveMsg {
+  pub fn into_cosmos_msg(self, contract_addr: impl Into<String>) -> StdResult<CosmosMsg> {
+    Ok(WasmMsg::Execute {
+      contract_addr: contract_addr.into(),
+      msg: to_json_binary(&ReceiverExecuteMsg::DropnoteReceive(self))?,
+      funds: vec![],
+    }.into())
+  }
+}
+
+/// Wrapper to serialize `DropnoteReceiveMsg` as a variant of the recipient's execute message.
+#[cw_serde]
+pub enum ReceiverExecuteMsg {
+  DropnoteReceive(DropnoteReceiveMsg),
+}
+
+/// Sent to subscribers whenever a note is stored. Subscribers must accept it as the
+/// `note_stored_hook` variant of their execute message.
+#[cw_serde]
+pub struct NoteStoredHook {
+  pub recipient: String,
+  pub sender: String,
+  pub idx: u64,
+}
+
+impl NoteStoredHook {
+  pub fn into_cosmos_msg(self, contract_addr: impl Into<String>) -> StdResult<CosmosMsg> {
+    Ok(WasmMsg::Execute {
+      contract_addr: contract_addr.into(),
+      msg: to_json_binary(&SubscriberExecuteMsg::NoteStoredHook(self))?,
+      funds: vec![],
+    }.into())
+  }
+}
+
+/// Wrapper to serialize `NoteStoredHook` as a variant of the subscriber's execute message.
+#[cw_serde]
+pub enum SubscriberExecuteMsg {
+  NoteStoredHook(NoteStoredHook),
+}
+
+/// Packets exchanged between dropnote instances over IBC.
+#[cw_serde]
+pub enum PacketMsg {
+  StoreNote(NotePacket),
+  KeyLookup(KeyLookupRequest),
+}
+
+#[cw_serde]
+pub struct NotePacket {
+  /// Identifies the fee escrow on the source chain.
+  pub id: u64,
+  /// Chain id of the source chain, which must match the chain id approved for the channel on the
+  /// destination.
+  pub chain_id: String,
+  pub sender: String,
+  pub recipient: String,
+  pub note: String,
+}
+
+#[cw_serde]
+pub struct KeyLookupRequest {
+  pub address: String,
+}
+
+/// Result of a `KeyLookupRequest`.
+#[cw_serde]
+pub struct KeyLookupResponse {
+  /// Chain id of the chain the key is registered on, which must match the chain id approved for
+  /// the channel.
+  pub chain_id: String,
+  pub address: String,
+  pub key: Option<Vec<u8>>,
+}
+
+/// Acknowledgement of a packet, carrying either a JSON encoded result or an error.
+#[cw_serde]
+pub enum PacketAck {
+  Result(Binary),
+  Error(String),
+}
+
+/// Result of a delivered `NotePacket`.
+#[cw_serde]
+pub struct NotePacketResult {
+  pub idx: u64,
+}
+
+/// Chain governance overrides. These bypass all admin & role checks.
+#[cw_serde]
+pub enum SudoMsg {
+  /// Reset the admin, or remove it entirely. Cancels any pending admin transfer.
+  SetAdmin { address: Option<String> },
+  /// Pause the given operations, or all operations if none are specified.
+  Pause { operations: Option<Vec<Operation>> },
+  /// Unpause the given operations, or all operations if none are specified.
+  Unpause { operations: Option<Vec<Operation>> },
+  /// Replace the fees immediately, ignoring the notice period. Cancels any scheduled fee update.
+  SetFees { fees: FeesMsg },
 }
 
 #[cw_serde]
@@ -29,27 +328,390 @@ pub enum ExecuteMsg {
 pub enum QueryMsg {
   #[returns(EncryptionKeyResponse)]
   EncryptionKey { address: String },
+  /// Encryption keys of many addresses at once. Invalid addresses and missing keys are reported
+  /// per entry.
+  #[returns(EncryptionKeysResponse)]
+  EncryptionKeys { addresses: Vec<String> },
+  /// Encryption key of an address on another chain, as cached by `ExecuteMsg::LookupRemoteKey`.
+  #[returns(RemoteEncryptionKeyResponse)]
+  RemoteEncryptionKey {
+    chain: String,
+    address: String,
+  },
+  /// Sealed notes in the recipient's inbox, oldest first.
+  #[returns(SealedNotesResponse)]
+  SealedNotes {
+    recipient: String,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+  },
+  #[returns(PostageResponse)]
+  Postage { address: String },
+  /// Nonce expected in the next note relayed on behalf of the address.
+  #[returns(u64)]
+  RelayNonce { address: String },
   #[returns(u64)]
   NoteCount {
     recipient: String,
     sender: String,
   },
-  #[returns(Vec<String>)]
+  /// Note counts of many conversations at once. Invalid addresses are reported per entry.
+  #[returns(NoteCountsResponse)]
+  NoteCounts { pairs: Vec<NotePair> },
+  /// Senders who have left notes for the recipient, ordered by address.
+  #[returns(SendersResponse)]
   Senders {
     recipient: String,
+    start_after: Option<String>,
+    limit: Option<u32>,
   },
-  #[returns(Vec<crate::state::Note>)]
+  /// Summaries of the recipient's conversations for rendering an inbox. Cursors are sender
+  /// addresses when ordered by sender, and `{nanos}/{sender}` positions when ordered by last
+  /// activity.
+  #[returns(ConversationsResponse)]
+  Conversations {
+    recipient: String,
+    start_after: Option<String>,
+    limit: Option<u32>,
+    /// Defaults to most recent activity first.
+    order_by: Option<ConversationOrder>,
+  },
+  /// Notes left by the sender for the recipient, oldest first. Cursors are note indices.
+  #[returns(NotesResponse)]
   Notes {
     recipient: String,
     sender: String,
     start_after: Option<u64>,
     limit: Option<u32>,
+    /// Only include notes within the given time and/or block height range.
+    range: Option<NoteRange>,
+  },
+  #[returns(crate::state::Ownership)]
+  Ownership {},
+  #[returns(RolesResponse)]
+  Roles { address: String },
+  #[returns(RoleMembersResponse)]
+  RoleMembers {
+    role: Role,
+    start_after: Option<String>,
+    limit: Option<u32>,
   },
-  #[returns(crate::state::Fees)]
+  #[returns(PausedResponse)]
+  Paused {},
+  #[returns(FeesResponse)]
   Fees {},
+  #[returns(EffectiveFeesResponse)]
+  EffectiveFees { address: String },
+  #[returns(FeeBalanceResponse)]
+  FeeBalance { address: String },
+  #[returns(FeeStatsResponse)]
+  FeeStats {},
+  #[returns(ReceiveHookResponse)]
+  ReceiveHook { address: String },
+  #[returns(SubscribersResponse)]
+  Subscribers {
+    start_after: Option<String>,
+    limit: Option<u32>,
+  },
+  #[returns(SubscriptionDepositResponse)]
+  SubscriptionDeposit {},
+  /// IBC channels connected to counterpart instances on other chains.
+  #[returns(ChannelsResponse)]
+  Channels {},
+  /// Counterpart instances IBC channels may be opened to, by connection.
+  #[returns(CounterpartiesResponse)]
+  Counterparties {},
+  /// Contract-wide usage statistics.
+  #[returns(StatsResponse)]
+  Stats {},
+  #[returns(CreditsResponse)]
+  Credits { address: String },
+  #[returns(SponsorResponse)]
+  Sponsor { address: String },
+  #[returns(SponsorshipResponse)]
+  Sponsorship {
+    sponsor: String,
+    sender: String,
+  },
+}
+
+#[cw_serde]
+pub struct FeesMsg {
+  pub store_keys: Uint128,
+  pub store_notes: Uint128,
+  pub denom: String,
+  #[serde(default)]
+  pub burn_fees: bool,
+  #[serde(default)]
+  pub beneficiaries: Vec<BeneficiaryMsg>,
+  #[serde(default)]
+  pub accumulate: bool,
+  /// Minimum notice in seconds for subsequent fee updates.
+  #[serde(default)]
+  pub update_delay: u64,
+}
+
+#[cw_serde]
+pub struct BeneficiaryMsg {
+  /// Recipient of this share. Burnt when omitted.
+  pub address: Option<String>,
+  /// Share of every collected fee in basis points. All shares must sum to 10000.
+  pub bps: u16,
+}
+
+#[cw_serde]
+pub enum ConversationOrder {
+  /// Most recent activity first.
+  LastActivity,
+  /// Ascending by sender address.
+  Sender,
+}
+
+#[cw_serde]
+pub struct ConversationsResponse {
+  pub conversations: Vec<Conversation>,
+  pub next_cursor: Option<String>,
+}
+
+#[cw_serde]
+pub struct Conversation {
+  pub sender: String,
+  pub count: u64,
+  pub unread: u64,
+  pub last_timestamp: Timestamp,
+  /// Leading bytes of the most recent note, which hold its envelope header.
+  pub preview: Vec<u8>,
+}
+
+/// Bounds on the notes to include. Lower bounds are inclusive, upper bounds exclusive, such that
+/// clients can pass the time or height of their last sync as `since`.
+#[cw_serde]
+#[derive(Default)]
+pub struct NoteRange {
+  pub since: Option<Timestamp>,
+  pub until: Option<Timestamp>,
+  pub since_height: Option<u64>,
+  pub until_height: Option<u64>,
+}
+
+#[cw_serde]
+pub struct NotesResponse {
+  pub notes: Vec<crate::state::Note>,
+  pub next_cursor: Option<u64>,
+}
+
+#[cw_serde]
+pub struct SealedNotesResponse {
+  pub notes: Vec<crate::state::SealedNote>,
+  /// Total number of sealed notes in the inbox.
+  pub count: u64,
+  pub next_cursor: Option<u64>,
+}
+
+#[cw_serde]
+pub struct PostageResponse {
+  pub postage: Option<Coin>,
+}
+
+#[cw_serde]
+pub struct SendersResponse {
+  pub senders: Vec<SenderSummary>,
+  pub next_cursor: Option<String>,
+}
+
+#[cw_serde]
+pub struct SenderSummary {
+  pub sender: String,
+  /// Number of notes left by the sender.
+  pub count: u64,
+  /// Time of the most recent note.
+  pub last_timestamp: Timestamp,
+}
+
+#[cw_serde]
+pub struct RolesResponse {
+  pub roles: Vec<Role>,
+}
+
+#[cw_serde]
+pub struct RoleMembersResponse {
+  pub members: Vec<String>,
+  pub next_cursor: Option<String>,
+}
+
+#[cw_serde]
+pub struct PausedResponse {
+  pub operations: Vec<Operation>,
 }
 
 #[cw_serde]
 pub struct EncryptionKeyResponse {
   pub key: Option<Vec<u8>>,
 }
+
+/// A cached remote key. All fields are unset if the key has never been looked up or the cached
+/// result has expired.
+#[cw_serde]
+pub struct RemoteEncryptionKeyResponse {
+  pub key: Option<Vec<u8>>,
+  /// Channel to send notes to the address through.
+  pub channel: Option<String>,
+  pub expires: Option<Timestamp>,
+}
+
+#[cw_serde]
+pub struct EncryptionKeysResponse {
+  pub keys: Vec<EncryptionKeyEntry>,
+}
+
+/// Either `key` or `error` is set.
+#[cw_serde]
+pub struct EncryptionKeyEntry {
+  pub address: String,
+  pub key: Option<Vec<u8>>,
+  pub error: Option<String>,
+}
+
+#[cw_serde]
+pub struct NotePair {
+  pub recipient: String,
+  pub sender: String,
+}
+
+#[cw_serde]
+pub struct NoteCountsResponse {
+  pub counts: Vec<NoteCountEntry>,
+}
+
+/// Either `count` or `error` is set. Conversations without notes have a count of zero.
+#[cw_serde]
+pub struct NoteCountEntry {
+  pub recipient: String,
+  pub sender: String,
+  pub count: Option<u64>,
+  pub error: Option<String>,
+}
+
+#[cw_serde]
+pub struct FeesResponse {
+  /// Fees currently in effect.
+  pub fees: crate::state::Fees,
+  /// Fee update scheduled to take effect in the future.
+  pub scheduled: Option<crate::state::ScheduledFees>,
+}
+
+#[cw_serde]
+pub struct EffectiveFeesResponse {
+  pub denom: String,
+  pub store_keys: Uint128,
+  pub store_notes: Uint128,
+  pub tier: Option<crate::state::FeeTier>,
+}
+
+#[cw_serde]
+pub struct FeeBalanceResponse {
+  pub balance: Vec<Coin>,
+}
+
+#[cw_serde]
+pub struct FeeStatsResponse {
+  pub stats: Vec<DenomFeeStats>,
+}
+
+#[cw_serde]
+pub struct ReceiveHookResponse {
+  pub hook: Option<crate::state::ReceiveHook>,
+}
+
+#[cw_serde]
+pub struct SubscribersResponse {
+  pub subscribers: Vec<SubscriberEntry>,
+  pub next_cursor: Option<String>,
+}
+
+#[cw_serde]
+pub struct SubscriberEntry {
+  pub address: String,
+  pub gas_limit: u64,
+  pub deposit: Option<Coin>,
+  /// Consecutive failed notifications. The subscriber is removed once this reaches the limit.
+  pub failures: u32,
+}
+
+#[cw_serde]
+pub struct SubscriptionDepositResponse {
+  pub deposit: Option<Coin>,
+}
+
+#[cw_serde]
+pub struct ChannelsResponse {
+  pub channels: Vec<ChannelInfo>,
+}
+
+#[cw_serde]
+pub struct ChannelInfo {
+  pub channel_id: String,
+  pub counterparty_port: String,
+  pub counterparty_channel: String,
+  pub connection_id: String,
+  /// Chain id of the counterpart, as approved for the connection when the channel was opened.
+  pub chain_id: String,
+}
+
+#[cw_serde]
+pub struct CounterpartiesResponse {
+  pub counterparties: Vec<CounterpartyInfo>,
+}
+
+#[cw_serde]
+pub struct CounterpartyInfo {
+  pub connection_id: String,
+  pub port_id: String,
+  pub chain_id: String,
+}
+
+#[cw_serde]
+pub struct StatsResponse {
+  pub notes: u64,
+  /// Total size of all stored notes in bytes.
+  pub bytes: u64,
+  pub unique_senders: u64,
+  pub unique_recipients: u64,
+  /// Number of sealed notes, which are included in `notes` & `bytes` but count towards no sender.
+  pub sealed_notes: u64,
+  /// Number of addresses with a registered encryption key.
+  pub keys: u64,
+  /// Lifetime fees collected per denom.
+  pub fees_collected: Vec<Coin>,
+}
+
+#[cw_serde]
+pub struct DenomFeeStats {
+  pub denom: String,
+  pub collected: Uint128,
+  pub burned: Uint128,
+  /// Fees sent out to beneficiaries directly.
+  pub distributed: Uint128,
+  /// Accumulated fees withdrawn by beneficiaries.
+  pub withdrawn: Uint128,
+}
+
+#[cw_serde]
+pub struct CreditsResponse {
+  pub credits: Vec<Coin>,
+}
+
+#[cw_serde]
+pub struct SponsorResponse {
+  pub rules: Option<crate::state::Sponsor>,
+  /// Prepaid credits of the sponsor, which cover the fees of sponsored senders.
+  pub budget: Vec<Coin>,
+}
+
+#[cw_serde]
+pub struct SponsorshipResponse {
+  pub sponsored: bool,
+  /// Whether an offer is pending acceptance by the sender.
+  pub offered: bool,
+  pub spent: Uint128,
+}
@@ -0,0 +1,18 @@
+pub const DEFAULT_LIMIT: u32 = 10;
+pub const MAX_LIMIT: u32 = 30;
+
+/// Clamp the requested page size to the allowed maximum.
+pub fn page_limit(limit: Option<u32>) -> usize {
+  limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize
+}
+
+/// Trim a page loaded with one item more than `limit` down to size. If that extra item exists,
+/// another page follows, which starts exclusively after the returned cursor.
+pub fn paginate<T, C>(mut items: Vec<T>, limit: usize, cursor: impl FnOnce(&T) -> C) -> (Vec<T>, Option<C>) {
+  if items.len() <= limit {
+    return (items, None);
+  }
+  items.truncate(limit);
+  let next = items.last().map(cursor);
+  (items, next)
+}
@@ -1,85 +1,877 @@
-use cosmwasm_std::{Addr, Order, Storage, Timestamp, Uint128};
-use cw_storage_plus::{Item, Map};
+use cosmwasm_std::{Addr, Coin, Empty, IbcChannel, Order, StdResult, Storage, Timestamp, Uint128};
+use cw_storage_plus::{Bound, Item, Map};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-const FEES: Item<Fees> = Item::new("fees");
-const ENCRYPTION_KEYS: Map<Addr, Vec<u8>> = Map::new("state");
+use crate::ContractError;
 
-const NOTE_META: Map<(Addr, Addr), NoteMeta> = Map::new("note_counts");
-const NOTES: Map<(Addr, Addr, u64), Note> = Map::new("notes");
+pub(crate) const FEES: Item<Fees> = Item::new("fees");
+const SCHEDULED_FEES: Item<ScheduledFees> = Item::new("scheduled_fees");
+pub(crate) const OWNERSHIP: Item<Ownership> = Item::new("ownership");
+const ROLES: Map<(String, Addr), Empty> = Map::new("roles");
+const PAUSED: Map<String, Empty> = Map::new("paused");
+pub(crate) const ENCRYPTION_KEYS: Map<Addr, Vec<u8>> = Map::new("state");
 
-#[derive(Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub(crate) const NOTE_META: Map<(Addr, Addr), NoteMeta> = Map::new("note_counts");
+pub(crate) const NOTES: Map<(Addr, Addr, u64), Note> = Map::new("notes");
+// first note index per (recipient, sender, timestamp in nanos)
+pub(crate) const NOTE_TIMES: Map<(Addr, Addr, u64), u64> = Map::new("note_times");
+// first note index per (recipient, sender, block height)
+pub(crate) const NOTE_HEIGHTS: Map<(Addr, Addr, u64), u64> = Map::new("note_heights");
+pub(crate) const STATS: Item<Stats> = Item::new("stats");
+// addresses which have ever sent a note, counted as unique senders
+pub(crate) const SENDERS: Map<Addr, Empty> = Map::new("senders");
+// addresses which have ever received a note, counted as unique recipients
+pub(crate) const RECIPIENTS: Map<Addr, Empty> = Map::new("recipients");
+// conversations of a recipient by (last activity in nanos, sender)
+pub(crate) const CONVERSATIONS: Map<(Addr, u64, Addr), Empty> = Map::new("conversations");
+// sealed notes by (recipient, seq). their senders are only revealed to the recipient.
+const SEALED_NOTES: Map<(Addr, u64), SealedNote> = Map::new("sealed_notes");
+// number of sealed notes per recipient, which is the next seq
+const SEALED_COUNTS: Map<Addr, u64> = Map::new("sealed_counts");
+// postage a recipient requires with every sealed note
+const POSTAGE: Map<Addr, Coin> = Map::new("postage");
+
+const FEE_BALANCES: Map<(Addr, String), Uint128> = Map::new("fee_balances");
+const FEE_STATS: Map<String, FeeStats> = Map::new("fee_stats");
+
+const CREDITS: Map<(Addr, String), Uint128> = Map::new("credits");
+
+const SPONSORS: Map<Addr, Sponsor> = Map::new("sponsors");
+// fees spent per (sender, sponsor)
+const SPONSORSHIPS: Map<(Addr, Addr), Uint128> = Map::new("sponsorships");
+// sponsorships offered per (sender, sponsor), pending acceptance by the sender
+const SPONSORSHIP_OFFERS: Map<(Addr, Addr), Empty> = Map::new("sponsorship_offers");
+
+const FEE_TIERS: Map<Addr, FeeTier> = Map::new("fee_tiers");
+
+const RECEIVE_HOOKS: Map<Addr, ReceiveHook> = Map::new("receive_hooks");
+
+// contracts notified of every stored note
+const SUBSCRIBERS: Map<Addr, Subscriber> = Map::new("subscribers");
+// subscriber addresses by id, which identifies subscribers in replies
+const SUBSCRIBER_IDS: Map<u64, Addr> = Map::new("subscriber_ids");
+const SUBSCRIBER_SEQ: Item<u64> = Item::new("subscriber_seq");
+// deposit required to subscribe without a moderator. self-registration is disabled when unset.
+const SUBSCRIPTION_DEPOSIT: Item<Coin> = Item::new("subscription_deposit");
+
+// ports of counterpart instances channels may be opened to, by connection id
+const IBC_COUNTERPARTIES: Map<String, Counterparty> = Map::new("ibc_counterparties");
+// connected IBC channels to counterpart instances by local channel id
+const IBC_CHANNELS: Map<String, IbcChannel> = Map::new("ibc_channels");
+// chain id of the counterpart of each connected channel, as approved for its connection at handshake
+const IBC_CHANNEL_CHAINS: Map<String, String> = Map::new("ibc_channel_chains");
+// fees of notes sent over IBC, held until delivery, by (channel id, packet id)
+const IBC_ESCROWS: Map<(String, u64), Escrow> = Map::new("ibc_escrows");
+const IBC_PACKET_SEQ: Item<u64> = Item::new("ibc_packet_seq");
+// next nonce expected in notes relayed on behalf of a sender
+const RELAY_NONCES: Map<Addr, u64> = Map::new("relay_nonces");
+
+// encryption keys looked up on other chains by (chain id, address)
+const REMOTE_KEYS: Map<(String, String), RemoteKey> = Map::new("remote_keys");
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct Ownership {
+  // the address allowed to administer the contract. will automatically receive fees unless
+  // beneficiaries are configured. when unset, fees are burnt instead.
+  pub owner: Option<Addr>,
+  // the address ownership has been proposed to, which must accept it before it takes effect.
+  pub pending_owner: Option<Addr>,
+  // time from which the pending transfer can no longer be accepted.
+  pub pending_expiry: Option<Timestamp>,
+}
+
+// operator roles. the owner implicitly holds every role and is the only one to grant them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+  Owner,
+  FeeManager,
+  Moderator,
+  Pauser,
+}
+
+impl Role {
+  pub const ALL: [Role; 4] = [Role::Owner, Role::FeeManager, Role::Moderator, Role::Pauser];
+
+  pub fn as_str(&self) -> &'static str {
+    match self {
+      Role::Owner => "owner",
+      Role::FeeManager => "fee_manager",
+      Role::Moderator => "moderator",
+      Role::Pauser => "pauser",
+    }
+  }
+}
+
+impl std::fmt::Display for Role {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.write_str(self.as_str())
+  }
+}
+
+// write operations which can be paused individually.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Operation {
+  StoreNote,
+  UpdateKey,
+  Deposit,
+  Withdraw,
+  Sponsor,
+  WithdrawFees,
+  SetReceiveHook,
+  MarkRead,
+  Subscribe,
+  LookupRemoteKey,
+  SetPostage,
+}
+
+impl Operation {
+  pub const ALL: [Operation; 11] = [
+    Operation::StoreNote,
+    Operation::UpdateKey,
+    Operation::Deposit,
+    Operation::Withdraw,
+    Operation::Sponsor,
+    Operation::WithdrawFees,
+    Operation::SetReceiveHook,
+    Operation::MarkRead,
+    Operation::Subscribe,
+    Operation::LookupRemoteKey,
+    Operation::SetPostage,
+  ];
+
+  pub fn as_str(&self) -> &'static str {
+    match self {
+      Operation::StoreNote => "store_note",
+      Operation::UpdateKey => "update_key",
+      Operation::Deposit => "deposit",
+      Operation::Withdraw => "withdraw",
+      Operation::Sponsor => "sponsor",
+      Operation::WithdrawFees => "withdraw_fees",
+      Operation::SetReceiveHook => "set_receive_hook",
+      Operation::MarkRead => "mark_read",
+      Operation::Subscribe => "subscribe",
+      Operation::LookupRemoteKey => "lookup_remote_key",
+      Operation::SetPostage => "set_postage",
+    }
+  }
+}
+
+impl std::fmt::Display for Operation {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.write_str(self.as_str())
+  }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
 pub struct Fees {
-  // the address allowed to adjust fees. will automatically receive fees.
-  // when unset, fees are burnt instead.
-  pub admin: Option<Addr>,
   pub store_keys: Uint128,
   pub store_notes: Uint128,
   pub denom: String,
-  // whether to burn fees rather than send to admin.
+  // whether to burn fees rather than send to the owner.
   pub burn_fees: bool,
+  // split of collected fees. when empty, the owner receives the entire fee.
+  #[serde(default)]
+  pub beneficiaries: Vec<Beneficiary>,
+  // whether to accumulate fees in the contract for beneficiaries to withdraw
+  // rather than sending them out with every transaction. burnt shares are still burnt immediately.
+  #[serde(default)]
+  pub accumulate: bool,
+  // minimum notice in seconds before a fee update takes effect. when non-zero, fees can only be
+  // changed through scheduled updates.
+  #[serde(default)]
+  pub update_delay: u64,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct ScheduledFees {
+  pub fees: Fees,
+  pub effective_at: Timestamp,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct Beneficiary {
+  // recipient of this share. when unset, the share is burnt.
+  pub address: Option<Addr>,
+  // share of every collected fee in basis points. all shares must sum to 10000.
+  pub bps: u16,
 }
 
-#[derive(Clone, PartialEq, Serialize, Deserialize)]
+// contract-wide usage counters.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct Stats {
+  pub notes: u64,
+  // total size of all stored notes in bytes.
+  pub bytes: u64,
+  pub senders: u64,
+  pub recipients: u64,
+  // number of sealed notes, which are included in the notes & bytes but have no sender.
+  #[serde(default)]
+  pub sealed_notes: u64,
+  // number of addresses with a registered encryption key.
+  pub keys: u64,
+}
+
+// lifetime fee statistics of a single denom, such that collected - burned - distributed - withdrawn
+// equals the outstanding fee balances.
+#[derive(Clone, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct FeeStats {
+  pub collected: Uint128,
+  pub burned: Uint128,
+  // fees sent out to beneficiaries directly, rather than accumulated.
+  #[serde(default)]
+  pub distributed: Uint128,
+  // accumulated fees withdrawn by beneficiaries.
+  pub withdrawn: Uint128,
+}
+
+// fee tier of an individual address, applied to all fees it pays.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum FeeTier {
+  Exempt {},
+  // discount on all fees in basis points.
+  Discount { bps: u16 },
+}
+
+// receive hook of a recipient contract, notified of every note it receives.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct ReceiveHook {
+  // how to treat a failing hook.
+  pub on_error: HookFailure,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum HookFailure {
+  // revert the entire transaction, such that the note is not stored.
+  Revert,
+  // store the note regardless.
+  Ignore,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct Subscriber {
+  pub id: u64,
+  // gas available to every notification.
+  pub gas_limit: u64,
+  // deposit paid by self-registered subscribers. refunded upon regular removal, but forfeited
+  // when the subscriber is removed for failing repeatedly.
+  pub deposit: Option<Coin>,
+  // number of consecutive failed notifications.
+  pub failures: u32,
+}
+
+// counterpart instance IBC channels may be opened to over a connection, as approved by the admin.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct Counterparty {
+  pub port_id: String,
+  // chain id of the counterpart's chain, which identifies remote senders & keys.
+  pub chain_id: String,
+}
+
+// fee of a note sent over IBC, released upon delivery or refunded otherwise.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct Escrow {
+  pub sender: Addr,
+  // sponsor who covered the fee, refunded instead of the sender.
+  pub sponsor: Option<Addr>,
+  pub fee: Coin,
+}
+
+// result of a key lookup on another chain.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct RemoteKey {
+  // unset if the address has not registered a key on its chain.
+  pub key: Option<Vec<u8>>,
+  // local channel the key was looked up through.
+  pub channel: String,
+  pub fetched_at: Timestamp,
+}
+
+// rules of a sponsor covering fees for other senders. the sponsor's budget are its prepaid credits.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct Sponsor {
+  // maximum total fees covered per sender. fees of other denoms are not covered. unlimited when unset.
+  pub per_sender_cap: Option<Coin>,
+  // time from which the sponsor no longer covers any fees.
+  pub expires: Option<Timestamp>,
+}
+
+#[derive(Clone, Default, PartialEq, Serialize, Deserialize)]
 pub struct NoteMeta {
   pub count: u64,
+  // time of the most recent note.
+  #[serde(default)]
+  pub last_timestamp: Timestamp,
+  // number of notes the recipient has read, oldest first.
+  #[serde(default)]
+  pub read: u64,
 }
 
-#[derive(Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
 pub struct Note {
   pub sender: Addr,
   pub note: Vec<u8>,
   pub timestamp: Timestamp,
+  // block height at which the note was stored. zero for notes stored before 0.2.0.
+  #[serde(default)]
+  pub height: u64,
+}
+
+// note stored in a recipient's anonymous inbox.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct SealedNote {
+  // encrypted note, including the sender's identity.
+  pub envelope: Vec<u8>,
+  pub timestamp: Timestamp,
+  pub height: u64,
 }
 
-pub fn load_fees(store: &dyn Storage) -> crate::ContractResult<Fees> {
-  Ok(FEES.load(store)?)
+/// Load the fees in effect at the given time. A scheduled update which has come into effect is
+/// taken into account even before it has been applied.
+pub fn load_fees(store: &dyn Storage, now: Timestamp) -> crate::ContractResult<Fees> {
+  match SCHEDULED_FEES.may_load(store)? {
+    Some(scheduled) if now >= scheduled.effective_at => Ok(scheduled.fees),
+    _ => Ok(FEES.load(store)?),
+  }
 }
 
 pub fn save_fees(store: &mut dyn Storage, fees: &Fees) -> crate::ContractResult<()> {
   Ok(FEES.save(store, fees)?)
 }
 
+/// Load the scheduled fee update, if it has not come into effect yet.
+pub fn load_scheduled_fees(store: &dyn Storage, now: Timestamp) -> crate::ContractResult<Option<ScheduledFees>> {
+  Ok(SCHEDULED_FEES.may_load(store)?.filter(|scheduled| now < scheduled.effective_at))
+}
+
+pub fn save_scheduled_fees(store: &mut dyn Storage, scheduled: Option<&ScheduledFees>) -> crate::ContractResult<()> {
+  match scheduled {
+    Some(scheduled) => SCHEDULED_FEES.save(store, scheduled)?,
+    None => SCHEDULED_FEES.remove(store),
+  }
+  Ok(())
+}
+
+/// Persist a scheduled fee update once it has come into effect.
+pub fn apply_scheduled_fees(store: &mut dyn Storage, now: Timestamp) -> crate::ContractResult<()> {
+  if let Some(scheduled) = SCHEDULED_FEES.may_load(store)? {
+    if now >= scheduled.effective_at {
+      FEES.save(store, &scheduled.fees)?;
+      SCHEDULED_FEES.remove(store);
+    }
+  }
+  Ok(())
+}
+
+pub fn load_ownership(store: &dyn Storage) -> crate::ContractResult<Ownership> {
+  Ok(OWNERSHIP.load(store)?)
+}
+
+pub fn save_ownership(store: &mut dyn Storage, ownership: &Ownership) -> crate::ContractResult<()> {
+  Ok(OWNERSHIP.save(store, ownership)?)
+}
+
+pub fn has_role(store: &dyn Storage, role: Role, addr: Addr) -> crate::ContractResult<bool> {
+  Ok(ROLES.has(store, (role.as_str().to_string(), addr)))
+}
+
+pub fn grant_role(store: &mut dyn Storage, role: Role, addr: Addr) -> crate::ContractResult<()> {
+  Ok(ROLES.save(store, (role.as_str().to_string(), addr), &Empty {})?)
+}
+
+pub fn revoke_role(store: &mut dyn Storage, role: Role, addr: Addr) {
+  ROLES.remove(store, (role.as_str().to_string(), addr))
+}
+
+pub fn find_role_members(store: &dyn Storage, role: Role, start_after: Option<Addr>, limit: usize) -> crate::ContractResult<Vec<Addr>> {
+  let members = ROLES.prefix(role.as_str().to_string())
+    .keys(store, start_after.map(Bound::exclusive), None, Order::Ascending)
+    .take(limit)
+    .collect::<Result<_, _>>()?;
+  Ok(members)
+}
+
+pub fn is_paused(store: &dyn Storage, operation: Operation) -> bool {
+  PAUSED.has(store, operation.as_str().to_string())
+}
+
+pub fn set_paused(store: &mut dyn Storage, operation: Operation, paused: bool) -> crate::ContractResult<()> {
+  if paused {
+    PAUSED.save(store, operation.as_str().to_string(), &Empty {})?;
+  } else {
+    PAUSED.remove(store, operation.as_str().to_string());
+  }
+  Ok(())
+}
+
+pub fn load_fee_balances(store: &dyn Storage, addr: Addr) -> crate::ContractResult<Vec<Coin>> {
+  load_balances(&FEE_BALANCES, store, addr)
+}
+
+pub fn add_fee_balance(store: &mut dyn Storage, addr: Addr, coin: &Coin) -> crate::ContractResult<()> {
+  add_balance(&FEE_BALANCES, store, addr, coin)
+}
+
+pub fn sub_fee_balance(store: &mut dyn Storage, addr: Addr, coin: &Coin) -> crate::ContractResult<()> {
+  sub_balance(&FEE_BALANCES, store, addr, coin)
+}
+
+pub fn load_credits(store: &dyn Storage, addr: Addr) -> crate::ContractResult<Vec<Coin>> {
+  load_balances(&CREDITS, store, addr)
+}
+
+pub fn add_credits(store: &mut dyn Storage, addr: Addr, coin: &Coin) -> crate::ContractResult<()> {
+  add_balance(&CREDITS, store, addr, coin)
+}
+
+pub fn sub_credits(store: &mut dyn Storage, addr: Addr, coin: &Coin) -> crate::ContractResult<()> {
+  sub_balance(&CREDITS, store, addr, coin)
+}
+
+pub fn load_credit(store: &dyn Storage, addr: Addr, denom: &str) -> crate::ContractResult<Uint128> {
+  Ok(CREDITS.may_load(store, (addr, denom.to_string()))?.unwrap_or_default())
+}
+
+pub fn load_fee_tier(store: &dyn Storage, addr: Addr) -> crate::ContractResult<Option<FeeTier>> {
+  Ok(FEE_TIERS.may_load(store, addr)?)
+}
+
+pub fn save_fee_tier(store: &mut dyn Storage, addr: Addr, tier: Option<&FeeTier>) -> crate::ContractResult<()> {
+  match tier {
+    Some(tier) => FEE_TIERS.save(store, addr, tier)?,
+    None => FEE_TIERS.remove(store, addr),
+  }
+  Ok(())
+}
+
+pub fn load_receive_hook(store: &dyn Storage, addr: Addr) -> crate::ContractResult<Option<ReceiveHook>> {
+  Ok(RECEIVE_HOOKS.may_load(store, addr)?)
+}
+
+pub fn save_receive_hook(store: &mut dyn Storage, addr: Addr, hook: Option<&ReceiveHook>) -> crate::ContractResult<()> {
+  match hook {
+    Some(hook) => RECEIVE_HOOKS.save(store, addr, hook)?,
+    None => RECEIVE_HOOKS.remove(store, addr),
+  }
+  Ok(())
+}
+
+pub fn load_subscriber(store: &dyn Storage, addr: Addr) -> crate::ContractResult<Option<Subscriber>> {
+  Ok(SUBSCRIBERS.may_load(store, addr)?)
+}
+
+pub fn load_subscriber_by_id(store: &dyn Storage, id: u64) -> crate::ContractResult<Option<(Addr, Subscriber)>> {
+  let Some(addr) = SUBSCRIBER_IDS.may_load(store, id)? else {
+    return Ok(None);
+  };
+  let subscriber = SUBSCRIBERS.load(store, addr.clone())?;
+  Ok(Some((addr, subscriber)))
+}
+
+pub fn find_subscribers(store: &dyn Storage, start_after: Option<Addr>, limit: usize) -> crate::ContractResult<Vec<(Addr, Subscriber)>> {
+  let start = start_after.map(Bound::exclusive);
+  Ok(SUBSCRIBERS
+    .range(store, start, None, Order::Ascending)
+    .take(limit)
+    .collect::<StdResult<Vec<_>>>()?)
+}
+
+/// Register a new subscriber under a fresh id.
+pub fn add_subscriber(store: &mut dyn Storage, addr: Addr, gas_limit: u64, deposit: Option<Coin>) -> crate::ContractResult<Subscriber> {
+  let id = SUBSCRIBER_SEQ.may_load(store)?.unwrap_or_default();
+  SUBSCRIBER_SEQ.save(store, &(id + 1))?;
+
+  let subscriber = Subscriber { id, gas_limit, deposit, failures: 0 };
+  SUBSCRIBER_IDS.save(store, id, &addr)?;
+  SUBSCRIBERS.save(store, addr, &subscriber)?;
+  Ok(subscriber)
+}
+
+pub fn save_subscriber(store: &mut dyn Storage, addr: Addr, subscriber: &Subscriber) -> crate::ContractResult<()> {
+  Ok(SUBSCRIBERS.save(store, addr, subscriber)?)
+}
+
+pub fn remove_subscriber(store: &mut dyn Storage, addr: Addr) -> crate::ContractResult<Option<Subscriber>> {
+  let subscriber = SUBSCRIBERS.may_load(store, addr.clone())?;
+  if let Some(subscriber) = &subscriber {
+    SUBSCRIBER_IDS.remove(store, subscriber.id);
+    SUBSCRIBERS.remove(store, addr);
+  }
+  Ok(subscriber)
+}
+
+pub fn load_subscription_deposit(store: &dyn Storage) -> crate::ContractResult<Option<Coin>> {
+  Ok(SUBSCRIPTION_DEPOSIT.may_load(store)?)
+}
+
+pub fn save_subscription_deposit(store: &mut dyn Storage, deposit: Option<&Coin>) -> crate::ContractResult<()> {
+  match deposit {
+    Some(deposit) => SUBSCRIPTION_DEPOSIT.save(store, deposit)?,
+    None => SUBSCRIPTION_DEPOSIT.remove(store),
+  }
+  Ok(())
+}
+
+pub fn load_counterparty(store: &dyn Storage, connection_id: String) -> crate::ContractResult<Option<Counterparty>> {
+  Ok(IBC_COUNTERPARTIES.may_load(store, connection_id)?)
+}
+
+pub fn load_counterparties(store: &dyn Storage) -> crate::ContractResult<Vec<(String, Counterparty)>> {
+  Ok(IBC_COUNTERPARTIES
+    .range(store, None, None, Order::Ascending)
+    .collect::<StdResult<Vec<_>>>()?)
+}
+
+pub fn save_counterparty(store: &mut dyn Storage, connection_id: String, counterparty: &Counterparty) -> crate::ContractResult<()> {
+  Ok(IBC_COUNTERPARTIES.save(store, connection_id, counterparty)?)
+}
+
+pub fn remove_counterparty(store: &mut dyn Storage, connection_id: String) {
+  IBC_COUNTERPARTIES.remove(store, connection_id)
+}
+
+pub fn load_channel(store: &dyn Storage, channel_id: String) -> crate::ContractResult<Option<IbcChannel>> {
+  Ok(IBC_CHANNELS.may_load(store, channel_id)?)
+}
+
+/// Connected channels along with the chain id of their counterpart.
+pub fn load_channels(store: &dyn Storage) -> crate::ContractResult<Vec<(IbcChannel, String)>> {
+  IBC_CHANNELS
+    .range(store, None, None, Order::Ascending)
+    .map(|item| {
+      let (channel_id, channel) = item?;
+      Ok((channel, IBC_CHANNEL_CHAINS.may_load(store, channel_id)?.unwrap_or_default()))
+    })
+    .collect()
+}
+
+pub fn load_channel_chain(store: &dyn Storage, channel_id: String) -> crate::ContractResult<Option<String>> {
+  Ok(IBC_CHANNEL_CHAINS.may_load(store, channel_id)?)
+}
+
+pub fn save_channel(store: &mut dyn Storage, channel: &IbcChannel, chain_id: String) -> crate::ContractResult<()> {
+  IBC_CHANNEL_CHAINS.save(store, channel.endpoint.channel_id.clone(), &chain_id)?;
+  Ok(IBC_CHANNELS.save(store, channel.endpoint.channel_id.clone(), channel)?)
+}
+
+pub fn remove_channel(store: &mut dyn Storage, channel_id: String) {
+  IBC_CHANNEL_CHAINS.remove(store, channel_id.clone());
+  IBC_CHANNELS.remove(store, channel_id)
+}
+
+/// Allocate the id identifying an outgoing packet's escrow.
+pub fn next_packet_id(store: &mut dyn Storage) -> crate::ContractResult<u64> {
+  let id = IBC_PACKET_SEQ.may_load(store)?.unwrap_or_default();
+  IBC_PACKET_SEQ.save(store, &(id + 1))?;
+  Ok(id)
+}
+
+pub fn save_escrow(store: &mut dyn Storage, channel_id: String, id: u64, escrow: &Escrow) -> crate::ContractResult<()> {
+  Ok(IBC_ESCROWS.save(store, (channel_id, id), escrow)?)
+}
+
+/// Remove and return an escrow, such that it is settled at most once.
+pub fn take_escrow(store: &mut dyn Storage, channel_id: String, id: u64) -> crate::ContractResult<Option<Escrow>> {
+  let escrow = IBC_ESCROWS.may_load(store, (channel_id.clone(), id))?;
+  IBC_ESCROWS.remove(store, (channel_id, id));
+  Ok(escrow)
+}
+
+pub fn load_remote_key(store: &dyn Storage, chain_id: String, address: String) -> crate::ContractResult<Option<RemoteKey>> {
+  Ok(REMOTE_KEYS.may_load(store, (chain_id, address))?)
+}
+
+pub fn save_remote_key(store: &mut dyn Storage, chain_id: String, address: String, key: &RemoteKey) -> crate::ContractResult<()> {
+  Ok(REMOTE_KEYS.save(store, (chain_id, address), key)?)
+}
+
+pub fn load_relay_nonce(store: &dyn Storage, addr: Addr) -> crate::ContractResult<u64> {
+  Ok(RELAY_NONCES.may_load(store, addr)?.unwrap_or_default())
+}
+
+pub fn save_relay_nonce(store: &mut dyn Storage, addr: Addr, nonce: u64) -> crate::ContractResult<()> {
+  Ok(RELAY_NONCES.save(store, addr, &nonce)?)
+}
+
+pub fn load_sponsor(store: &dyn Storage, sponsor: Addr) -> crate::ContractResult<Option<Sponsor>> {
+  Ok(SPONSORS.may_load(store, sponsor)?)
+}
+
+pub fn save_sponsor(store: &mut dyn Storage, sponsor: Addr, rules: &Sponsor) -> crate::ContractResult<()> {
+  Ok(SPONSORS.save(store, sponsor, rules)?)
+}
+
+pub fn load_sponsorship(store: &dyn Storage, sender: Addr, sponsor: Addr) -> crate::ContractResult<Option<Uint128>> {
+  Ok(SPONSORSHIPS.may_load(store, (sender, sponsor))?)
+}
+
+/// Find all sponsors covering the given sender along with the fees they have spent on them so far.
+pub fn find_sponsorships(store: &dyn Storage, sender: Addr) -> crate::ContractResult<Vec<(Addr, Uint128)>> {
+  let sponsorships = SPONSORSHIPS.prefix(sender)
+    .range(store, None, None, Order::Ascending)
+    .collect::<Result<_, _>>()?;
+  Ok(sponsorships)
+}
+
+pub fn save_sponsorship(store: &mut dyn Storage, sender: Addr, sponsor: Addr, spent: Uint128) -> crate::ContractResult<()> {
+  Ok(SPONSORSHIPS.save(store, (sender, sponsor), &spent)?)
+}
+
+pub fn remove_sponsorship(store: &mut dyn Storage, sender: Addr, sponsor: Addr) {
+  SPONSORSHIPS.remove(store, (sender, sponsor))
+}
+
+pub fn count_sponsorships(store: &dyn Storage, sender: Addr) -> usize {
+  SPONSORSHIPS.prefix(sender).keys_raw(store, None, None, Order::Ascending).count()
+}
+
+pub fn has_sponsorship_offer(store: &dyn Storage, sender: Addr, sponsor: Addr) -> bool {
+  SPONSORSHIP_OFFERS.has(store, (sender, sponsor))
+}
+
+pub fn save_sponsorship_offer(store: &mut dyn Storage, sender: Addr, sponsor: Addr) -> crate::ContractResult<()> {
+  Ok(SPONSORSHIP_OFFERS.save(store, (sender, sponsor), &Empty {})?)
+}
+
+pub fn remove_sponsorship_offer(store: &mut dyn Storage, sender: Addr, sponsor: Addr) {
+  SPONSORSHIP_OFFERS.remove(store, (sender, sponsor))
+}
+
+pub fn load_fee_stats(store: &dyn Storage) -> crate::ContractResult<Vec<(String, FeeStats)>> {
+  let stats = FEE_STATS.range(store, None, None, Order::Ascending)
+    .collect::<Result<_, _>>()?;
+  Ok(stats)
+}
+
+pub fn update_fee_stats(store: &mut dyn Storage, denom: &str, action: impl FnOnce(&mut FeeStats)) -> crate::ContractResult<()> {
+  let mut stats = FEE_STATS.may_load(store, denom.to_string())?.unwrap_or_default();
+  action(&mut stats);
+  FEE_STATS.save(store, denom.to_string(), &stats)?;
+  Ok(())
+}
+
 pub fn load_enc_key(store: &dyn Storage, addr: Addr) -> crate::ContractResult<Option<Vec<u8>>> {
   Ok(ENCRYPTION_KEYS.may_load(store, addr)?)
 }
 
 pub fn save_enc_key(store: &mut dyn Storage, addr: Addr, key: &Vec<u8>) -> crate::ContractResult<()> {
+  if !ENCRYPTION_KEYS.has(store, addr.clone()) {
+    update_stats(store, |stats| stats.keys += 1)?;
+  }
   Ok(ENCRYPTION_KEYS.save(store, addr, key)?)
 }
 
-pub fn load_notes(store: &dyn Storage, recipient: Addr, sender: Addr, start_after: Option<u64>, limit: Option<u32>) -> crate::ContractResult<Vec<Note>> {
-  let meta = NOTE_META.load(store, (recipient.clone(), sender.clone()))?;
-  let start = start_after.unwrap_or(0);
-  let end = limit.map(|l| (start + l as u64).min(meta.count)).unwrap_or(meta.count);
-  let res: Vec<Note> = (start..end)
-    .map(|idx| NOTES.load(store, (recipient.clone(), sender.clone(), idx)))
+pub fn load_stats(store: &dyn Storage) -> crate::ContractResult<Stats> {
+  Ok(STATS.may_load(store)?.unwrap_or_default())
+}
+
+fn update_stats(store: &mut dyn Storage, action: impl FnOnce(&mut Stats)) -> crate::ContractResult<()> {
+  let mut stats = load_stats(store)?;
+  action(&mut stats);
+  STATS.save(store, &stats)?;
+  Ok(())
+}
+
+/// Append a sealed note to the recipient's inbox and return its seq.
+pub fn store_sealed_note(store: &mut dyn Storage, recipient: Addr, note: &SealedNote) -> crate::ContractResult<u64> {
+  let seq = load_sealed_count(store, recipient.clone())?;
+  SEALED_COUNTS.save(store, recipient.clone(), &(seq + 1))?;
+  SEALED_NOTES.save(store, (recipient.clone(), seq), note)?;
+
+  let new_recipient = !RECIPIENTS.has(store, recipient.clone());
+  if new_recipient {
+    RECIPIENTS.save(store, recipient, &Empty {})?;
+  }
+  update_stats(store, |stats| {
+    stats.notes += 1;
+    stats.bytes += note.envelope.len() as u64;
+    stats.recipients += new_recipient as u64;
+    stats.sealed_notes += 1;
+  })?;
+  Ok(seq)
+}
+
+pub fn load_sealed_count(store: &dyn Storage, recipient: Addr) -> crate::ContractResult<u64> {
+  Ok(SEALED_COUNTS.may_load(store, recipient)?.unwrap_or_default())
+}
+
+/// Load sealed notes with seqs from `start` on.
+pub fn load_sealed_notes(store: &dyn Storage, recipient: Addr, start: u64, limit: usize) -> crate::ContractResult<Vec<(u64, SealedNote)>> {
+  let notes = SEALED_NOTES.prefix(recipient)
+    .range(store, Some(Bound::inclusive(start)), None, Order::Ascending)
+    .take(limit)
+    .collect::<Result<_, _>>()?;
+  Ok(notes)
+}
+
+pub fn load_postage(store: &dyn Storage, addr: Addr) -> crate::ContractResult<Option<Coin>> {
+  Ok(POSTAGE.may_load(store, addr)?)
+}
+
+pub fn save_postage(store: &mut dyn Storage, addr: Addr, postage: Option<&Coin>) -> crate::ContractResult<()> {
+  match postage {
+    Some(postage) => POSTAGE.save(store, addr, postage)?,
+    None => POSTAGE.remove(store, addr),
+  }
+  Ok(())
+}
+
+/// Count a newly stored note in the contract-wide statistics.
+pub(crate) fn count_note(store: &mut dyn Storage, recipient: Addr, sender: Addr, note: &Note) -> crate::ContractResult<()> {
+  let new_sender = !SENDERS.has(store, sender.clone());
+  if new_sender {
+    SENDERS.save(store, sender, &Empty {})?;
+  }
+  let new_recipient = !RECIPIENTS.has(store, recipient.clone());
+  if new_recipient {
+    RECIPIENTS.save(store, recipient, &Empty {})?;
+  }
+  update_stats(store, |stats| {
+    stats.notes += 1;
+    stats.bytes += note.note.len() as u64;
+    stats.senders += new_sender as u64;
+    stats.recipients += new_recipient as u64;
+  })
+}
+
+/// Load notes with indices in `start..end`.
+pub fn load_notes(store: &dyn Storage, recipient: Addr, sender: Addr, start: u64, end: Option<u64>, limit: usize) -> crate::ContractResult<Vec<(u64, Note)>> {
+  if end.is_some_and(|end| end <= start) {
+    return Ok(vec![]);
+  }
+  let notes = NOTES.prefix((recipient, sender))
+    .range(store, Some(Bound::inclusive(start)), end.map(Bound::exclusive), Order::Ascending)
+    .take(limit)
     .collect::<Result<_, _>>()?;
-  Ok(res)
+  Ok(notes)
+}
+
+/// Find the index of the first note stored at or after the given time. Notes of a conversation
+/// are stored in chronological order, so all following notes are at or after the time, too. If
+/// there is no such note, this is the index the next note will receive.
+pub fn find_note_index_by_time(store: &dyn Storage, recipient: Addr, sender: Addr, time: Timestamp) -> crate::ContractResult<u64> {
+  find_note_index(&NOTE_TIMES, store, recipient, sender, time.nanos())
+}
+
+/// Find the index of the first note stored at or after the given block height. See
+/// `find_note_index_by_time`.
+pub fn find_note_index_by_height(store: &dyn Storage, recipient: Addr, sender: Addr, height: u64) -> crate::ContractResult<u64> {
+  find_note_index(&NOTE_HEIGHTS, store, recipient, sender, height)
+}
+
+fn find_note_index(index: &Map<(Addr, Addr, u64), u64>, store: &dyn Storage, recipient: Addr, sender: Addr, key: u64) -> crate::ContractResult<u64> {
+  let first = index.prefix((recipient.clone(), sender.clone()))
+    .range(store, Some(Bound::inclusive(key)), None, Order::Ascending)
+    .next()
+    .transpose()?;
+  match first {
+    Some((_, idx)) => Ok(idx),
+    None => Ok(NOTE_META.may_load(store, (recipient, sender))?.unwrap_or_default().count),
+  }
 }
 
 pub fn load_note_meta(store: &dyn Storage, sender: Addr, recipient: Addr) -> crate::ContractResult<NoteMeta> {
   Ok(NOTE_META.load(store, (recipient, sender))?)
 }
 
-pub fn find_senders(store: &dyn Storage, recipient: Addr) -> crate::ContractResult<Vec<Addr>> {
-  let prefix = NOTE_META.prefix(recipient.clone());
-  let senders: Vec<Addr> = prefix.keys(store, None, None, Order::Ascending)
-    .map(|item| item.unwrap())
-    .collect();
+pub fn find_note_meta(store: &dyn Storage, sender: Addr, recipient: Addr) -> crate::ContractResult<Option<NoteMeta>> {
+  Ok(NOTE_META.may_load(store, (recipient, sender))?)
+}
+
+pub fn save_note_meta(store: &mut dyn Storage, sender: Addr, recipient: Addr, meta: &NoteMeta) -> crate::ContractResult<()> {
+  Ok(NOTE_META.save(store, (recipient, sender), meta)?)
+}
+
+pub fn load_note(store: &dyn Storage, recipient: Addr, sender: Addr, idx: u64) -> crate::ContractResult<Note> {
+  Ok(NOTES.load(store, (recipient, sender, idx))?)
+}
+
+pub fn find_senders(store: &dyn Storage, recipient: Addr, start_after: Option<Addr>, limit: usize) -> crate::ContractResult<Vec<(Addr, NoteMeta)>> {
+  let senders = NOTE_META.prefix(recipient)
+    .range(store, start_after.map(Bound::exclusive), None, Order::Ascending)
+    .take(limit)
+    .collect::<Result<_, _>>()?;
   Ok(senders)
 }
 
-pub fn store_note(store: &mut dyn Storage, sender: Addr, recipient: Addr, note: Note) -> crate::ContractResult<()> {
-  let meta = NOTE_META.may_load(store, (recipient.clone(), sender.clone()))?;
-  let mut meta = match meta {
-    Some(meta) => meta,
-    None => NoteMeta { count: 0 },
-  };
+/// Find the senders of a recipient by most recent activity, newest first.
+/// Load the recipient's senders by most recent activity, starting after the given (last activity in
+/// nanoseconds, sender) position.
+pub fn find_recent_senders(store: &dyn Storage, recipient: Addr, start_after: Option<(u64, Addr)>, limit: usize) -> crate::ContractResult<Vec<(Addr, NoteMeta)>> {
+  let end = start_after.map(Bound::exclusive);
+  let senders = CONVERSATIONS.sub_prefix(recipient.clone())
+    .keys(store, None, end, Order::Descending)
+    .take(limit)
+    .map(|item| {
+      let (_, sender) = item?;
+      let meta = NOTE_META.load(store, (recipient.clone(), sender.clone()))?;
+      Ok((sender, meta))
+    })
+    .collect::<crate::ContractResult<_>>()?;
+  Ok(senders)
+}
+
+/// Store a note and return its index within the conversation.
+pub fn store_note(store: &mut dyn Storage, sender: Addr, recipient: Addr, note: Note) -> crate::ContractResult<u64> {
+  let mut meta = NOTE_META.may_load(store, (recipient.clone(), sender.clone()))?.unwrap_or_default();
+  if meta.count > 0 {
+    CONVERSATIONS.remove(store, (recipient.clone(), meta.last_timestamp.nanos(), sender.clone()));
+  }
   let idx = meta.count;
   meta.count += 1;
+  meta.last_timestamp = note.timestamp;
   NOTE_META.save(store, (recipient.clone(), sender.clone()), &meta)?;
+  CONVERSATIONS.save(store, (recipient.clone(), note.timestamp.nanos(), sender.clone()), &Empty {})?;
+  index_note(store, recipient.clone(), sender.clone(), idx, &note)?;
+  count_note(store, recipient.clone(), sender.clone(), &note)?;
   NOTES.save(store, (recipient, sender, idx), &note)?;
+  Ok(idx)
+}
+
+/// Index the note by time & block height unless an earlier note already covers them.
+pub(crate) fn index_note(store: &mut dyn Storage, recipient: Addr, sender: Addr, idx: u64, note: &Note) -> crate::ContractResult<()> {
+  let time_key = (recipient.clone(), sender.clone(), note.timestamp.nanos());
+  if !NOTE_TIMES.has(store, time_key.clone()) {
+    NOTE_TIMES.save(store, time_key, &idx)?;
+  }
+  let height_key = (recipient, sender, note.height);
+  if !NOTE_HEIGHTS.has(store, height_key.clone()) {
+    NOTE_HEIGHTS.save(store, height_key, &idx)?;
+  }
+  Ok(())
+}
+
+fn load_balances(map: &Map<(Addr, String), Uint128>, store: &dyn Storage, addr: Addr) -> crate::ContractResult<Vec<Coin>> {
+  let balances = map.prefix(addr)
+    .range(store, None, None, Order::Ascending)
+    .map(|item| item.map(|(denom, amount)| Coin { denom, amount }))
+    .collect::<Result<_, _>>()?;
+  Ok(balances)
+}
+
+fn add_balance(map: &Map<(Addr, String), Uint128>, store: &mut dyn Storage, addr: Addr, coin: &Coin) -> crate::ContractResult<()> {
+  map.update(store, (addr, coin.denom.clone()), |balance| -> StdResult<_> {
+    Ok(balance.unwrap_or_default() + coin.amount)
+  })?;
+  Ok(())
+}
+
+fn sub_balance(map: &Map<(Addr, String), Uint128>, store: &mut dyn Storage, addr: Addr, coin: &Coin) -> crate::ContractResult<()> {
+  let key = (addr, coin.denom.clone());
+  let balance = map.may_load(store, key.clone())?.unwrap_or_default();
+  if balance < coin.amount {
+    return Err(ContractError::InsufficientFunds {});
+  }
+
+  let balance = balance - coin.amount;
+  if balance.is_zero() {
+    map.remove(store, key);
+  } else {
+    map.save(store, key, &balance)?;
+  }
   Ok(())
 }